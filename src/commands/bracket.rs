@@ -0,0 +1,1051 @@
+use std::{cmp::Ordering, collections::HashMap, fmt, sync::{Arc, Mutex}};
+
+use linked_hash_map::LinkedHashMap;
+
+use super::{game::Game, participants::same_person, schedule, storage};
+
+// The id of the game between the losers of the semifinals, which Challonge also calls "3P".
+pub const THIRD_PLACE: &str = "3P";
+
+// A single elimination bracket made of linked games.
+pub struct Bracket {
+    // Every game keyed by its "[round #]-[game #]" id, in round order.
+    pub games: LinkedHashMap<String, Arc<Mutex<Game>>>,
+    // The teams in the order they were seeded.
+    pub teams: Vec<String>,
+    // The ids of the games that have a winner and when they were declared, in the order they were declared.
+    declared: Mutex<Vec<(String, u64)>>,
+    // Teams that dropped out after the tournament started. Their games are forfeited to their opponents.
+    withdrawn: Mutex<Vec<String>>,
+    // The ids of the games that were won by forfeit.
+    forfeited: Mutex<Vec<String>>,
+}
+
+// What happens to the winner of a game after it is declared.
+pub enum Declared {
+    // The winner moves on to the game with this id.
+    NextGame(String),
+    // The game was the final.
+    Champion,
+    // The game was the third place game.
+    ThirdPlace,
+}
+
+// The id of a game, the teams in each slot, the order they finished in and when the result was declared.
+pub type GameRecord = (String, Vec<String>, Vec<String>, Option<u64>);
+
+// A game a team has been in, from Bracket::path.
+pub struct TeamGame {
+    pub id: String,
+    // Everyone else in the game. Empty when nobody else has made it to the game yet.
+    pub opponents: Vec<String>,
+    // The team that finished first, which is empty when the game has not been played.
+    pub winner: String,
+    // Where the team finished in the game starting at 0, or None if it has not been played or they were left out of a heat's ranking.
+    pub place: Option<usize>,
+    // Whether the team moved on to the next game from it.
+    pub advanced: bool,
+}
+
+impl TeamGame {
+    // Checks if the game is a free-for-all heat rather than a game between two teams.
+    pub fn is_heat(&self) -> bool {
+        self.opponents.len() > 1
+    }
+
+    // Gets the text of who the team plays, like "Sam" or "Sam, Alex, Jo".
+    pub fn opponent_text(&self) -> String {
+        self.opponents.join(", ")
+    }
+}
+
+// Why a winner could not be declared.
+pub enum DeclareError {
+    UnknownGame,
+    AlreadyDeclared,
+    NotInGame,
+    NotDeclared,
+    SameWinner,
+    NoOpponent,
+    RankedTwice,
+    // The number of teams that have to be ranked.
+    NotEnoughRanked(usize),
+}
+
+impl fmt::Display for DeclareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeclareError::AlreadyDeclared => write!(f, "A winner for this game has already been declared."),
+            DeclareError::UnknownGame | DeclareError::NotInGame => write!(f, "Please enter a valid game and team for this round."),
+            DeclareError::NotDeclared => write!(f, "No winner has been declared for this game yet."),
+            DeclareError::SameWinner => write!(f, "That team is already the winner of this game."),
+            DeclareError::NoOpponent => write!(f, "That team has no opponent in this game yet."),
+            DeclareError::RankedTwice => write!(f, "Each team can only be ranked once."),
+            DeclareError::NotEnoughRanked(count) => write!(f, "Please rank at least the top {} teams of this heat.", count),
+        }
+    }
+}
+
+impl Bracket {
+    // Makes a bracket where the teams are paired up in order in the first round.
+    pub fn new(teams: &[String]) -> Self {
+        let mut first_round: LinkedHashMap<String, Arc<Mutex<Game>>> = LinkedHashMap::new();
+        for (index, team_name) in teams.iter().enumerate() {
+            let id = format!("{}-{}", 1, index / 2 + 1);
+            if index % 2 == 0 {
+                first_round.insert(id.clone(), Arc::new(Mutex::new(Game::new(id.clone(), "".to_owned(), "".to_owned(), None))));
+            }
+            let mut guard = first_round[&id].lock().expect("There was an unknown error.");
+            guard.add_team(team_name.to_owned()).expect("Teams already filled.");
+        }
+
+        let mut games = first_round.clone();
+        fill_tournament(&mut first_round, 1, &mut games);
+        Bracket { games, teams: teams.to_vec(), declared: Mutex::new(Vec::new()), withdrawn: Mutex::new(Vec::new()), forfeited: Mutex::new(Vec::new()) }
+    }
+
+    // Makes free-for-all heats of up to size teams where the best advance of each heat move on, until there are few enough for one final heat.
+    // The teams are dealt out to the first round's heats in order, so the first ones entered are in different heats.
+    // advance has to be at most half of size so every round has fewer heats than the one before.
+    pub fn heats(teams: &[String], size: usize, advance: usize) -> Self {
+        let mut games: LinkedHashMap<String, Arc<Mutex<Game>>> = LinkedHashMap::new();
+        let first_count = teams.len().div_ceil(size).max(1);
+        let mut slots: Vec<usize> = (0..first_count).map(|heat| teams.iter().skip(heat).step_by(first_count).count()).collect();
+        let mut previous: Vec<Arc<Mutex<Game>>> = Vec::new();
+        let mut round = 1;
+        loop {
+            let current: Vec<Arc<Mutex<Game>>> = slots.iter().enumerate()
+                .map(|(index, slots)| Arc::new(Mutex::new(Game::heat(format!("{}-{}", round, index + 1), *slots, advance))))
+                .collect();
+            for (index, heat) in previous.iter().enumerate() {
+                heat.lock().expect("There was an unknown error.").set_next(Some(Arc::clone(&current[index % current.len()])));
+            }
+            for heat in current.iter() {
+                let id = heat.lock().expect("There was an unknown error.").id.clone();
+                games.insert(id, Arc::clone(heat));
+            }
+            if current.len() == 1 {
+                break;
+            }
+
+            //Each heat of the next round takes the teams that move on from as many heats as fit in it
+            let next_count = current.len().div_ceil(size / advance);
+            let mut next_slots = vec![0; next_count];
+            for (index, slots) in slots.iter().enumerate() {
+                next_slots[index % next_count] += advance.min(*slots);
+            }
+            slots = next_slots;
+            previous = current;
+            round += 1;
+        }
+
+        for (index, team) in teams.iter().enumerate() {
+            let id = format!("1-{}", index % first_count + 1);
+            games[&id].lock().expect("There was an unknown error.").add_team(team.to_owned()).expect("Teams already filled.");
+        }
+        Bracket { games, teams: teams.to_vec(), declared: Mutex::new(Vec::new()), withdrawn: Mutex::new(Vec::new()), forfeited: Mutex::new(Vec::new()) }
+    }
+
+    // Adds a game between the losers of the two semifinals. Returns false if the bracket is too small to have semifinals.
+    pub fn add_third_place(&mut self) -> bool {
+        let final_id = match self.games.iter().find(|(_, game)| game.lock().expect("There was an unknown error.").next_game.is_none()) {
+            Some((id, _)) => id.clone(),
+            None => return false,
+        };
+        let semifinals: Vec<Arc<Mutex<Game>>> = self.games.values()
+            .filter(|game| {
+                let guard = game.lock().expect("There was an unknown error.");
+                guard.next_game.as_ref().map(|next_game| Arc::ptr_eq(next_game, &self.games[&final_id])).unwrap_or(false)
+            })
+            .cloned()
+            .collect();
+        //With fewer than 4 teams someone gets to the final without playing a semifinal
+        if self.teams.len() < 4 || semifinals.len() != 2 || self.games.contains_key(THIRD_PLACE) {
+            return false;
+        }
+        let third_place = Arc::new(Mutex::new(Game::new(THIRD_PLACE.to_owned(), "".to_owned(), "".to_owned(), None)));
+        for semifinal in semifinals.iter() {
+            let mut semifinal = semifinal.lock().expect("There was an unknown error.");
+            semifinal.set_loser_game(Some(Arc::clone(&third_place)));
+            //Semifinals of an imported bracket may already have been played
+            let loser = semifinal.loser();
+            if !loser.is_empty() {
+                third_place.lock().expect("There was an unknown error.").add_team(loser).expect("Teams already full.");
+            }
+        }
+        self.games.insert(THIRD_PLACE.to_owned(), third_place);
+        true
+    }
+
+    // Makes a bracket from games that are already linked and filled in, like ones read from a file.
+    // declared is the ids of the games with a winner and when they were declared, in the order they were declared,
+    // and forfeited is the ids of the games that were won by forfeit.
+    pub fn from_games(games: LinkedHashMap<String, Arc<Mutex<Game>>>, teams: Vec<String>, declared: Vec<(String, u64)>, forfeited: Vec<String>) -> Self {
+        Bracket { games, teams, declared: Mutex::new(declared), withdrawn: Mutex::new(Vec::new()), forfeited: Mutex::new(forfeited) }
+    }
+
+    // Makes the text of the bracket. Games that nobody has made it to yet are left out.
+    pub fn text(&self) -> String {
+        let withdrawn = self.withdrawn.lock().expect("There was an unknown error.").clone();
+        let forfeited = self.forfeited.lock().expect("There was an unknown error.").clone();
+        let mut out: String = String::new();
+        for (key, game) in self.games.iter() {
+            let guard = game.lock().expect("There was an unknown error.");
+            if guard.entrants().is_empty() {
+                continue;
+            }
+            let start = guard.start.map(|start| format!(" ({})", schedule::timestamp(start))).unwrap_or_default();
+            let forfeit = forfeited.contains(key);
+            out.push_str(format!("\n{}{}:\n", game_name(key), start).as_str());
+            //Heats list everyone in the order they finished once they are declared
+            let teams = if guard.teams.len() > 2 && !guard.ranking.is_empty() {
+                let mut teams = guard.ranking.clone();
+                teams.extend(guard.entrants().into_iter().filter(|team| !guard.ranking.contains(team)));
+                teams
+            } else {
+                guard.teams.clone()
+            };
+            for team in teams.iter() {
+                out.push_str(format!("\t{}\n", slot_text(team, &guard, forfeit, &withdrawn)).as_str());
+            }
+        }
+        out
+    }
+
+    // Declares the winner of the game with the id and moves them on to their next game.
+    pub fn declare(&self, id: &str, winner: &str) -> Result<Declared, DeclareError> {
+        let game = self.games.get(id).ok_or(DeclareError::UnknownGame)?;
+        let mut game = game.lock().expect("There was an unknown error.");
+        if !game.ranking.is_empty() {
+            return Err(DeclareError::AlreadyDeclared);
+        }
+        if winner.is_empty() || !game.teams.iter().any(|team| team == winner) {
+            return Err(DeclareError::NotInGame);
+        }
+
+        let next_id = game.next_game.as_ref().map(|next_game| next_game.lock().expect("There was an unknown error.").id.clone());
+        game.set_winner(winner.to_owned());
+        self.declared.lock().expect("There was an unknown error.").push((id.to_owned(), storage::now()));
+        Ok(match next_id {
+            Some(next_id) => Declared::NextGame(next_id),
+            None if id == THIRD_PLACE => Declared::ThirdPlace,
+            None => Declared::Champion,
+        })
+    }
+
+    // Checks if the final has been played, and the third place game if there is one.
+    pub fn is_finished(&self) -> bool {
+        self.games.values().all(|game| {
+            let game = game.lock().expect("There was an unknown error.");
+            game.next_game.is_some() || !game.ranking.is_empty()
+        })
+    }
+
+    // Declares the order the teams of a heat finished in, best first, and moves the ones that advance on to their next heat.
+    // At least the teams that advance have to be ranked, or the winner of the final heat. Returns the ranking as the teams are named in the heat.
+    pub fn declare_ranking(&self, id: &str, names: &[String]) -> Result<(Vec<String>, Declared), DeclareError> {
+        let game = self.games.get(id).ok_or(DeclareError::UnknownGame)?;
+        let mut game = game.lock().expect("There was an unknown error.");
+        if !game.ranking.is_empty() {
+            return Err(DeclareError::AlreadyDeclared);
+        }
+        let entrants = game.entrants();
+        let mut ranking: Vec<String> = Vec::new();
+        for name in names.iter() {
+            match entrants.iter().find(|team| same_person(team, name)) {
+                Some(team) if ranking.contains(team) => return Err(DeclareError::RankedTwice),
+                Some(team) => ranking.push(team.clone()),
+                None => return Err(DeclareError::NotInGame),
+            }
+        }
+        let needed = if game.next_game.is_some() { game.advance.min(entrants.len()) } else { 1 };
+        if ranking.len() < needed {
+            return Err(DeclareError::NotEnoughRanked(needed));
+        }
+
+        let next_id = game.next_game.as_ref().map(|next_game| next_game.lock().expect("There was an unknown error.").id.clone());
+        game.declare(ranking.clone());
+        self.declared.lock().expect("There was an unknown error.").push((id.to_owned(), storage::now()));
+        Ok((ranking, match next_id {
+            Some(next_id) => Declared::NextGame(next_id),
+            None => Declared::Champion,
+        }))
+    }
+
+    // Declares the opponent of a team that forfeits a game the winner. Returns the winner and what happens to them.
+    pub fn forfeit(&self, id: &str, team: &str) -> Result<(String, Declared), DeclareError> {
+        let winner = self.opponent(id, team)?;
+        let declared = self.declare(id, &winner)?;
+        self.forfeited.lock().expect("There was an unknown error.").push(id.to_owned());
+        Ok((winner, declared))
+    }
+
+    // Gets who a team plays in a game that has not been played.
+    pub fn opponent(&self, id: &str, team: &str) -> Result<String, DeclareError> {
+        let game = self.games.get(id).ok_or(DeclareError::UnknownGame)?;
+        let game = game.lock().expect("There was an unknown error.");
+        if !game.ranking.is_empty() {
+            return Err(DeclareError::AlreadyDeclared);
+        }
+        let opponent = if !game.top_team().is_empty() && same_person(game.top_team(), team) {
+            game.bottom_team().to_owned()
+        } else if !game.bottom_team().is_empty() && same_person(game.bottom_team(), team) {
+            game.top_team().to_owned()
+        } else {
+            return Err(DeclareError::NotInGame);
+        };
+        if opponent.is_empty() {
+            return Err(DeclareError::NoOpponent);
+        }
+        Ok(opponent)
+    }
+
+    // Checks if a team has withdrawn from the tournament.
+    pub fn is_withdrawn(&self, team: &str) -> bool {
+        self.withdrawn.lock().expect("There was an unknown error.").iter().any(|out| same_person(out, team))
+    }
+
+    // Checks if a game was won by forfeit.
+    pub fn is_forfeit(&self, id: &str) -> bool {
+        self.forfeited.lock().expect("There was an unknown error.").iter().any(|forfeited| forfeited == id)
+    }
+
+    // Takes back the result that was declared last. Returns the game's id and the winner that was taken back.
+    pub fn undo(&self) -> Option<(String, String)> {
+        let (id, _) = self.declared.lock().expect("There was an unknown error.").pop()?;
+        self.forfeited.lock().expect("There was an unknown error.").retain(|forfeited| *forfeited != id);
+        let mut game = self.games[&id].lock().expect("There was an unknown error.");
+        let winner = game.winner().to_owned();
+        game.clear_winner();
+        Some((id, winner))
+    }
+
+    // Gets the games after a declared game that have been played with its winner or loser in them, in the order they would be undone.
+    pub fn played_after(&self, id: &str) -> Result<Vec<String>, DeclareError> {
+        let mut next_game = Some(Arc::clone(self.games.get(id).ok_or(DeclareError::UnknownGame)?));
+        let mut played = Vec::new();
+        while let Some(game) = next_game {
+            let guard = game.lock().expect("There was an unknown error.");
+            if guard.id != id {
+                if guard.ranking.is_empty() {
+                    break;
+                }
+                played.push(guard.id.clone());
+            }
+            //The loser of a semifinal has played the third place game
+            if let Some(loser_game) = guard.loser_game.as_ref() {
+                let loser_game = loser_game.lock().expect("There was an unknown error.");
+                if !loser_game.ranking.is_empty() && !played.contains(&loser_game.id) {
+                    played.push(loser_game.id.clone());
+                }
+            }
+            next_game = guard.next_game.as_ref().map(Arc::clone);
+        }
+        played.reverse();
+        Ok(played)
+    }
+
+    // Changes the winner of a declared game, taking back the results of the games after it that were played.
+    // Returns the ids of the games that were taken back.
+    pub fn correct(&self, id: &str, winner: &str) -> Result<Vec<String>, DeclareError> {
+        let winner = {
+            let game = self.games.get(id).ok_or(DeclareError::UnknownGame)?;
+            let game = game.lock().expect("There was an unknown error.");
+            if game.ranking.is_empty() {
+                return Err(DeclareError::NotDeclared);
+            }
+            let winner = game.teams.iter().find(|team| !team.is_empty() && same_person(team, winner)).ok_or(DeclareError::NotInGame)?.clone();
+            if game.winner() == winner {
+                return Err(DeclareError::SameWinner);
+            }
+            winner
+        };
+
+        let undone = self.played_after(id)?;
+        let mut declared = self.declared.lock().expect("There was an unknown error.");
+        let mut forfeited = self.forfeited.lock().expect("There was an unknown error.");
+        for game_id in undone.iter().chain(std::iter::once(&id.to_owned())) {
+            self.games[game_id].lock().expect("There was an unknown error.").clear_winner();
+            declared.retain(|(declared_id, _)| declared_id != game_id);
+            forfeited.retain(|forfeited_id| forfeited_id != game_id);
+        }
+        self.games[id].lock().expect("There was an unknown error.").set_winner(winner);
+        declared.push((id.to_owned(), storage::now()));
+        Ok(undone)
+    }
+
+    // Takes a team out of the tournament so the games they have left are forfeited. Returns false if they were already out.
+    pub fn withdraw(&self, team: &str) -> bool {
+        let mut withdrawn = self.withdrawn.lock().expect("There was an unknown error.");
+        if withdrawn.iter().any(|out| same_person(out, team)) {
+            return false;
+        }
+        withdrawn.push(team.to_owned());
+        true
+    }
+
+    // Gets the id, winner and withdrawn team of every game that is ready where one team has withdrawn and the other has not.
+    pub fn forfeits(&self) -> Vec<(String, String, String)> {
+        let withdrawn = self.withdrawn.lock().expect("There was an unknown error.").clone();
+        let is_out = |team: &str| !team.is_empty() && withdrawn.iter().any(|out| same_person(out, team));
+        self.ready_games().into_iter()
+            .filter_map(|(id, top, bottom)| {
+                match (is_out(&top), is_out(&bottom)) {
+                    (true, false) if !bottom.is_empty() => Some((id, bottom, top)),
+                    (false, true) if !top.is_empty() => Some((id, top, bottom)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    // Sets when a game starts, or takes the game off the schedule if start is None.
+    pub fn schedule(&self, id: &str, start: Option<u64>) -> Result<(), DeclareError> {
+        let game = self.games.get(id).ok_or(DeclareError::UnknownGame)?;
+        game.lock().expect("There was an unknown error.").start = start;
+        Ok(())
+    }
+
+    // Schedules every game that has not been played, with the first round at start and each round after spacing seconds later.
+    pub fn schedule_rounds(&self, start: u64, spacing: u64) {
+        //The third place game is played at the same time as the final
+        let last_round: u64 = self.games.keys().filter_map(|id| id.split('-').next().and_then(|round| round.parse().ok())).max().unwrap_or(1);
+        for (id, game) in self.games.iter() {
+            let round: u64 = if id == THIRD_PLACE { last_round } else { id.split('-').next().and_then(|round| round.parse().ok()).unwrap_or(1) };
+            let mut game = game.lock().expect("There was an unknown error.");
+            if game.ranking.is_empty() {
+                game.start = Some(start + round.saturating_sub(1) * spacing);
+            }
+        }
+    }
+
+    // Gets when a game is scheduled to start.
+    pub fn start_of(&self, id: &str) -> Option<u64> {
+        self.games.get(id)?.lock().expect("There was an unknown error.").start
+    }
+
+    // Gets the id, top team, bottom team and start of every scheduled game that has not been played.
+    pub fn scheduled(&self) -> Vec<(String, String, String, u64)> {
+        self.games.iter()
+            .filter_map(|(id, game)| {
+                let game = game.lock().expect("There was an unknown error.");
+                match game.start {
+                    Some(start) if game.ranking.is_empty() => Some((id.clone(), game.top_team().to_owned(), game.bottom_team().to_owned(), start)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    // Gets the id of the game that the winner of a game moves on to.
+    pub fn next_id(&self, id: &str) -> Option<String> {
+        let game = self.games.get(id)?.lock().expect("There was an unknown error.");
+        let next_game = game.next_game.as_ref().map(Arc::clone)?;
+        drop(game);
+        let next_id = next_game.lock().expect("There was an unknown error.").id.clone();
+        Some(next_id)
+    }
+
+    // Gets the id of the game that the loser of a game moves on to.
+    pub fn loser_id(&self, id: &str) -> Option<String> {
+        let game = self.games.get(id)?.lock().expect("There was an unknown error.");
+        let loser_game = game.loser_game.as_ref().map(Arc::clone)?;
+        drop(game);
+        let loser_id = loser_game.lock().expect("There was an unknown error.").id.clone();
+        Some(loser_id)
+    }
+
+    // Gets the places the teams finished in once the final has been played, best first, as the first and last place they share and the teams.
+    // The final decides the top places and the third place game the two after them. Everyone else is placed by the round they went out in,
+    // then by where they finished in their game, and shares places with the teams that went out the same way.
+    pub fn placements(&self) -> Vec<(usize, usize, Vec<String>)> {
+        let games = self.snapshot();
+        match games.iter().find(|game| game.next_id.is_none() && game.id != THIRD_PLACE) {
+            Some(game) if !game.ranking.is_empty() => (),
+            _ => return Vec::new(),
+        };
+
+        let round = |id: &str| -> u32 { id.split('-').next().and_then(|round| round.parse().ok()).unwrap_or(0) };
+        let last_round = games.iter().map(|game| round(&game.id)).max().unwrap_or(0);
+        let mut groups: Vec<Vec<String>> = Vec::new();
+        for knocked_out in (1..=last_round).rev() {
+            let in_round: Vec<&GameState> = games.iter().filter(|game| game.id != THIRD_PLACE && round(&game.id) == knocked_out).collect();
+            groups.extend(finishing_groups(&in_round, &groups.concat()));
+            if knocked_out == last_round {
+                let third_place: Vec<&GameState> = games.iter().filter(|game| game.id == THIRD_PLACE).collect();
+                groups.extend(finishing_groups(&third_place, &groups.concat()));
+            }
+        }
+
+        let mut place = 1;
+        groups.into_iter()
+            .map(|teams| {
+                let first = place;
+                place += teams.len();
+                (first, place - 1, teams)
+            })
+            .collect()
+    }
+
+    // Gets the id, the teams in each slot, the order they finished in and the time the result was declared of every game,
+    // including ones nobody has made it to yet.
+    pub fn results(&self) -> Vec<GameRecord> {
+        let declared = self.declared.lock().expect("There was an unknown error.").clone();
+        self.snapshot().into_iter()
+            .map(|game| {
+                let time = declared.iter().find(|(id, _)| *id == game.id).map(|(_, time)| *time);
+                (game.id, game.teams, game.ranking, time)
+            })
+            .collect()
+    }
+
+    // Gets the id, top team and bottom team of every game that can be declared now.
+    // That is every game with no winner that has all its teams, or some of them and no way to get another.
+    pub fn ready_games(&self) -> Vec<(String, String, String)> {
+        self.ready_heats().into_iter()
+            .map(|(id, teams)| (id, teams.first().cloned().unwrap_or_default(), teams.get(1).cloned().unwrap_or_default()))
+            .collect()
+    }
+
+    // Gets the id and the teams in each slot of every game that can be declared now, like ready_games.
+    pub fn ready_heats(&self) -> Vec<(String, Vec<String>)> {
+        let games = self.snapshot();
+        games.iter()
+            .filter(|game| {
+                let teams = game.entrants().len();
+                game.ranking.is_empty() && (teams == game.teams.len() || (teams > 0 && !has_pending_feeder(&games, &game.id)))
+            })
+            .map(|game| (game.id.clone(), game.teams.clone()))
+            .collect()
+    }
+
+    // Gets every game a team has been in, in the order they were played, with everyone in a heat as their opponents.
+    pub fn path(&self, team: &str) -> Vec<TeamGame> {
+        self.snapshot().into_iter()
+            .filter_map(|game| {
+                let entered = game.entrants().into_iter().find(|entrant| same_person(entrant, team))?;
+                let place = game.ranking.iter().position(|ranked| *ranked == entered);
+                Some(TeamGame {
+                    id: game.id.clone(),
+                    opponents: game.entrants().into_iter().filter(|entrant| *entrant != entered).collect(),
+                    winner: game.winner().to_owned(),
+                    place,
+                    advanced: game.next_id.is_some() && place.map(|place| place < game.advance).unwrap_or(false),
+                })
+            })
+            .collect()
+    }
+
+    // Copies every game so they can be looked at together without holding more than one lock at a time.
+    fn snapshot(&self) -> Vec<GameState> {
+        self.games.iter()
+            .map(|(id, game)| {
+                let guard = game.lock().expect("There was an unknown error.");
+                let next_game = guard.next_game.as_ref().map(Arc::clone);
+                let loser_game = guard.loser_game.as_ref().map(Arc::clone);
+                let state = GameState {
+                    id: id.clone(),
+                    teams: guard.teams.clone(),
+                    ranking: guard.ranking.clone(),
+                    advance: guard.advance,
+                    next_id: None,
+                    loser_id: None,
+                };
+                drop(guard);
+                let game_id = |game: Arc<Mutex<Game>>| game.lock().expect("There was an unknown error.").id.clone();
+                GameState { next_id: next_game.map(game_id), loser_id: loser_game.map(game_id), ..state }
+            })
+            .collect()
+    }
+}
+
+// A copy of a game's teams, the order they finished in and the games its winners and loser move on to.
+struct GameState {
+    id: String,
+    teams: Vec<String>,
+    ranking: Vec<String>,
+    advance: usize,
+    next_id: Option<String>,
+    loser_id: Option<String>,
+}
+
+impl GameState {
+    fn winner(&self) -> &str {
+        self.ranking.first().map(String::as_str).unwrap_or_default()
+    }
+
+    fn entrants(&self) -> Vec<String> {
+        self.teams.iter().filter(|team| !team.is_empty()).cloned().collect()
+    }
+}
+
+// Groups the teams of declared games that are not placed yet by where they finished in their game, best first.
+// Teams left out of a heat's ranking finished behind everyone in it.
+fn finishing_groups(games: &[&GameState], placed: &[String]) -> Vec<Vec<String>> {
+    let finishes: Vec<(usize, String)> = games.iter()
+        .filter(|game| !game.ranking.is_empty())
+        .flat_map(|game| {
+            game.entrants().into_iter()
+                .map(|team| (game.ranking.iter().position(|ranked| *ranked == team).unwrap_or(game.ranking.len()), team))
+                .collect::<Vec<_>>()
+        })
+        .filter(|(_, team)| !placed.contains(team))
+        .collect();
+    let last = finishes.iter().map(|(position, _)| *position).max();
+    last.map(|last| (0..=last)
+        .map(|position| finishes.iter().filter(|(finish, _)| *finish == position).map(|(_, team)| team.clone()).collect::<Vec<String>>())
+        .filter(|group| !group.is_empty())
+        .collect())
+        .unwrap_or_default()
+}
+
+// Checks if a game still has a team to come from a game before it.
+fn has_pending_feeder(games: &[GameState], id: &str) -> bool {
+    games.iter()
+        .filter(|feeder| (feeder.next_id.as_deref() == Some(id) || feeder.loser_id.as_deref() == Some(id)) && feeder.ranking.is_empty())
+        .any(|feeder| !feeder.entrants().is_empty() || has_pending_feeder(games, &feeder.id))
+}
+
+// Puts teams in the order that seeds a bracket by rating, with the highest rated first. Teams without a rating go last.
+// Top seeds are spread out so they can only meet in later rounds, like 1 vs 8, 4 vs 5, 2 vs 7 and 3 vs 6 for 8 teams.
+pub fn seed_order(teams: &[String], ratings: &HashMap<String, f64>) -> Vec<String> {
+    let mut seeded: Vec<String> = teams.to_vec();
+    let rating = |team: &String| ratings.get(team).copied().unwrap_or(f64::MIN);
+    seeded.sort_by(|first, second| rating(second).partial_cmp(&rating(first)).unwrap_or(Ordering::Equal));
+
+    seed_positions(seeded.len()).into_iter()
+        .map(|seed| seeded[seed - 1].clone())
+        .collect()
+}
+
+// Puts the teams that made it out of a group stage in bracket order. pools has the teams that move on from each pool, from first place down.
+// Pool winners are the top seeds, and every other team is placed to play someone from another pool first, like A1 vs B2 and B1 vs A2,
+// and in the other half of the bracket from the teams of their pool that placed higher where possible.
+pub fn cross_pool_order(pools: &[Vec<String>]) -> Vec<String> {
+    let count: usize = pools.iter().map(|pool| pool.len()).sum();
+    let positions = seed_positions(count);
+    let mut slots: Vec<Option<(usize, String)>> = vec![None; count];
+    let places = pools.iter().map(|pool| pool.len()).max().unwrap_or(0);
+    let mut seed = 1;
+    for place in 0..places {
+        let mut remaining: Vec<usize> = (0..pools.len()).filter(|pool| pools[*pool].len() > place).collect();
+        while !remaining.is_empty() {
+            let position = positions.iter().position(|spot| *spot == seed).expect("Every seed has a spot.");
+            //Teams are paired up in order in the first round, so the spot next to this one is the opponent
+            let opponent = slots.get(position ^ 1).and_then(|slot| slot.as_ref()).map(|(pool, _)| *pool);
+            let half = position * 2 / count;
+            let pick = (0..remaining.len())
+                .min_by_key(|index| {
+                    let pool = remaining[*index];
+                    let same_half = slots.iter().enumerate()
+                        .any(|(spot, slot)| spot * 2 / count == half && slot.as_ref().map(|(other, _)| *other == pool).unwrap_or(false));
+                    (opponent == Some(pool), same_half)
+                })
+                .expect("There are teams left for this place.");
+            let pool = remaining.remove(pick);
+            slots[position] = Some((pool, pools[pool][place].clone()));
+            seed += 1;
+        }
+    }
+    slots.into_iter().flatten().map(|(_, team)| team).collect()
+}
+
+// Gets the seed in each spot of a bracket, like 1, 8, 4, 5, 2, 7, 3, 6 for 8 teams.
+// The spots of a full bracket are worked out first, then seeds past the number of teams are left out.
+fn seed_positions(count: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = vec![1];
+    while order.len() < count {
+        let size = order.len() * 2;
+        order = order.iter().flat_map(|seed| vec![*seed, size + 1 - seed]).collect();
+    }
+    order.into_iter().filter(|seed| *seed <= count).collect()
+}
+
+// Makes the name of a game from its id, like "Round 2 Game 1" for "2-1".
+pub fn game_name(id: &str) -> String {
+    if id == THIRD_PLACE {
+        return "Third Place Game".to_owned();
+    }
+    let mut nums = id.split('-');
+    let round = nums.next().unwrap_or_default();
+    let number = nums.next().unwrap_or_default();
+    format!("Round {} Game {}", round, number)
+}
+
+// Makes the text of a place that teams share, like "1st" or "5th-8th".
+pub fn place_name(first: usize, last: usize) -> String {
+    let ordinal = |place: usize| {
+        let suffix = match (place % 10, place % 100) {
+            (_, 11..=13) => "th",
+            (1, _) => "st",
+            (2, _) => "nd",
+            (3, _) => "rd",
+            _ => "th",
+        };
+        format!("{}{}", place, suffix)
+    };
+    if first == last {
+        ordinal(first)
+    } else {
+        format!("{}-{}", ordinal(first), ordinal(last))
+    }
+}
+
+// Makes the text of the places the teams finished in, one place a line.
+pub fn placements_text(placements: &[(usize, usize, Vec<String>)]) -> String {
+    placements.iter()
+        .map(|(first, last, teams)| format!("{}: {}\n", place_name(*first, *last), teams.join(", ")))
+        .collect()
+}
+
+// Makes the text for one team in a game, marking the winner and teams that withdrew.
+// Heats mark where everyone ranked finished and who moves on.
+fn slot_text(team: &str, game: &Game, forfeit: bool, withdrawn: &[String]) -> String {
+    let winner = game.winner();
+    let place = game.ranking.iter().position(|ranked| ranked == team);
+    let mut text = if team.is_empty() {
+        "-".to_owned()
+    } else if let (true, Some(place)) = (game.teams.len() > 2, place) {
+        let advances = if place < game.advance && game.next_game.is_some() { ", advances" } else { "" };
+        format!("{} ({}{})", team, place_name(place + 1, place + 1), advances)
+    } else if team == winner && forfeit {
+        format!("{} (winner by forfeit)", team)
+    } else if team == winner {
+        format!("{} (winner)", team)
+    } else {
+        team.to_owned()
+    };
+    if withdrawn.iter().any(|out| out == team) {
+        text.push_str(" (withdrawn)");
+    }
+    text
+}
+
+//Fills up all the tournament games with their next games.
+fn fill_tournament(current_round: &mut LinkedHashMap<String, Arc<Mutex<Game>>>, round_num: u32, all_games: &mut LinkedHashMap<String, Arc<Mutex<Game>>>) {
+    let round_num = round_num + 1;
+    let mut next_round: LinkedHashMap<String, Arc<Mutex<Game>>> = LinkedHashMap::new();
+    let len = current_round.len();
+    if len > 1 {
+        for (index, (_, game)) in current_round.iter_mut().enumerate() {
+            let next_id = format!("{}-{}", round_num, index / 2 + 1);
+
+            if index % 2 == 0 {
+                let next_game = Arc::new(Mutex::new(Game::new(next_id.clone(), "".to_owned(), "".to_owned(), None)));
+                all_games.insert(next_id.clone(), Arc::clone(&next_game));
+                next_round.insert(next_id.clone(), Arc::clone(&next_game));
+            }
+            game.lock().unwrap().set_next(Some(Arc::clone(&next_round[&format!("{}-{}", round_num, index / 2 + 1)])));
+        }
+        fill_tournament(&mut next_round, round_num, all_games);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn withdrawing_matches_mentions_and_capitalization() {
+        let bracket = Bracket::new(&names(&["<@!1>", "Alex", "Sam", "Jo"]));
+        assert!(bracket.withdraw("<@1>"));
+        assert!(!bracket.withdraw("<@!1>"));
+        assert!(bracket.withdraw("alex"));
+        assert!(!bracket.withdraw("ALEX"));
+        assert!(bracket.is_withdrawn("Alex"));
+    }
+
+    #[test]
+    fn forfeits_count_withdrawn_teams_entered_another_way() {
+        let bracket = Bracket::new(&names(&["<@!1>", "Alex", "Sam", "Jo"]));
+        let games = bracket.ready_games();
+        let (id, top, bottom) = games.iter().find(|(_, top, _)| top == "<@!1>").cloned().expect("Every team should have a first game.");
+        bracket.withdraw("<@1>");
+        assert_eq!(bracket.forfeits(), vec![(id.clone(), bottom.clone(), top.clone())]);
+
+        //Nobody wins by forfeit when both teams have withdrawn
+        bracket.withdraw(&bottom.to_uppercase());
+        assert!(bracket.forfeits().iter().all(|(game, _, _)| *game != id));
+    }
+
+    // Gets the teams in the game that is ready with the id.
+    fn ready(bracket: &Bracket, id: &str) -> (String, String) {
+        let (_, top, bottom) = bracket.ready_games().into_iter().find(|(game, _, _)| game == id).expect("The game should be ready.");
+        (top, bottom)
+    }
+
+    #[test]
+    fn undo_takes_back_the_last_result() {
+        let bracket = Bracket::new(&names(&["A", "B", "C", "D"]));
+        assert!(bracket.undo().is_none());
+        let (top, _) = ready(&bracket, "1-1");
+        assert!(matches!(bracket.declare("1-1", &top), Ok(Declared::NextGame(next)) if next == "2-1"));
+        assert!(matches!(bracket.declare("1-1", &top), Err(DeclareError::AlreadyDeclared)));
+
+        assert_eq!(bracket.undo(), Some(("1-1".to_owned(), top.clone())));
+        //The winner is taken out of the final again and the game can be declared again
+        assert!(bracket.games["2-1"].lock().unwrap().entrants().is_empty());
+        assert_eq!(ready(&bracket, "1-1").0, top);
+        assert!(bracket.undo().is_none());
+    }
+
+    #[test]
+    fn correcting_a_result_takes_back_the_games_after_it() {
+        let bracket = Bracket::new(&names(&["A", "B", "C", "D"]));
+        let (first, first_loser) = ready(&bracket, "1-1");
+        let (second, _) = ready(&bracket, "1-2");
+        bracket.declare("1-1", &first).ok();
+        bracket.declare("1-2", &second).ok();
+        assert!(matches!(bracket.declare("2-1", &first), Ok(Declared::Champion)));
+
+        assert_eq!(bracket.correct("1-1", &first_loser).ok(), Some(names(&["2-1"])));
+        let final_game = bracket.games["2-1"].lock().unwrap();
+        assert!(final_game.ranking.is_empty());
+        assert!(final_game.teams.contains(&first_loser) && final_game.teams.contains(&second));
+        assert!(!final_game.teams.contains(&first));
+        drop(final_game);
+        //The other semifinal is left alone
+        assert_eq!(bracket.games["1-2"].lock().unwrap().winner(), second);
+
+        //Taking back the correction takes back the corrected game
+        assert_eq!(bracket.undo(), Some(("1-1".to_owned(), first_loser)));
+    }
+
+    #[test]
+    fn corrections_have_to_change_a_declared_game() {
+        let bracket = Bracket::new(&names(&["A", "B", "C", "D"]));
+        let (top, _) = ready(&bracket, "1-1");
+        assert!(matches!(bracket.correct("1-1", &top), Err(DeclareError::NotDeclared)));
+        bracket.declare("1-1", &top).ok();
+        assert!(matches!(bracket.correct("1-1", &top), Err(DeclareError::SameWinner)));
+        assert!(matches!(bracket.correct("1-1", "Nobody"), Err(DeclareError::NotInGame)));
+        assert!(matches!(bracket.correct("9-9", &top), Err(DeclareError::UnknownGame)));
+
+        //The winner is found like any other name, so case and nickname mentions don't matter
+        let bracket = Bracket::new(&names(&["Alex", "<@1>"]));
+        let (top, bottom) = ready(&bracket, "1-1");
+        bracket.declare("1-1", &top).ok();
+        let typed = if bottom == "Alex" { "alex" } else { "<@!1>" };
+        assert!(bracket.correct("1-1", typed).is_ok());
+        assert_eq!(bracket.path(&bottom)[0].winner, bottom);
+        assert!(matches!(bracket.correct("1-1", &bottom.to_uppercase()), Err(DeclareError::SameWinner)));
+    }
+
+    #[test]
+    fn seeds_are_spread_so_the_top_seeds_meet_last() {
+        assert_eq!(seed_positions(8), vec![1, 8, 4, 5, 2, 7, 3, 6]);
+        assert_eq!(seed_positions(4), vec![1, 4, 2, 3]);
+        //Seeds past the number of teams are byes and left out
+        assert_eq!(seed_positions(6), vec![1, 4, 5, 2, 3, 6]);
+        assert_eq!(seed_positions(1), vec![1]);
+    }
+
+    #[test]
+    fn seeding_by_rating_puts_the_highest_rated_against_the_lowest() {
+        let teams = names(&["Low", "Top", "Unrated", "Second"]);
+        let ratings: HashMap<String, f64> = [("Low", 1400.0), ("Top", 1700.0), ("Second", 1600.0)].iter()
+            .map(|(name, rating)| (name.to_string(), *rating))
+            .collect();
+        assert_eq!(seed_order(&teams, &ratings), names(&["Top", "Unrated", "Second", "Low"]));
+    }
+
+    #[test]
+    fn pool_winners_play_runners_up_from_another_pool() {
+        let pools = vec![names(&["A1", "A2"]), names(&["B1", "B2"])];
+        assert_eq!(cross_pool_order(&pools), names(&["A1", "B2", "B1", "A2"]));
+    }
+
+    #[test]
+    fn first_games_are_between_pools() {
+        for (num_pools, advance) in [(4, 2), (2, 4), (4, 4), (8, 2)].iter() {
+            let pools: Vec<Vec<String>> = (0..*num_pools)
+                .map(|pool| (1..=*advance).map(|place| format!("{}{}", (b'A' + pool as u8) as char, place)).collect())
+                .collect();
+            let order = cross_pool_order(&pools);
+            assert_eq!(order.len(), num_pools * advance);
+            for pair in order.chunks(2) {
+                assert_ne!(pair[0][..1], pair[1][..1], "{:?} should not have two teams from one pool in the first round", order);
+            }
+            //Pool winners are the top seeds, and each one is in the other half from their runner up
+            let half = |team: &str| order.iter().position(|placed| placed == team).unwrap() * 2 / order.len();
+            for pool in pools.iter() {
+                assert_ne!(half(&pool[0]), half(&pool[1]), "{:?} should split up {} and {}", order, pool[0], pool[1]);
+            }
+        }
+    }
+
+    // Declares the top team the winner of every game until the bracket is finished.
+    fn play_out(bracket: &Bracket) {
+        while let Some((id, top, _)) = bracket.ready_games().into_iter().next() {
+            assert!(bracket.declare(&id, &top).is_ok());
+        }
+        assert!(bracket.is_finished());
+    }
+
+    // Gets the first and last place and how many teams share it for each placement.
+    fn shape(bracket: &Bracket) -> Vec<(usize, usize, usize)> {
+        bracket.placements().into_iter().map(|(first, last, teams)| (first, last, teams.len())).collect()
+    }
+
+    #[test]
+    fn placements_go_by_the_round_teams_went_out_in() {
+        let bracket = Bracket::new(&names(&["A", "B", "C", "D", "E", "F", "G", "H"]));
+        assert!(bracket.placements().is_empty());
+        play_out(&bracket);
+        assert_eq!(shape(&bracket), vec![(1, 1, 1), (2, 2, 1), (3, 4, 2), (5, 8, 4)]);
+
+        let champion = bracket.games["3-1"].lock().unwrap().winner().to_owned();
+        assert_eq!(bracket.placements()[0].2, vec![champion]);
+    }
+
+    #[test]
+    fn the_third_place_game_splits_third_and_fourth() {
+        let mut bracket = Bracket::new(&names(&["A", "B", "C", "D", "E", "F", "G", "H"]));
+        assert!(bracket.add_third_place());
+        play_out(&bracket);
+        assert_eq!(shape(&bracket), vec![(1, 1, 1), (2, 2, 1), (3, 3, 1), (4, 4, 1), (5, 8, 4)]);
+        let third = bracket.games[THIRD_PLACE].lock().unwrap().winner().to_owned();
+        assert_eq!(bracket.placements()[2].2, vec![third]);
+    }
+
+    #[test]
+    fn third_place_can_be_added_after_the_semifinals() {
+        let mut bracket = Bracket::new(&names(&["A", "B", "C", "D"]));
+        for id in ["1-1", "1-2"].iter() {
+            let (top, _) = ready(&bracket, id);
+            bracket.declare(id, &top).ok();
+        }
+        assert!(bracket.add_third_place());
+        let losers: Vec<String> = ["1-1", "1-2"].iter().map(|id| bracket.games[*id].lock().unwrap().loser()).collect();
+        assert_eq!(bracket.games[THIRD_PLACE].lock().unwrap().entrants(), losers);
+
+        //Brackets without semifinals can't have a third place game
+        assert!(!Bracket::new(&names(&["A", "B", "C"])).add_third_place());
+    }
+
+    #[test]
+    fn every_team_is_placed_once() {
+        for count in 2..=12 {
+            let teams: Vec<String> = (1..=count).map(|team| team.to_string()).collect();
+            for third_place in [false, true].iter() {
+                let mut bracket = Bracket::new(&teams);
+                if *third_place {
+                    bracket.add_third_place();
+                }
+                play_out(&bracket);
+                let placements = bracket.placements();
+                let mut placed: Vec<String> = placements.iter().flat_map(|(_, _, teams)| teams.clone()).collect();
+                placed.sort();
+                let mut expected = teams.clone();
+                expected.sort();
+                assert_eq!(placed, expected, "every one of {} teams should be placed", count);
+                assert_eq!(placements.last().map(|(_, last, _)| *last), Some(count));
+            }
+        }
+    }
+
+    #[test]
+    fn place_names_use_ordinals() {
+        assert_eq!(place_name(1, 1), "1st");
+        assert_eq!(place_name(3, 4), "3rd-4th");
+        assert_eq!(place_name(5, 8), "5th-8th");
+        assert_eq!(place_name(11, 12), "11th-12th");
+        assert_eq!(place_name(21, 22), "21st-22nd");
+        assert_eq!(place_name(113, 113), "113th");
+    }
+
+    // Ranks every heat in the order its teams are in until the final heat is declared.
+    fn play_out_heats(bracket: &Bracket) {
+        while let Some((id, teams)) = bracket.ready_heats().into_iter().next() {
+            let teams: Vec<String> = teams.into_iter().filter(|team| !team.is_empty()).collect();
+            assert!(bracket.declare_ranking(&id, &teams).is_ok());
+        }
+        assert!(bracket.is_finished());
+    }
+
+    #[test]
+    fn heats_narrow_down_to_one_final_heat() {
+        let teams: Vec<String> = (1..=16).map(|team| team.to_string()).collect();
+        let bracket = Bracket::heats(&teams, 4, 2);
+        let ids: Vec<&String> = bracket.games.keys().collect();
+        assert_eq!(ids, vec!["1-1", "1-2", "1-3", "1-4", "2-1", "2-2", "3-1"]);
+        assert!(bracket.games.values().all(|heat| heat.lock().unwrap().teams.len() == 4));
+        //The first teams entered start in different heats
+        assert_eq!(bracket.games["1-1"].lock().unwrap().entrants(), names(&["1", "5", "9", "13"]));
+
+        play_out_heats(&bracket);
+        assert_eq!(shape(&bracket), vec![(1, 1, 1), (2, 2, 1), (3, 3, 1), (4, 4, 1), (5, 6, 2), (7, 8, 2), (9, 12, 4), (13, 16, 4)]);
+    }
+
+    #[test]
+    fn every_team_in_heats_is_placed_once() {
+        for count in 3..=40 {
+            let teams: Vec<String> = (1..=count).map(|team| team.to_string()).collect();
+            for (size, advance) in [(3, 1), (4, 2), (5, 2), (8, 3), (10, 5)].iter() {
+                let bracket = Bracket::heats(&teams, *size, *advance);
+                assert!(bracket.games.values().all(|heat| heat.lock().unwrap().teams.len() <= *size));
+                play_out_heats(&bracket);
+                let mut placed: Vec<String> = bracket.placements().into_iter().flat_map(|(_, _, teams)| teams).collect();
+                placed.sort();
+                let mut expected = teams.clone();
+                expected.sort();
+                assert_eq!(placed, expected, "every one of {} teams should be placed in heats of {}", count, size);
+            }
+        }
+    }
+
+    #[test]
+    fn heats_need_the_teams_that_move_on_ranked() {
+        let bracket = Bracket::heats(&names(&["A", "B", "C", "D", "E", "F", "G", "H"]), 4, 2);
+        assert!(matches!(bracket.declare_ranking("1-1", &names(&["A"])), Err(DeclareError::NotEnoughRanked(2))));
+        assert!(matches!(bracket.declare_ranking("1-1", &names(&["A", "a"])), Err(DeclareError::RankedTwice)));
+        assert!(matches!(bracket.declare_ranking("1-1", &names(&["A", "B"])), Err(DeclareError::NotInGame)));
+
+        //Names are matched like everywhere else, and the ranking comes back as the teams were entered
+        assert!(matches!(bracket.declare_ranking("1-1", &names(&["g", "C"])), Ok((ranking, Declared::NextGame(next))) if ranking == names(&["G", "C"]) && next == "2-1"));
+        assert!(matches!(bracket.declare_ranking("1-1", &names(&["A", "C"])), Err(DeclareError::AlreadyDeclared)));
+        bracket.declare_ranking("1-2", &names(&["B", "D"])).ok();
+
+        //Only the winner of the final heat has to be ranked, and everyone left out ties behind
+        assert!(matches!(bracket.declare_ranking("2-1", &names(&["D"])), Ok((_, Declared::Champion))));
+        let placements = bracket.placements();
+        assert_eq!(placements[0], (1, 1, names(&["D"])));
+        assert_eq!(placements[1].0..=placements[1].1, 2..=4);
+
+        //Taking back the final heat empties the ranking again
+        assert_eq!(bracket.undo().map(|(id, _)| id), Some("2-1".to_owned()));
+        assert!(bracket.placements().is_empty());
+    }
+
+    #[test]
+    fn paths_through_heats_have_everyone_in_the_heat() {
+        let bracket = Bracket::heats(&names(&["A", "B", "C", "D"]), 4, 2);
+        let path = bracket.path("c");
+        assert_eq!(path.len(), 1);
+        assert_eq!(path[0].id, "1-1");
+        assert_eq!(path[0].opponents, names(&["A", "B", "D"]));
+        assert!(path[0].winner.is_empty() && path[0].place.is_none() && !path[0].advanced);
+        assert_eq!(bracket.ready_heats(), vec![("1-1".to_owned(), names(&["A", "B", "C", "D"]))]);
+
+        //Only the winner has to be ranked in the final heat, and everyone left out has no place
+        bracket.declare_ranking("1-1", &names(&["C", "A"])).ok();
+        let a = bracket.path("A");
+        assert_eq!((a[0].winner.as_str(), a[0].place, a[0].advanced), ("C", Some(1), false));
+        assert_eq!(bracket.path("C")[0].place, Some(0));
+        assert_eq!(bracket.path("D")[0].place, None);
+    }
+
+    #[test]
+    fn paths_through_heats_follow_the_teams_that_move_on() {
+        let bracket = Bracket::heats(&names(&["A", "B", "C", "D", "E", "F", "G", "H"]), 4, 2);
+        bracket.declare_ranking("1-1", &names(&["G", "C"])).ok();
+        let advanced: Vec<(String, Option<usize>, bool)> = bracket.path("G").into_iter().map(|game| (game.id, game.place, game.advanced)).collect();
+        assert_eq!(advanced, vec![("1-1".to_owned(), Some(0), true), ("2-1".to_owned(), None, false)]);
+        let out: Vec<(String, Option<usize>, bool)> = bracket.path("E").into_iter().map(|game| (game.id, game.place, game.advanced)).collect();
+        assert_eq!(out, vec![("1-1".to_owned(), None, false)]);
+    }
+}
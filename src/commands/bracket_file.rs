@@ -0,0 +1,509 @@
+use std::{collections::HashMap, fmt, sync::{Arc, Mutex}};
+
+use linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, Serialize};
+
+use super::{bracket::{Bracket, THIRD_PLACE}, game::Game, results::{GameResult, SessionResult}, storage};
+
+// A bracket file in the layout used by Challonge and tools that read its exports, so brackets can be moved
+// between the bot and other sites. Everything is inside "tournament":
+//
+// {"tournament": {
+//     "name": "tournament-3",
+//     "tournament_type": "single elimination",
+//     "state": "pending", "underway" or "complete",
+//     "participants": [{"participant": {"id": 1, "name": "team1", "seed": 1, "misc": "<Discord user id or empty>", "final_rank": 1}}, ...],
+//     "matches": [{"match": {
+//         "id": 1,
+//         "identifier": "1-1",
+//         "round": 1,
+//         "state": "pending", "open" or "complete",
+//         "player1_id": 1, "player2_id": 2,
+//         "player1_prereq_match_id": null, "player2_prereq_match_id": null,
+//         "winner_id": 1, "loser_id": 2,
+//         "scores_csv": "1-0",
+//         "forfeited": false,
+//         "declared_at": 1760000000
+//     }}, ...]
+// }}
+//
+// Player ids are participant ids and prereq match ids are the matches whose winners play in the match, or whose losers do
+// when player1_is_prereq_match_loser or player2_is_prereq_match_loser is true. The final rank is the place a participant finished in.
+// Any field can be null or left out, except the ids. When a match has scores but no winner_id, the player with the
+// higher score is the winner. The only match fed by losers that can be imported is a third place game, which is fed by the
+// losers of two matches. Other rounds below 1, like Challonge's losers bracket, can't be imported.
+#[derive(Serialize, Deserialize)]
+pub struct BracketFile {
+    pub tournament: TournamentEntry,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TournamentEntry {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub tournament_type: String,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub participants: Vec<ParticipantEntry>,
+    #[serde(default)]
+    pub matches: Vec<MatchEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ParticipantEntry {
+    pub participant: FileParticipant,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FileParticipant {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub seed: Option<usize>,
+    // The Discord user id of the participant if their name is a mention.
+    #[serde(default)]
+    pub misc: Option<String>,
+    // The first place the participant shares once the tournament is over.
+    #[serde(default)]
+    pub final_rank: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MatchEntry {
+    #[serde(rename = "match")]
+    pub game: FileMatch,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FileMatch {
+    pub id: u64,
+    #[serde(default)]
+    pub identifier: Option<String>,
+    #[serde(default)]
+    pub round: i32,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub player1_id: Option<u64>,
+    #[serde(default)]
+    pub player2_id: Option<u64>,
+    #[serde(default)]
+    pub player1_prereq_match_id: Option<u64>,
+    #[serde(default)]
+    pub player2_prereq_match_id: Option<u64>,
+    #[serde(default)]
+    pub player1_is_prereq_match_loser: bool,
+    #[serde(default)]
+    pub player2_is_prereq_match_loser: bool,
+    #[serde(default)]
+    pub winner_id: Option<u64>,
+    #[serde(default)]
+    pub loser_id: Option<u64>,
+    #[serde(default)]
+    pub scores_csv: String,
+    #[serde(default)]
+    pub forfeited: Option<bool>,
+    // When the winner was declared in seconds since the Unix epoch. Not part of Challonge's layout.
+    #[serde(default)]
+    pub declared_at: Option<u64>,
+}
+
+// Why a bracket file could not be turned into a bracket.
+pub enum ImportError {
+    NotJson(String),
+    NoMatches,
+    LosersBracket,
+    DuplicateParticipant(String),
+    UnknownParticipant(u64),
+    UnknownMatch(u64),
+    SeveralNextMatches(u64),
+    NotOneFinal,
+    WrongPlayer(u64),
+    WrongWinner(u64),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::NotJson(error) => write!(f, "The bracket file could not be read: {}", error),
+            ImportError::NoMatches => write!(f, "The bracket file has no matches."),
+            ImportError::LosersBracket => write!(f, "Only single elimination brackets can be imported."),
+            ImportError::DuplicateParticipant(name) => write!(f, "{} is in the bracket file more than once.", name),
+            ImportError::UnknownParticipant(id) => write!(f, "Participant {} is not in the bracket file.", id),
+            ImportError::UnknownMatch(id) => write!(f, "Match {} is not in the bracket file.", id),
+            ImportError::SeveralNextMatches(id) => write!(f, "The winner of match {} moves on to more than one match.", id),
+            ImportError::NotOneFinal => write!(f, "The bracket file must have exactly one final match."),
+            ImportError::WrongPlayer(id) => write!(f, "The players of match {} don't match the winners of the matches before it.", id),
+            ImportError::WrongWinner(id) => write!(f, "The winner of match {} is not one of its players.", id),
+        }
+    }
+}
+
+impl BracketFile {
+    // Reads a bracket file.
+    pub fn parse(text: &str) -> Result<Self, ImportError> {
+        serde_json::from_str(text).map_err(|error| ImportError::NotJson(error.to_string()))
+    }
+
+    // Makes the bracket file of a session's results. Sessions without games only have participants.
+    pub fn from_result(result: &SessionResult) -> Self {
+        let mut participant_ids: HashMap<&str, u64> = HashMap::new();
+        let participants = result.participants.iter().enumerate()
+            .map(|(index, participant)| {
+                let id = index as u64 + 1;
+                participant_ids.insert(participant.name.as_str(), id);
+                ParticipantEntry { participant: FileParticipant {
+                    id,
+                    name: participant.name.clone(),
+                    seed: Some(participant.seed),
+                    misc: participant.user_id.map(|user_id| user_id.to_string()),
+                    //Placements like "5th-8th" start with the first place that is shared
+                    final_rank: participant.placement.as_deref().and_then(|place| place.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()),
+                } }
+            })
+            .collect();
+
+        let match_ids: HashMap<&str, u64> = result.games.iter().enumerate().map(|(index, game)| (game.id.as_str(), index as u64 + 1)).collect();
+        let player = |name: &str| if name.is_empty() { None } else { participant_ids.get(name).copied() };
+        let matches = result.games.iter()
+            .map(|game| {
+                //The game whose winner, or loser for the third place game, is the top team comes first, then the rest in bracket order
+                let is_loser = |feeder: &GameResult| feeder.loser_game.as_deref() == Some(game.id.as_str());
+                let moved_on = |feeder: &GameResult| if !is_loser(feeder) {
+                    feeder.winner.clone()
+                } else if feeder.winner.is_empty() || feeder.winner == feeder.top {
+                    feeder.bottom.clone()
+                } else {
+                    feeder.top.clone()
+                };
+                let mut feeders: Vec<_> = result.games.iter().filter(|feeder| feeder.next_game.as_deref() == Some(game.id.as_str()) || is_loser(feeder)).collect();
+                feeders.sort_by_key(|feeder| feeder.winner.is_empty() || moved_on(feeder) != game.top);
+                let prereq = |index: usize| feeders.get(index).and_then(|feeder| match_ids.get(feeder.id.as_str()).copied());
+                let prereq_loser = |index: usize| feeders.get(index).map(|feeder| is_loser(feeder)).unwrap_or(false);
+                let (winner_id, loser_id, scores_csv) = if game.winner.is_empty() {
+                    (None, None, String::new())
+                } else if game.winner == game.top {
+                    (player(&game.top), player(&game.bottom), "1-0".to_owned())
+                } else {
+                    (player(&game.bottom), player(&game.top), "0-1".to_owned())
+                };
+                let state = if !game.winner.is_empty() {
+                    "complete"
+                } else if !game.top.is_empty() && !game.bottom.is_empty() {
+                    "open"
+                } else {
+                    "pending"
+                };
+                MatchEntry { game: FileMatch {
+                    id: match_ids[game.id.as_str()],
+                    identifier: Some(game.id.clone()),
+                    round: game.round as i32,
+                    state: state.to_owned(),
+                    player1_id: player(&game.top),
+                    player2_id: player(&game.bottom),
+                    player1_prereq_match_id: prereq(0),
+                    player2_prereq_match_id: prereq(1),
+                    player1_is_prereq_match_loser: prereq_loser(0),
+                    player2_is_prereq_match_loser: prereq_loser(1),
+                    winner_id,
+                    loser_id,
+                    scores_csv,
+                    forfeited: Some(game.forfeit),
+                    declared_at: game.declared_at,
+                } }
+            })
+            .collect::<Vec<MatchEntry>>();
+
+        let state = if matches.is_empty() {
+            "pending"
+        } else if matches.iter().any(|entry| entry.game.winner_id.is_none() && entry.game.state != "pending") {
+            "underway"
+        } else {
+            "complete"
+        };
+        BracketFile { tournament: TournamentEntry {
+            name: format!("{}-{}", result.kind, result.session),
+            tournament_type: "single elimination".to_owned(),
+            state: state.to_owned(),
+            participants,
+            matches,
+        } }
+    }
+
+    // Builds a bracket out of the file's matches, with the results that were already played.
+    // Games are numbered again by round in the order they are in the file, so they can be named like the bot's own games.
+    pub fn into_bracket(self) -> Result<Bracket, ImportError> {
+        let TournamentEntry { mut participants, mut matches, .. } = self.tournament;
+        if matches.is_empty() {
+            return Err(ImportError::NoMatches);
+        }
+        //A third place game is fed by the losers of two matches and is added once the rest of the bracket is made
+        let is_third_place = |game: &FileMatch| game.player1_is_prereq_match_loser && game.player2_is_prereq_match_loser
+            && game.player1_prereq_match_id.is_some() && game.player2_prereq_match_id.is_some();
+        let third_place = matches.iter().position(|entry| is_third_place(&entry.game)).map(|index| matches.remove(index).game);
+        if matches.is_empty() {
+            return Err(ImportError::NoMatches);
+        }
+        if matches.iter().any(|entry| entry.game.round < 1 || entry.game.player1_is_prereq_match_loser || entry.game.player2_is_prereq_match_loser) {
+            return Err(ImportError::LosersBracket);
+        }
+
+        //Participants without a seed go after the seeded ones in the order they are in the file
+        participants.sort_by_key(|entry| entry.participant.seed.unwrap_or(usize::MAX));
+        let mut names: HashMap<u64, String> = HashMap::new();
+        let mut teams: Vec<String> = Vec::new();
+        for entry in participants {
+            let name = entry.participant.name.trim().to_owned();
+            if teams.contains(&name) {
+                return Err(ImportError::DuplicateParticipant(name));
+            }
+            names.insert(entry.participant.id, name.clone());
+            teams.push(name);
+        }
+        let name = |id: Option<u64>| -> Result<String, ImportError> {
+            match id {
+                Some(id) => names.get(&id).cloned().ok_or(ImportError::UnknownParticipant(id)),
+                None => Ok(String::new()),
+            }
+        };
+
+        //Giving every match a "[round #]-[game #]" id
+        matches.sort_by_key(|entry| entry.game.round);
+        let mut ids: HashMap<u64, String> = HashMap::new();
+        let mut count_in_round: HashMap<i32, u32> = HashMap::new();
+        for entry in matches.iter() {
+            let count = count_in_round.entry(entry.game.round).or_insert(0);
+            *count += 1;
+            ids.insert(entry.game.id, format!("{}-{}", entry.game.round, count));
+        }
+
+        //Making the games a round at a time and linking the games before each one to it.
+        //Winners of the matches before that are missing from a match's players are moved into it.
+        let mut games: LinkedHashMap<String, Arc<Mutex<Game>>> = LinkedHashMap::new();
+        let mut winners: HashMap<u64, String> = HashMap::new();
+        let mut declared: Vec<(String, u64)> = Vec::new();
+        let mut forfeited: Vec<String> = Vec::new();
+        let mut has_next: Vec<u64> = Vec::new();
+        for entry in matches.iter() {
+            let game = &entry.game;
+            let mut top = name(game.player1_id)?;
+            let mut bottom = name(game.player2_id)?;
+            let prereqs: Vec<u64> = [game.player1_prereq_match_id, game.player2_prereq_match_id].iter().flatten().copied().collect();
+            for prereq in prereqs.iter() {
+                if !ids.contains_key(prereq) || !games.contains_key(&ids[prereq]) {
+                    return Err(ImportError::UnknownMatch(*prereq));
+                }
+                if has_next.contains(prereq) {
+                    return Err(ImportError::SeveralNextMatches(*prereq));
+                }
+                has_next.push(*prereq);
+                if let Some(winner) = winners.get(prereq) {
+                    if *winner == top || *winner == bottom {
+                        continue;
+                    } else if top.is_empty() {
+                        top = winner.clone();
+                    } else if bottom.is_empty() {
+                        bottom = winner.clone();
+                    } else {
+                        return Err(ImportError::WrongPlayer(game.id));
+                    }
+                }
+            }
+            //Winners still to come from the matches before need an empty spot to move into
+            let filled = [&top, &bottom].iter().filter(|team| !team.is_empty()).count();
+            if filled + prereqs.iter().filter(|prereq| !winners.contains_key(prereq)).count() > 2 {
+                return Err(ImportError::WrongPlayer(game.id));
+            }
+
+            let winner = match game.winner_id {
+                Some(_) => name(game.winner_id)?,
+                None => score_winner(&game.scores_csv, &top, &bottom),
+            };
+            if !winner.is_empty() && winner != top && winner != bottom {
+                return Err(ImportError::WrongWinner(game.id));
+            }
+            let id = ids[&game.id].clone();
+            let mut new_game = Game::new(id.clone(), top, bottom, None);
+            if !winner.is_empty() {
+                new_game.ranking = new_game.ranking_for(&winner);
+            }
+            if !winner.is_empty() {
+                declared.push((id.clone(), game.declared_at.unwrap_or_else(storage::now)));
+                if game.forfeited == Some(true) {
+                    forfeited.push(id.clone());
+                }
+                winners.insert(game.id, winner);
+            }
+            let new_game = Arc::new(Mutex::new(new_game));
+            for prereq in prereqs.iter() {
+                games[&ids[prereq]].lock().expect("There was an unknown error.").set_next(Some(Arc::clone(&new_game)));
+            }
+            games.insert(id, new_game);
+        }
+        if matches.len() - has_next.len() != 1 {
+            return Err(ImportError::NotOneFinal);
+        }
+
+        //Losers of the matches before the third place game that are missing from it are moved into it like winners are
+        if let Some(game) = third_place {
+            let mut top = name(game.player1_id)?;
+            let mut bottom = name(game.player2_id)?;
+            let prereqs = [game.player1_prereq_match_id, game.player2_prereq_match_id];
+            for prereq in prereqs.iter().flatten() {
+                let before = ids.get(prereq).and_then(|id| games.get(id)).ok_or(ImportError::UnknownMatch(*prereq))?;
+                let loser = before.lock().expect("There was an unknown error.").loser();
+                if loser.is_empty() || loser == top || loser == bottom {
+                    continue;
+                } else if top.is_empty() {
+                    top = loser;
+                } else if bottom.is_empty() {
+                    bottom = loser;
+                } else {
+                    return Err(ImportError::WrongPlayer(game.id));
+                }
+            }
+            let winner = match game.winner_id {
+                Some(_) => name(game.winner_id)?,
+                None => score_winner(&game.scores_csv, &top, &bottom),
+            };
+            if !winner.is_empty() && winner != top && winner != bottom {
+                return Err(ImportError::WrongWinner(game.id));
+            }
+            let mut new_game = Game::new(THIRD_PLACE.to_owned(), top, bottom, None);
+            if !winner.is_empty() {
+                new_game.ranking = new_game.ranking_for(&winner);
+            }
+            if !winner.is_empty() {
+                declared.push((THIRD_PLACE.to_owned(), game.declared_at.unwrap_or_else(storage::now)));
+                if game.forfeited == Some(true) {
+                    forfeited.push(THIRD_PLACE.to_owned());
+                }
+            }
+            let new_game = Arc::new(Mutex::new(new_game));
+            for prereq in prereqs.iter().flatten() {
+                games[&ids[prereq]].lock().expect("There was an unknown error.").set_loser_game(Some(Arc::clone(&new_game)));
+            }
+            games.insert(THIRD_PLACE.to_owned(), new_game);
+        }
+
+        //Keeping the order results were declared in so `!undo` takes back the latest one
+        declared.sort_by_key(|(_, time)| *time);
+        Ok(Bracket::from_games(games, teams, declared, forfeited))
+    }
+}
+
+// Works out the winner of a match from scores like "3-1" or "2-1,1-2,2-0", counting the sets each player won.
+// Returns nothing if there are no scores or they are tied.
+fn score_winner(scores: &str, top: &str, bottom: &str) -> String {
+    let (mut top_sets, mut bottom_sets) = (0, 0);
+    for set in scores.split(',') {
+        //Scores can be negative, so the first '-' after a digit splits them
+        let split = set.trim().char_indices().skip(1).find(|(_, c)| *c == '-').map(|(index, _)| index);
+        if let Some(index) = split {
+            let set = set.trim();
+            if let (Ok(first), Ok(second)) = (set[..index].parse::<i64>(), set[index + 1..].parse::<i64>()) {
+                match first.cmp(&second) {
+                    std::cmp::Ordering::Greater => top_sets += 1,
+                    std::cmp::Ordering::Less => bottom_sets += 1,
+                    std::cmp::Ordering::Equal => (),
+                }
+            }
+        }
+    }
+    if top_sets > bottom_sets {
+        top.to_owned()
+    } else if bottom_sets > top_sets {
+        bottom.to_owned()
+    } else {
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::model::id::ChannelId;
+
+    use super::*;
+
+    fn round_trip(bracket: &Bracket) -> Bracket {
+        let result = SessionResult::from_bracket(1, ChannelId(1), &bracket.teams, bracket);
+        let text = serde_json::to_string(&BracketFile::from_result(&result)).expect("Failed to write the bracket file.");
+        match BracketFile::parse(&text).and_then(BracketFile::into_bracket) {
+            Ok(bracket) => bracket,
+            Err(error) => panic!("Failed to import the bracket file: {}", error),
+        }
+    }
+
+    // Gets the teams and ranking of every game, leaving out when it was declared.
+    fn games(bracket: &Bracket) -> Vec<(String, Vec<String>, Vec<String>)> {
+        bracket.results().into_iter().map(|(id, teams, ranking, _)| (id, teams, ranking)).collect()
+    }
+
+    #[test]
+    fn exported_brackets_import_the_same() {
+        let teams: Vec<String> = ["<@1>", "Sam", "Alex", "Jo", "Kim"].iter().map(|name| name.to_string()).collect();
+        let mut bracket = Bracket::new(&teams);
+        assert!(bracket.add_third_place());
+        //Playing the first round and one semifinal, with one game won by forfeit
+        let mut first_round = bracket.ready_games();
+        first_round.retain(|(id, _, _)| id.starts_with("1-"));
+        for (index, (id, top, bottom)) in first_round.into_iter().enumerate() {
+            if index == 0 {
+                bracket.forfeit(&id, &top).ok();
+            } else {
+                bracket.declare(&id, if bottom.is_empty() { &top } else { &bottom }).ok();
+            }
+        }
+        let (id, top, _) = bracket.ready_games().into_iter().find(|(_, top, bottom)| !top.is_empty() && !bottom.is_empty())
+            .expect("A semifinal should be ready.");
+        bracket.declare(&id, &top).ok();
+
+        let imported = round_trip(&bracket);
+        assert_eq!(imported.teams, bracket.teams);
+        assert_eq!(games(&imported), games(&bracket));
+        for id in bracket.games.keys() {
+            assert_eq!(imported.is_forfeit(id), bracket.is_forfeit(id));
+            assert_eq!(imported.next_id(id), bracket.next_id(id));
+            assert_eq!(imported.loser_id(id), bracket.loser_id(id));
+        }
+        assert_eq!(imported.ready_games(), bracket.ready_games());
+    }
+
+    #[test]
+    fn finished_brackets_keep_their_placements() {
+        let teams: Vec<String> = ["A", "B", "C", "D"].iter().map(|name| name.to_string()).collect();
+        let mut bracket = Bracket::new(&teams);
+        bracket.add_third_place();
+        while let Some((id, top, _)) = bracket.ready_games().into_iter().next() {
+            bracket.declare(&id, &top).ok();
+        }
+        assert!(bracket.is_finished());
+        assert_eq!(round_trip(&bracket).placements(), bracket.placements());
+    }
+
+    #[test]
+    fn scores_decide_the_winner_when_there_is_no_winner_id() {
+        let text = r#"{"tournament": {
+            "participants": [{"participant": {"id": 10, "name": "Red", "seed": 2}}, {"participant": {"id": 20, "name": "Blue", "seed": 1}}],
+            "matches": [{"match": {"id": 1, "round": 1, "player1_id": 20, "player2_id": 10, "scores_csv": "1-3"}}]
+        }}"#;
+        let bracket = match BracketFile::parse(text).and_then(BracketFile::into_bracket) {
+            Ok(bracket) => bracket,
+            Err(error) => panic!("Failed to import the bracket file: {}", error),
+        };
+        assert_eq!(bracket.teams, vec!["Blue".to_owned(), "Red".to_owned()]);
+        assert_eq!(bracket.games["1-1"].lock().unwrap().winner(), "Red");
+        assert!(bracket.is_finished());
+    }
+
+    #[test]
+    fn losers_brackets_are_not_imported() {
+        let text = r#"{"tournament": {
+            "participants": [{"participant": {"id": 1, "name": "Red"}}, {"participant": {"id": 2, "name": "Blue"}}],
+            "matches": [{"match": {"id": 1, "round": 1, "player1_id": 1, "player2_id": 2}}, {"match": {"id": 2, "round": -1}}]
+        }}"#;
+        assert!(matches!(BracketFile::parse(text).and_then(BracketFile::into_bracket), Err(ImportError::LosersBracket)));
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use serde_json::{Value, json};
+use serenity::{cache::Cache, http::Http, model::id::{ChannelId, MessageId, RoleId, UserId}, prelude::{RwLock, TypeMap, TypeMapKey}, utils::parse_username};
+use tokio::sync::mpsc::UnboundedSender;
+
+const API: &str = "https://discord.com/api/v8";
+// Response types from the Discord API.
+const CHANNEL_MESSAGE: u64 = 4;
+const DEFERRED_UPDATE_MESSAGE: u64 = 6;
+// Only the person who used the interaction can see the response.
+const EPHEMERAL: u64 = 64;
+
+// A click on a button posted by a session, like a winner button in `!tournament`.
+pub struct Click {
+    pub user: UserId,
+    pub roles: Vec<RoleId>,
+    // The id of the thing the button is for, like a game id.
+    pub target: String,
+    // Which choice was clicked, like "top" or "bottom".
+    pub choice: String,
+}
+
+// Key for the sessions waiting for button clicks, kept in the client's data.
+// Buttons have custom ids of "declare:[session]:[target]:[choice]" and clicks are sent to the session.
+pub struct ButtonSessions;
+
+impl TypeMapKey for ButtonSessions {
+    type Value = HashMap<String, UnboundedSender<Click>>;
+}
+
+// Starts sending clicks on a session's buttons to sender.
+pub async fn register(data: &RwLock<TypeMap>, session: String, sender: UnboundedSender<Click>) {
+    if let Some(sessions) = data.write().await.get_mut::<ButtonSessions>() {
+        sessions.insert(session, sender);
+    }
+}
+
+// Stops taking clicks on a session's buttons.
+pub async fn unregister(data: &RwLock<TypeMap>, session: &str) {
+    if let Some(sessions) = data.write().await.get_mut::<ButtonSessions>() {
+        sessions.remove(session);
+    }
+}
+
+// Sends a click on a session button to its session and makes the response to the interaction.
+// Returns None if the interaction is not for a session button.
+pub async fn route(data: &RwLock<TypeMap>, interaction: &Value) -> Option<Value> {
+    let custom_id = interaction["data"]["custom_id"].as_str()?;
+    let parts: Vec<&str> = custom_id.splitn(4, ':').collect();
+    let (session, target, choice) = match parts.as_slice() {
+        ["declare", session, target, choice] => (*session, *target, *choice),
+        _ => return None,
+    };
+    let user = interaction["member"]["user"]["id"].as_str()?.parse().ok().map(UserId)?;
+    let roles = interaction["member"]["roles"].as_array()
+        .map(|roles| roles.iter().filter_map(|id| id.as_str()?.parse().ok().map(RoleId)).collect())
+        .unwrap_or_default();
+
+    let click = Click { user, roles, target: target.to_owned(), choice: choice.to_owned() };
+    let sent = match data.read().await.get::<ButtonSessions>().and_then(|sessions| sessions.get(session)) {
+        Some(sender) => sender.send(click).is_ok(),
+        None => false,
+    };
+    //The session edits the messages itself, so the click only needs to be acknowledged
+    Some(if sent {
+        json!({ "type": DEFERRED_UPDATE_MESSAGE })
+    } else {
+        json!({ "type": CHANNEL_MESSAGE, "data": { "content": "This session is over.", "flags": EPHEMERAL } })
+    })
+}
+
+// Answers an interaction that came through the gateway instead of the interactions endpoint.
+pub async fn respond(interaction: &Value, response: &Value) -> Result<(), reqwest::Error> {
+    let id = interaction["id"].as_str().unwrap_or_default();
+    let token = interaction["token"].as_str().unwrap_or_default();
+    reqwest::Client::new()
+        .post(&format!("{}/interactions/{}/{}/callback", API, id, token))
+        .json(response)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+// Makes a row of buttons, one for each (label, custom id) pair.
+pub fn button_row(buttons: &[(String, String)]) -> Value {
+    let buttons: Vec<Value> = buttons.iter()
+        .map(|(label, custom_id)| json!({ "type": 2, "style": 1, "label": label, "custom_id": custom_id }))
+        .collect();
+    json!([{ "type": 1, "components": buttons }])
+}
+
+// Posts a message with components, which serenity's message builder does not support.
+pub async fn send(http: &Http, channel_id: ChannelId, content: &str, components: &Value) -> Result<MessageId, reqwest::Error> {
+    let message: Value = reqwest::Client::new()
+        .post(&format!("{}/channels/{}/messages", API, channel_id))
+        .header("Authorization", &http.token)
+        .json(&json!({ "content": content, "components": components }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(MessageId(message["id"].as_str().and_then(|id| id.parse().ok()).unwrap_or_default()))
+}
+
+// Changes the text and components of a message posted with send.
+pub async fn edit(http: &Http, channel_id: ChannelId, message_id: MessageId, content: &str, components: &Value) -> Result<(), reqwest::Error> {
+    reqwest::Client::new()
+        .patch(&format!("{}/channels/{}/messages/{}", API, channel_id, message_id))
+        .header("Authorization", &http.token)
+        .json(&json!({ "content": content, "components": components }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+// Makes the text for a button, using the user name for mentions since they are not shown in buttons.
+pub async fn label(cache: &Cache, name: &str) -> String {
+    let name = match parse_username(name) {
+        Some(id) => match cache.user(id).await {
+            Some(user) => user.name,
+            None => name.to_owned(),
+        },
+        None => name.to_owned(),
+    };
+    name.chars().take(80).collect()
+}
@@ -0,0 +1,276 @@
+use serenity::{client::Context, framework::standard::{Args, CommandError, CommandResult, macros::command}, model::{channel::Message, id::{GuildId, UserId}, misc::Mentionable}, utils::parse_username};
+
+use super::{config::{self, GuildConfig}, ladder::{Challenge, Ladder}, manager::Manager, organizers, participants::same_person, ratings::Ratings, schedule, storage};
+
+// Gets the names a person named in a command could have been entered as, looking up server members by mention.
+async fn names_for(manager: &Manager<'_>, name: &str) -> Vec<String> {
+    match parse_username(name) {
+        Some(user_id) => manager.names_of(UserId(user_id)).await,
+        None => vec![name.to_owned()],
+    }
+}
+
+// Checks if the author of a message can run the ladder: anyone with the organizer role or the Manage Server permission.
+async fn runs_ladder(ctx: &Context, guild_id: GuildId, msg: &Message) -> Result<bool, CommandError> {
+    let member = msg.member(&ctx).await?;
+    Ok(organizers::is_organizer(ctx, guild_id, &member).await || member.permissions(&ctx.cache).await?.manage_guild())
+}
+
+// Takes away expired challenges and declares reported results nobody disputed in time, posting what happened.
+// Nothing watches the deadlines, so this runs at the start of `!ladder` and `!challenge` and they are settled by whoever uses one next.
+async fn settle(ctx: &Context, msg: &Message, config: &GuildConfig, ladder: &mut Ladder) -> Result<(), CommandError> {
+    let now = storage::now();
+    for challenge in ladder.expire(now) {
+        msg.channel_id.say(&ctx.http, format!("{}'s challenge to {} expired without a result.", challenge.challenger, challenge.defender)).await?;
+    }
+    for challenge in ladder.unconfirmed(now) {
+        if let Some(report) = challenge.report {
+            if let Ok(played) = ladder.declare(&report.reporter, &report.winner) {
+                msg.channel_id.say(&ctx.http, format!("Nobody disputed {}'s report in time.", report.reporter)).await?;
+                finish(ctx, msg, config, ladder, &played, &report.winner).await?;
+            }
+        }
+    }
+    ladder.save(msg.guild_id.expect("Failed to get guild_id from msg."))?;
+    Ok(())
+}
+
+// Posts the result of a challenge that was declared and counts it toward the server's ratings.
+async fn finish(ctx: &Context, msg: &Message, config: &GuildConfig, ladder: &Ladder, challenge: &Challenge, winner: &str) -> Result<(), CommandError> {
+    let guild_id = msg.guild_id.expect("Failed to get guild_id from msg.");
+    let loser = challenge.opponent(winner).to_owned();
+    let winner = challenge.opponent(&loser).to_owned();
+    let mut ratings = Ratings::load(guild_id);
+    ratings.record(&winner, &loser, config);
+    ratings.save(guild_id)?;
+
+    let place = ladder.position(&winner).map(|index| index + 1).unwrap_or_default();
+    let out = if winner == challenge.challenger {
+        format!("{} beat {} and moves up to #{} on the ladder.", winner, loser, place)
+    } else {
+        format!("{} beat {} and stays at #{} on the ladder.", winner, loser, place)
+    };
+    msg.channel_id.say(&ctx.http, out).await?;
+    Ok(())
+}
+
+// Makes the text of the ladder with the challenges that have not been played.
+fn ladder_text(ladder: &Ladder) -> String {
+    let mut out = String::from("**Ladder**\n");
+    for (index, player) in ladder.players.iter().enumerate() {
+        out.push_str(&format!("{}. {}\n", index + 1, player));
+    }
+    if !ladder.challenges.is_empty() {
+        out.push_str("\n**Challenges**\n");
+    }
+    for challenge in ladder.challenges.iter() {
+        out.push_str(&format!("{} challenged {}, ", challenge.challenger, challenge.defender));
+        match &challenge.report {
+            Some(report) if report.disputed => out.push_str(&format!("{} reported that {} won and it is disputed\n", report.reporter, report.winner)),
+            Some(report) => out.push_str(&format!("{} reported that {} won, which counts {}\n", report.reporter, report.winner, schedule::relative(report.deadline))),
+            None => out.push_str(&format!("expires {}\n", schedule::relative(challenge.deadline))),
+        }
+    }
+    out
+}
+
+#[command]
+#[description = "Shows or changes the server's ladder, where people move up by beating the people above them.\n\
+    \n\
+    `!ladder` on its own shows everyone's places and the challenges that have not been played.\n\
+    `!ladder join` puts you at the bottom of the ladder and `!ladder leave` takes you off it.\n\
+    Challenge someone above you with `!challenge [name]`. Once you have played, use `!ladder lost` or `!ladder won`. \
+    A win has to be confirmed by the other person with `!ladder confirm`, or disputed with `!ladder dispute [reason]`, \
+    and counts after a while if nobody disputes it. \
+    Challenges that run out and results nobody disputed are settled the next time someone uses `!ladder` or `!challenge`. If the challenger wins, they swap places with the person they challenged.\n\
+    Organizers and people with the Manage Server permission can use `!ladder add [name], [name]...`, `!ladder remove [name]` \
+    and `!ladder declare [winner's name]` to settle a challenge.\n\
+    \n\
+    **Sample usage:** `!ladder join`"]
+async fn ladder(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("Failed to get guild_id from msg.");
+    let config = config::get(ctx, guild_id).await;
+    let manager = Manager::new(ctx, guild_id, msg.channel_id);
+    let mut ladder = Ladder::load(guild_id);
+    settle(ctx, msg, &config, &mut ladder).await?;
+
+    let action = args.single::<String>().unwrap_or_default().to_lowercase();
+    let rest = args.rest().trim().to_owned();
+    let me = ladder.find(&manager.names_of(msg.author.id).await);
+
+    match action.as_str() {
+        "" => {
+            if ladder.players.is_empty() {
+                msg.channel_id.say(&ctx.http, format!("Nobody is on the ladder yet. Use `{}` to join it.", config.command("ladder join"))).await?;
+            } else {
+                manager.say_output(&ladder_text(&ladder)).await?;
+            }
+            return Ok(());
+        }
+        "join" => {
+            let name = msg.author.id.mention().to_string();
+            if me.is_some() || !ladder.join(&name) {
+                msg.channel_id.say(&ctx.http, "You are already on the ladder.").await?;
+                return Err(CommandError::from("Already on the ladder."));
+            }
+            msg.channel_id.say(&ctx.http, format!("{} joined the ladder at #{}.", name, ladder.players.len())).await?;
+        }
+        "leave" => match me {
+            Some(name) => {
+                ladder.leave(&name);
+                msg.channel_id.say(&ctx.http, format!("{} left the ladder.", name)).await?;
+            }
+            None => {
+                msg.channel_id.say(&ctx.http, "You are not on the ladder.").await?;
+                return Err(CommandError::from("Not on the ladder."));
+            }
+        },
+        "add" | "remove" | "declare" => {
+            if !runs_ladder(ctx, guild_id, msg).await? {
+                msg.channel_id.say(&ctx.http, "Only organizers and people with the Manage Server permission can do that.").await?;
+                return Err(CommandError::from("Insufficient permissions for user."));
+            }
+            if rest.is_empty() {
+                msg.channel_id.say(&ctx.http, format!("Please enter a name after `{} {}`.", config.command("ladder"), action)).await?;
+                return Err(CommandError::from("Invalid arguments."));
+            }
+            if action == "add" {
+                let added: Vec<String> = rest.split(',').map(|name| name.trim().to_owned())
+                    .filter(|name| !name.is_empty() && ladder.join(name))
+                    .collect();
+                msg.channel_id.say(&ctx.http, format!("Added {} people to the bottom of the ladder.", added.len())).await?;
+            } else {
+                let name = match ladder.find(&names_for(&manager, &rest).await) {
+                    Some(name) => name,
+                    None => {
+                        msg.channel_id.say(&ctx.http, format!("{} is not on the ladder.", rest)).await?;
+                        return Err(CommandError::from("Not on the ladder."));
+                    }
+                };
+                if action == "remove" {
+                    ladder.leave(&name);
+                    msg.channel_id.say(&ctx.http, format!("Took {} off the ladder.", name)).await?;
+                } else {
+                    match ladder.declare(&name, &name) {
+                        Ok(challenge) => finish(ctx, msg, &config, &ladder, &challenge, &name).await?,
+                        Err(_) => {
+                            msg.channel_id.say(&ctx.http, format!("{} does not have a challenge to declare.", name)).await?;
+                            return Err(CommandError::from("No challenge."));
+                        }
+                    }
+                }
+            }
+        }
+        "won" | "lost" | "confirm" | "dispute" => {
+            let (name, challenge) = match me.as_ref().and_then(|name| ladder.challenge_of(name).map(|challenge| (name.clone(), challenge.clone()))) {
+                Some(found) => found,
+                None => {
+                    msg.channel_id.say(&ctx.http, "You do not have a challenge on the ladder.").await?;
+                    return Err(CommandError::from("No challenge."));
+                }
+            };
+            let opponent = challenge.opponent(&name).to_owned();
+            match action.as_str() {
+                //Admitting a loss needs nobody to confirm it
+                "lost" => {
+                    if let Ok(played) = ladder.declare(&name, &opponent) {
+                        finish(ctx, msg, &config, &ladder, &played, &opponent).await?;
+                    }
+                }
+                "won" => {
+                    let deadline = storage::now() + config.report_confirm;
+                    match ladder.report(&name, &name, deadline) {
+                        Ok(challenge) if challenge.report.as_ref().map(|report| report.disputed).unwrap_or(false) => {
+                            let ping = organizers::organizer_role(ctx, guild_id).await.map(|role| format!("{} ", role.mention())).unwrap_or_default();
+                            msg.channel_id.say(&ctx.http, format!("{}Organizers, {} and {} both reported winning {}'s challenge to {}.\n\
+                                Use `{} [winner's name]` to settle it.", ping, name, opponent, challenge.challenger, challenge.defender,
+                                config.command("ladder declare"))).await?;
+                        }
+                        Ok(_) => {
+                            msg.channel_id.say(&ctx.http, format!("{} reported beating {}. {}, use `{}` to confirm it or `{} [reason]` to dispute it. \
+                                It counts {} if nobody disputes it.", name, opponent, opponent, config.command("ladder confirm"),
+                                config.command("ladder dispute"), schedule::relative(deadline))).await?;
+                        }
+                        Err(_) => {
+                            msg.channel_id.say(&ctx.http, "The result of your challenge was disputed, so only the organizers can declare it now.").await?;
+                            return Err(CommandError::from("Disputed."));
+                        }
+                    }
+                }
+                "confirm" => match challenge.report {
+                    Some(report) if !same_person(&report.reporter, &name) => {
+                        if let Ok(played) = ladder.declare(&name, &report.winner) {
+                            finish(ctx, msg, &config, &ladder, &played, &report.winner).await?;
+                        }
+                    }
+                    _ => {
+                        msg.channel_id.say(&ctx.http, format!("{} has not reported a result for you to confirm.", opponent)).await?;
+                        return Err(CommandError::from("Nothing to confirm."));
+                    }
+                },
+                _ => match ladder.dispute(&name) {
+                    Ok(challenge) => {
+                        let ping = organizers::organizer_role(ctx, guild_id).await.map(|role| format!("{} ", role.mention())).unwrap_or_default();
+                        msg.channel_id.say(&ctx.http, format!("{}Organizers, {} disputed the result of {}'s challenge to {}. {}\n\
+                            Use `{} [winner's name]` to settle it.", ping, name, challenge.challenger, challenge.defender, rest,
+                            config.command("ladder declare"))).await?;
+                    }
+                    Err(_) => {
+                        msg.channel_id.say(&ctx.http, format!("{} has not reported a result for you to dispute.", opponent)).await?;
+                        return Err(CommandError::from("Nothing to dispute."));
+                    }
+                },
+            }
+        }
+        _ => {
+            msg.channel_id.say(&ctx.http, format!("{} is not a valid argument. Use `{}help ladder` to see how the ladder works.", action, config.prefix)).await?;
+            return Err(CommandError::from("Invalid arguments."));
+        }
+    }
+
+    ladder.save(guild_id)?;
+    Ok(())
+}
+
+#[command]
+#[description = "Challenges someone above you on the server's ladder.\n\
+    You can challenge people up to 3 places above you, or as many as set with `!config ladder_range`, \
+    and the challenge has to be played within a week, or as long as set with `!config ladder_window`. \
+    Report the result with `!ladder won` or `!ladder lost`.\n\
+    \n\
+    **Sample usage:** `!challenge @Sam`"]
+async fn challenge(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.expect("Failed to get guild_id from msg.");
+    let config = config::get(ctx, guild_id).await;
+    let manager = Manager::new(ctx, guild_id, msg.channel_id);
+    let mut ladder = Ladder::load(guild_id);
+    settle(ctx, msg, &config, &mut ladder).await?;
+
+    let challenger = match ladder.find(&manager.names_of(msg.author.id).await) {
+        Some(name) => name,
+        None => {
+            msg.channel_id.say(&ctx.http, format!("You are not on the ladder. Use `{}` to join it.", config.command("ladder join"))).await?;
+            return Err(CommandError::from("Not on the ladder."));
+        }
+    };
+    let name = args.rest().trim();
+    let defender = match ladder.find(&names_for(&manager, name).await) {
+        Some(defender) if !name.is_empty() => defender,
+        _ => {
+            msg.channel_id.say(&ctx.http, "Please enter the name of someone on the ladder.").await?;
+            return Err(CommandError::from("Not on the ladder."));
+        }
+    };
+
+    let deadline = storage::now() + config.ladder_window;
+    if let Err(error) = ladder.challenge(&challenger, &defender, config.ladder_range, deadline) {
+        msg.channel_id.say(&ctx.http, error.to_string()).await?;
+        return Err(CommandError::from("Invalid challenge."));
+    }
+    ladder.save(guild_id)?;
+    msg.channel_id.say(&ctx.http, format!("{} challenged {} for #{} on the ladder. Play by {}, then report the result with `{}` or `{}`.",
+        challenger, defender, ladder.position(&defender).map(|index| index + 1).unwrap_or_default(), schedule::timestamp(deadline),
+        config.command("ladder won"), config.command("ladder lost"))).await?;
+
+    Ok(())
+}
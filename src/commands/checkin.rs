@@ -0,0 +1,134 @@
+use std::time::{Duration, Instant};
+
+use serenity::{Error, client::Context, futures::StreamExt, collector::ReactionAction, model::{channel::{Message, ReactionType}, id::{ChannelId, UserId}, misc::Mentionable}};
+
+use super::{config::GuildConfig, manager::Manager, organizers::Organizers, participants, schedule, storage};
+
+// The reaction people click to check in.
+const CHECK_IN_EMOJI: &str = "✅";
+
+// How long check-in stays open when -checkin is not followed by a length of time.
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(600);
+
+// Runs check-in before a tournament starts and returns the teams that checked in, in the order they were entered.
+// Participants check in by reacting to the check-in message or with `!checkin`, and can take it back with `!checkout`.
+// Organizers can check anyone in with `!checkin [name]` and start early with `!start`.
+pub async fn run(ctx: &Context, channel_id: ChannelId, config: &GuildConfig, organizers: &Organizers, manager: &Manager<'_>,
+    teams: &[String], window: Duration) -> Result<Vec<String>, Error> {
+    let deadline = Instant::now() + window;
+    let mut checked_in: Vec<String> = Vec::new();
+    let mut message = channel_id.say(&ctx.http, status_text(config, teams, &checked_in, storage::now() + window.as_secs())).await?;
+    message.react(&ctx.http, ReactionType::Unicode(CHECK_IN_EMOJI.to_owned())).await?;
+    let mut reactions = message.await_reactions(&ctx).added(true).removed(true).await;
+    let bot_id = ctx.cache.current_user_id().await;
+
+    let commands = [config.command("checkin"), config.command("checkout"), config.command("start")];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining == Duration::from_secs(0) {
+            break;
+        }
+        let filter_commands = commands.clone();
+        let filter = move |_: &Message, content: &str| filter_commands.iter().any(|command| content.split_whitespace().next() == Some(command.as_str()));
+
+        let changed = tokio::select! {
+            Some(action) = reactions.next() => {
+                let (reaction, added) = match &*action {
+                    ReactionAction::Added(reaction) => (reaction, true),
+                    ReactionAction::Removed(reaction) => (reaction, false),
+                };
+                match reaction.user_id {
+                    Some(user_id) if user_id != bot_id && reaction.emoji == ReactionType::Unicode(CHECK_IN_EMOJI.to_owned()) => {
+                        set_checked_in(manager, teams, &mut checked_in, user_id, added).await
+                    }
+                    _ => false,
+                }
+            }
+            reply = organizers.session.await_reply(ctx, remaining, filter) => {
+                let reply = match reply {
+                    Some(reply) => reply,
+                    None => break,
+                };
+                let mut words = reply.content.splitn(2, ' ');
+                let command = words.next().unwrap_or_default();
+                let name = words.next().unwrap_or_default().trim();
+                if command == commands[2] {
+                    if organizers.accepts(&reply) {
+                        break;
+                    }
+                    channel_id.say(&ctx.http, "Only the organizers can start the tournament early.").await?;
+                    false
+                } else if command == commands[0] && !name.is_empty() {
+                    //Organizers can check in people who can't do it themselves, like teams that are not server members
+                    match participants::find_person(teams, &[name.to_owned()]) {
+                        Some(team) if organizers.accepts(&reply) => {
+                            if !checked_in.contains(team) {
+                                checked_in.push(team.clone());
+                            }
+                            true
+                        }
+                        Some(_) => {
+                            channel_id.say(&ctx.http, "Only the organizers can check in other people.").await?;
+                            false
+                        }
+                        None => {
+                            channel_id.say(&ctx.http, format!("{} is not registered for this tournament.", name)).await?;
+                            false
+                        }
+                    }
+                } else {
+                    let changed = set_checked_in(manager, teams, &mut checked_in, reply.author.id, command == commands[0]).await;
+                    if !changed && participants::find_person(teams, &manager.names_of(reply.author.id).await).is_none() {
+                        channel_id.say(&ctx.http, format!("{}, you are not registered for this tournament.", reply.author.id.mention())).await?;
+                    }
+                    changed
+                }
+            }
+        };
+        if changed {
+            let deadline_time = storage::now() + deadline.saturating_duration_since(Instant::now()).as_secs();
+            message.edit(&ctx, |m| m.content(status_text(config, teams, &checked_in, deadline_time))).await?;
+        }
+    }
+
+    //Keeping the order the teams were entered in so seeding is not changed by the order people checked in
+    let checked_in: Vec<String> = teams.iter().filter(|team| checked_in.contains(team)).cloned().collect();
+    let missing: Vec<String> = teams.iter().filter(|team| !checked_in.contains(team)).cloned().collect();
+    let mut out = format!("Check-in is closed. {} of {} participants checked in.", checked_in.len(), teams.len());
+    if !missing.is_empty() {
+        out.push_str(&format!("\nDropped because they did not check in: {}", missing.join(", ")));
+    }
+    channel_id.say(&ctx.http, out).await?;
+    Ok(checked_in)
+}
+
+// Checks a user in or out if they are registered. Returns true if anything changed.
+async fn set_checked_in(manager: &Manager<'_>, teams: &[String], checked_in: &mut Vec<String>, user_id: UserId, check_in: bool) -> bool {
+    let team = match participants::find_person(teams, &manager.names_of(user_id).await) {
+        Some(team) => team,
+        None => return false,
+    };
+    let already = checked_in.contains(team);
+    if check_in && !already {
+        checked_in.push(team.clone());
+        true
+    } else if !check_in && already {
+        checked_in.retain(|checked| checked != team);
+        true
+    } else {
+        false
+    }
+}
+
+// Makes the text of the check-in message with who has checked in so far.
+fn status_text(config: &GuildConfig, teams: &[String], checked_in: &[String], deadline: u64) -> String {
+    let waiting: Vec<&String> = teams.iter().filter(|team| !checked_in.contains(team)).collect();
+    let mut out = format!("**Check-in** closes {}. React with {} or use `{}` to check in, and `{}` to take it back.\n\
+        {} of {} participants have checked in.",
+        schedule::relative(deadline), CHECK_IN_EMOJI, config.command("checkin"), config.command("checkout"), checked_in.len(), teams.len());
+    if !waiting.is_empty() && waiting.len() <= 50 {
+        let names: Vec<&str> = waiting.iter().map(|team| team.as_str()).collect();
+        out.push_str(&format!("\nWaiting for: {}", names.join(", ")));
+    }
+    out
+}
@@ -0,0 +1,304 @@
+use std::{collections::HashMap, io, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serenity::{client::Context, framework::standard::{Args, CommandError, CommandResult, Delimiter, macros::command}, model::{channel::Message, id::GuildId}, prelude::{RwLock, TypeMap, TypeMapKey}};
+
+use super::storage;
+
+// How results like groups and brackets are posted.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStyle {
+    // As a regular message.
+    Plain,
+    // Inside a code block.
+    Code,
+    // As an embed.
+    Embed,
+}
+
+// How ratings change after rated games.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RatingSystem {
+    // Elo, which moves ratings by up to the guild's K factor each game.
+    Elo,
+    // Glicko-2, which also tracks how sure each rating is.
+    Glicko2,
+}
+
+// The settings for a guild.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct GuildConfig {
+    // The prefix that commands start with.
+    pub prefix: String,
+    // How many seconds to wait for names to be entered before making groups and teams.
+    pub timeout: u64,
+    // How many seconds to wait for results to be declared before a tournament ends.
+    pub tournament_timeout: u64,
+    // How many seconds before a scheduled tournament game to remind the teams.
+    pub reminder: u64,
+    // How many seconds after a scheduled tournament game starts to post that it is overdue if it has no result.
+    pub overdue: u64,
+    // How many seconds a team has to confirm or dispute a result the other team reported before it counts.
+    pub report_confirm: u64,
+    pub rating_system: RatingSystem,
+    // The most an Elo rating can change in one game.
+    pub rating_k: f64,
+    // How many places above themselves people on the ladder can challenge.
+    pub ladder_range: usize,
+    // How many seconds a ladder challenge has to be played in before it expires.
+    pub ladder_window: u64,
+    // Flags that are added to every use of a command, keyed by the command name.
+    pub default_flags: HashMap<String, Vec<String>>,
+    // The name of the role that is allowed to run and control any session.
+    pub organizer_role: Option<String>,
+    pub output_style: OutputStyle,
+}
+
+impl Default for GuildConfig {
+    fn default() -> Self {
+        GuildConfig {
+            prefix: "!".to_owned(),
+            timeout: 600,
+            tournament_timeout: 6000,
+            reminder: 900,
+            overdue: 1800,
+            report_confirm: 900,
+            rating_system: RatingSystem::Elo,
+            rating_k: 32.0,
+            ladder_range: 3,
+            ladder_window: 604800,
+            default_flags: HashMap::new(),
+            organizer_role: None,
+            output_style: OutputStyle::Plain,
+        }
+    }
+}
+
+impl GuildConfig {
+    // Gets how long to wait for names to be entered.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+
+    // Gets how long to wait for tournament results.
+    pub fn tournament_timeout(&self) -> Duration {
+        Duration::from_secs(self.tournament_timeout)
+    }
+
+    // Makes the text of a command in this guild, like "!stop" for "stop".
+    pub fn command(&self, name: &str) -> String {
+        format!("{}{}", self.prefix, name)
+    }
+
+    // Adds the default flags for a command to the end of its arguments.
+    pub fn with_default_flags(&self, command: &str, args: Args) -> Args {
+        match self.default_flags.get(command) {
+            Some(flags) if !flags.is_empty() => {
+                let text = format!("{} {}", args.rest(), flags.join(" "));
+                Args::new(text.trim(), &[Delimiter::Single(' ')])
+            }
+            _ => args,
+        }
+    }
+}
+
+// Key for the settings of every guild that has been loaded, kept in the client's data.
+pub struct GuildConfigs;
+
+impl TypeMapKey for GuildConfigs {
+    type Value = HashMap<GuildId, GuildConfig>;
+}
+
+// Gets the settings for a guild, loading them if they have not been used yet.
+pub async fn get(ctx: &Context, guild_id: GuildId) -> GuildConfig {
+    get_from(&ctx.data, guild_id).await
+}
+
+// Gets the settings for a guild from the client's data when there is no Context, like for slash commands.
+pub async fn get_from(data: &RwLock<TypeMap>, guild_id: GuildId) -> GuildConfig {
+    if let Some(config) = data.read().await.get::<GuildConfigs>().and_then(|configs| configs.get(&guild_id)) {
+        return config.clone();
+    }
+    let config: GuildConfig = storage::load(guild_id, "config");
+    if let Some(configs) = data.write().await.get_mut::<GuildConfigs>() {
+        configs.insert(guild_id, config.clone());
+    }
+    config
+}
+
+// Saves new settings for a guild.
+pub async fn set(ctx: &Context, guild_id: GuildId, config: GuildConfig) -> io::Result<()> {
+    storage::save(guild_id, "config", &config)?;
+    if let Some(configs) = ctx.data.write().await.get_mut::<GuildConfigs>() {
+        configs.insert(guild_id, config);
+    }
+    Ok(())
+}
+
+// Makes the text listing all of a guild's settings.
+fn describe(config: &GuildConfig) -> String {
+    let mut out = String::from("**Settings**\n");
+    out.push_str(&format!("prefix: `{}`\n", config.prefix));
+    out.push_str(&format!("timeout: {} seconds\n", config.timeout));
+    out.push_str(&format!("tournament_timeout: {} seconds\n", config.tournament_timeout));
+    out.push_str(&format!("reminder: {} seconds\n", config.reminder));
+    out.push_str(&format!("overdue: {} seconds\n", config.overdue));
+    out.push_str(&format!("report_confirm: {} seconds\n", config.report_confirm));
+    out.push_str(&format!("rating: {}\n", match config.rating_system {
+        RatingSystem::Elo => format!("elo (K {})", config.rating_k),
+        RatingSystem::Glicko2 => "glicko".to_owned(),
+    }));
+    out.push_str(&format!("ladder_range: {} places\n", config.ladder_range));
+    out.push_str(&format!("ladder_window: {} seconds\n", config.ladder_window));
+    out.push_str(&format!("organizer: {}\n", config.organizer_role.as_deref().unwrap_or("none")));
+    out.push_str(&format!("output: {}\n", match config.output_style {
+        OutputStyle::Plain => "plain",
+        OutputStyle::Code => "code",
+        OutputStyle::Embed => "embed",
+    }));
+    out.push_str("flags:");
+    if config.default_flags.is_empty() {
+        out.push_str(" none");
+    }
+    for (command, flags) in config.default_flags.iter() {
+        out.push_str(&format!("\n    {}: {}", command, flags.join(" ")));
+    }
+    out
+}
+
+
+
+
+#[command]
+#[description = "Shows or changes the settings for this server.\n\
+    \n\
+    Use `!config` on its own to see the settings. Changing them requires the Manage Server permission.\n\
+    \n\
+    `!config prefix [prefix]` changes what commands start with.\n\
+    `!config timeout [seconds]` changes how long to wait for names to be entered.\n\
+    `!config tournament_timeout [seconds]` changes how long to wait for tournament results.\n\
+    `!config reminder [seconds]` changes how long before a scheduled game the teams are reminded.\n\
+    `!config overdue [seconds]` changes how long after a scheduled game starts it is posted as overdue if it has no result.\n\
+    `!config report_confirm [seconds]` changes how long a team has to confirm or dispute a result the other team reported.\n\
+    `!config rating [elo/glicko]` changes how ratings are worked out after tournament games, and `!config k [number]` changes how much Elo ratings can move in one game.\n\
+    `!config ladder_range [number]` changes how many places above themselves people on the ladder can challenge, \
+    and `!config ladder_window [seconds]` changes how long a challenge has to be played in.\n\
+    `!config flags [command] [flags]` sets flags that are always added to a command. Leave the flags out to clear them.\n\
+    `!config organizer [role name]` sets the organizer role. Use `none` to remove it.\n\
+    `!config output [plain/code/embed]` changes how results are posted.\n\
+    `!config reset` goes back to the default settings.\n\
+    \n\
+    **Sample usage:** `!config flags group -random -channel`"]
+async fn config(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+
+    let guild_id = msg.guild_id.expect("Failed to get guild_id from msg.");
+    let mut config = get(ctx, guild_id).await;
+
+    let setting = match args.single::<String>() {
+        Ok(setting) => setting,
+        Err(_) => {
+            msg.channel_id.say(&ctx.http, describe(&config)).await?;
+            return Ok(());
+        }
+    };
+
+    //Checking if the user is allowed to change settings
+    let member = msg.member(&ctx).await?;
+    let member_permissions = member.permissions(&ctx.cache).await?;
+    if !member_permissions.manage_guild() {
+        msg.channel_id.say(&ctx.http, "You need the Manage Server permission to change settings.").await?;
+        return Err(CommandError::from("Insufficient permissions for user."));
+    }
+
+    let value = args.rest().trim().to_owned();
+    let invalid = match setting.as_str() {
+        "prefix" if !value.is_empty() && !value.contains(char::is_whitespace) => {
+            config.prefix = value;
+            false
+        }
+        "timeout" | "tournament_timeout" | "reminder" | "overdue" | "report_confirm" | "ladder_window" => match value.parse::<u64>() {
+            Ok(seconds) if seconds > 0 => {
+                match setting.as_str() {
+                    "timeout" => config.timeout = seconds,
+                    "tournament_timeout" => config.tournament_timeout = seconds,
+                    "reminder" => config.reminder = seconds,
+                    "report_confirm" => config.report_confirm = seconds,
+                    "ladder_window" => config.ladder_window = seconds,
+                    _ => config.overdue = seconds,
+                }
+                false
+            }
+            _ => true,
+        },
+        "rating" => {
+            let system = match value.to_lowercase().as_str() {
+                "elo" => Some(RatingSystem::Elo),
+                "glicko" | "glicko2" | "glicko-2" => Some(RatingSystem::Glicko2),
+                _ => None,
+            };
+            if let Some(system) = system {
+                config.rating_system = system;
+            }
+            system.is_none()
+        }
+        "ladder_range" => match value.parse::<usize>() {
+            Ok(range) if range > 0 => {
+                config.ladder_range = range;
+                false
+            }
+            _ => true,
+        },
+        "k" => match value.parse::<f64>() {
+            Ok(k) if k > 0.0 && k.is_finite() => {
+                config.rating_k = k;
+                false
+            }
+            _ => true,
+        },
+        "flags" if !value.is_empty() => {
+            let mut words = value.split_whitespace();
+            let command = words.next().unwrap_or_default().to_lowercase();
+            let flags: Vec<String> = words.map(|word| word.to_owned()).collect();
+            if flags.is_empty() {
+                config.default_flags.remove(&command);
+            } else {
+                config.default_flags.insert(command, flags);
+            }
+            false
+        }
+        "organizer" if !value.is_empty() => {
+            config.organizer_role = if value.eq_ignore_ascii_case("none") { None } else { Some(value) };
+            false
+        }
+        "output" => {
+            let style = match value.to_lowercase().as_str() {
+                "plain" => Some(OutputStyle::Plain),
+                "code" => Some(OutputStyle::Code),
+                "embed" => Some(OutputStyle::Embed),
+                _ => None,
+            };
+            if let Some(style) = style {
+                config.output_style = style;
+            }
+            style.is_none()
+        }
+        "reset" => {
+            config = GuildConfig::default();
+            false
+        }
+        _ => true,
+    };
+
+    if invalid {
+        msg.channel_id.say(&ctx.http, format!("That is not a valid setting. Use `{}help config` to see the settings.", config.prefix)).await?;
+        return Err(CommandError::from("Invalid arguments."));
+    }
+
+    set(ctx, guild_id, config.clone()).await?;
+    msg.channel_id.say(&ctx.http, describe(&config)).await?;
+
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use std::time::{Duration, Instant};
+
+use linked_hash_map::LinkedHashMap;
+use rand::{prelude::SliceRandom, thread_rng};
+use serenity::{client::Context, framework::standard::{Args, CommandError, CommandResult, macros::command}, model::{channel::Message, id::UserId}, utils::parse_username};
+
+use super::{config, organizers::Organizers, manager::format_teams, participants::{self, Tags, same_person}};
+
+// The most characters Discord allows in a message.
+const MESSAGE_LIMIT: usize = 2000;
+
+
+
+
+#[command]
+#[description = "Lets captains take turns picking people for their teams.\n\
+    \n\
+    Captains are the arguments that are not flags. Captains that are mentioned pick for themselves with `!pick [name or number]`, \
+    and the person who started the draft picks for the others.\n\
+    \n\
+    You must use `!stop` to stop adding people to the pool.\n\
+    \n\
+    Picks go in the same order every round unless the `-snake` argument is used to reverse the order every other round. \
+    Each pick has a 60 second timer that can be changed with `-timer [seconds]`, and the first person left in the pool is picked when it runs out.\n\
+    \n\
+    **Sample usage:** `!draft @captain1 @captain2 -snake -all -random -timer 30`"]
+async fn draft(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+
+    let guild_id = msg.guild_id.expect("Failed to get guild_id from msg");
+    let config = config::get(ctx, guild_id).await;
+    let mut args = config.with_default_flags("draft", args);
+
+    let mut teams: LinkedHashMap<String, Vec<String>> = LinkedHashMap::new();
+
+    //Setting to true if arguments are present
+    //all adds all members of the discord server to the pool
+    //random shuffles the pool
+    //snake reverses the pick order every other round
+    let mut all = false;
+    let mut random = false;
+    let mut snake = false;
+    let mut timer: u64 = 60;
+
+    while !args.is_empty() {
+        if let Ok(arg) = args.single::<String>(){
+            if arg == "-all" {
+                all = true;
+            } else if arg == "-random" {
+                random = true;
+            } else if arg == "-snake" {
+                snake = true;
+            } else if arg == "-timer" {
+                match args.single::<u64>() {
+                    Ok(seconds) if seconds > 0 => timer = seconds,
+                    _ => {
+                        msg.channel_id.say(&ctx.http, "-timer must be followed by a number of seconds.").await?;
+                        return Err(CommandError::from("Invalid arguments."));
+                    }
+                }
+            } else if arg.starts_with('-') {
+                msg.channel_id.say(&ctx.http,format!("{} is not a valid argument.", arg)).await?;
+            } else {
+                teams.insert(arg, Vec::new());
+            }
+        }
+    }
+
+    if teams.len() < 2 {
+        msg.channel_id.say(&ctx.http, "Please enter at least 2 captains.").await?;
+        return Err(CommandError::from("Not enough captains."));
+    }
+
+    //Stores the people that can be picked
+    let mut pool: Vec<String> = Vec::new();
+
+    if all {
+        let members = guild_id.members(&ctx.http, None, None).await?;
+        msg.channel_id.say(&ctx.http,"-\nAdding all channel members to the pool\n-").await?;
+        for member in members.iter() {
+            pool.push(member.user.to_string());
+        }
+    }
+
+    //Asking the user to input names
+    msg.channel_id.say(&ctx.http, format!("{} is starting a draft with {} captains.\n\
+        Please enter the names to put in the pool or `{}` to stop.\n\
+        You may enter names one at a time or as a comma separated list.", msg.author, teams.len(), config.command("stop"))).await?;
+    //Taking input from the organizers with up to the server's timeout (10 minutes by default)
+    let organizers = Organizers::new(ctx, msg, "draft").await;
+    let mut answer = organizers.await_reply(ctx, config.timeout()).await;
+
+    // Stops the loop and starting the draft if the user does `!stop`
+    // or adds more people to the pool from user inputs
+    while let Some(message) = answer {
+        if message.content == config.command("stop") {
+            answer = None;
+        } else {
+            msg.channel_id.say(&ctx.http,"Adding them.").await?;
+
+            participants::parse_names(&message.content, &mut pool, &mut Tags::new());
+
+            answer = organizers.await_reply(ctx, config.timeout()).await;
+        }
+    }
+
+    //Captains can't be picked
+    let captains: Vec<String> = teams.keys().map(|k| k.to_owned()).collect();
+    pool.retain(|person| !captains.iter().any(|captain| same_person(captain, person)));
+
+    //Shuffles the order of the pool before the draft starts.
+    if random {
+        pool.shuffle(&mut thread_rng());
+    }
+
+    let stop_command = config.command("stop");
+    let pick_command = config.command("pick");
+    let mut board = msg.channel_id.say(&ctx.http, board_text(&teams, &pool, None, timer, &pick_command)).await?;
+
+    let mut round = 0;
+    while !pool.is_empty() {
+        let mut order = captains.clone();
+        if snake && round % 2 == 1 {
+            order.reverse();
+        }
+
+        for captain in order.iter() {
+            if pool.is_empty() {
+                break;
+            }
+            board.edit(&ctx, |m| m.content(board_text(&teams, &pool, Some(captain), timer, &pick_command))).await?;
+
+            //Mentioned captains pick for themselves and the person running the draft picks for the rest
+            let picker = parse_username(captain).map(UserId).unwrap_or(msg.author.id);
+            let deadline = Instant::now() + Duration::from_secs(timer);
+            let mut picked: Option<usize> = None;
+            while picked.is_none() {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.as_secs() == 0 {
+                    break;
+                }
+                //Organizers can also stop the draft at any time
+                let (stop, pick, session) = (stop_command.clone(), pick_command.clone(), organizers.clone());
+                let reply = organizers.session.await_reply(ctx, remaining, move |message, content| (message.author.id == picker && content.starts_with(&pick))
+                    || (content == stop && session.accepts(message))).await;
+                match reply {
+                    Some(message) if message.content == stop_command => {
+                        msg.channel_id.say(&ctx.http, "The draft has been ended.").await?;
+                        return Ok(());
+                    }
+                    Some(message) => {
+                        let choice = message.content.trim_start_matches(&pick_command).trim();
+                        picked = find_in_pool(&pool, choice);
+                        if picked.is_none() {
+                            msg.channel_id.say(&ctx.http, format!("{} is not in the pool.", choice)).await?;
+                        }
+                    }
+                    None => break,
+                }
+            }
+
+            //Picking the first person left in the pool when time runs out
+            let index = match picked {
+                Some(index) => index,
+                None => {
+                    msg.channel_id.say(&ctx.http, format!("Time is up, {} was picked for {}.", pool[0], captain)).await?;
+                    0
+                }
+            };
+            let person = pool.remove(index);
+            teams.get_mut(captain).expect("Failed to get team from key").push(person);
+        }
+        round += 1;
+    }
+
+    board.edit(&ctx, |m| m.content(board_text(&teams, &pool, None, timer, &pick_command))).await?;
+    msg.channel_id.say(&ctx.http, "The draft is over!").await?;
+
+    Ok(())
+}
+
+// Makes the text of the draft board with the teams so far, who is left in the pool, and whose turn it is.
+// The board is cut down to fit in one message, leaving out the end of the pool first.
+fn board_text(teams: &LinkedHashMap<String, Vec<String>>, pool: &[String], picking: Option<&String>, timer: u64, pick_command: &str) -> String {
+    let footer = match picking {
+        Some(captain) => format!("\nNow picking: {} ({} seconds)\nUse `{} [name or number]`.", captain, timer, pick_command),
+        None => String::new(),
+    };
+    //Room for the footer and the line saying how many people were left out
+    let limit = MESSAGE_LIMIT.saturating_sub(footer.len() + 40);
+
+    let mut out = String::from("**Draft board**\n");
+    out.push_str(&format_teams(teams));
+    if !pool.is_empty() {
+        out.push_str("Pool:\n");
+        for (index, person) in pool.iter().enumerate() {
+            let line = format!("    {}. {}\n", index + 1, person);
+            if out.len() + line.len() > limit {
+                out.push_str(&format!("    ...and {} more\n", pool.len() - index));
+                break;
+            }
+            out.push_str(&line);
+        }
+    }
+    //The teams alone can be too long when the draft has a lot of people
+    if out.len() > limit {
+        let mut end = limit;
+        while !out.is_char_boundary(end) {
+            end -= 1;
+        }
+        let end = out[..end].rfind('\n').unwrap_or(end);
+        out.truncate(end);
+        out.push_str("\n...\n");
+    }
+    out.push_str(&footer);
+    out
+}
+
+// Finds a person in the pool by their number on the board or by their name.
+fn find_in_pool(pool: &[String], choice: &str) -> Option<usize> {
+    if let Ok(number) = choice.parse::<usize>() {
+        if number >= 1 && number <= pool.len() {
+            return Some(number - 1);
+        }
+    }
+    pool.iter().position(|person| same_person(person, choice))
+}
@@ -0,0 +1,43 @@
+use serenity::{client::Context, framework::standard::{Args, CommandError, CommandResult, macros::command}, model::channel::Message};
+
+use super::results::{self, ExportFormat};
+
+
+
+
+#[command]
+#[description = "Attaches a file with the results of a finished session.\n\
+    \n\
+    The file has the teams and their members, user ids and seeds, or the games of a tournament with their results and when they were declared. \
+    Leave out the session id to get the last session in this server. The file is a CSV file unless `json` is given.\n\
+    Use `bracket` to get a tournament as a bracket file that other bracket tools and `!tournament` can import.\n\
+    \n\
+    **Sample usage:** `!export 3 json`"]
+async fn export(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+
+    let guild_id = msg.guild_id.expect("Failed to get guild_id from msg.");
+
+    let mut session: Option<u32> = None;
+    let mut format = ExportFormat::Csv;
+    while !args.is_empty() {
+        let arg = args.single::<String>()?;
+        if let Ok(id) = arg.parse::<u32>() {
+            session = Some(id);
+        } else if let Some(chosen) = ExportFormat::parse(&arg) {
+            format = chosen;
+        } else {
+            msg.channel_id.say(&ctx.http, format!("{} is not a valid argument.", arg)).await?;
+            return Err(CommandError::from("Invalid arguments."));
+        }
+    }
+
+    match results::find(guild_id, session) {
+        Some(result) => results::send(ctx, msg.channel_id, &result, format).await?,
+        None => {
+            msg.channel_id.say(&ctx.http, "There are no saved results for that session.").await?;
+            return Err(CommandError::from("No results."));
+        }
+    }
+
+    Ok(())
+}
@@ -5,32 +5,80 @@ use std::{sync::{Arc, Mutex}};
 
 pub struct Game {
     pub id: String,
-    pub top_team: String,
-    pub bottom_team: String,
-    pub winner: String,
+    //The team in each slot of the game, empty where nobody has made it to the game yet.
+    //Bracket games have a top and a bottom slot, and free-for-all heats have a slot for everyone racing.
+    pub teams: Vec<String>,
+    //The teams in the order they finished once the game is declared, with the winner first.
+    //Teams left out of a heat's ranking finished behind everyone in it.
+    pub ranking: Vec<String>,
+    //How many of the best placed teams move on to the next game.
+    pub advance: usize,
     pub next_game: Option<Arc<Mutex<Game>>>,
+    //The game the loser moves on to, like the third place game for the semifinals.
+    pub loser_game: Option<Arc<Mutex<Game>>>,
+    //When the game is scheduled to start in seconds since the Unix epoch, if it has been scheduled.
+    pub start: Option<u64>,
 }
 
 impl Game {
     pub fn new(id: String, top_team: String, bottom_team: String, next_game: Option<Arc<Mutex<Game>>>) -> Self {
         Game {
             id,
-            top_team,
-            bottom_team,
-            winner: String::new(),
+            teams: vec![top_team, bottom_team],
+            ranking: Vec::new(),
+            advance: 1,
             next_game,
+            loser_game: None,
+            start: None,
         }
     }
 
-    //Adds the team to itself if they are not filled. If they are filled,
+    //Makes an empty heat with room for slots teams, where the best advance of them move on.
+    pub fn heat(id: String, slots: usize, advance: usize) -> Self {
+        Game {
+            id,
+            teams: vec![String::new(); slots],
+            ranking: Vec::new(),
+            advance,
+            next_game: None,
+            loser_game: None,
+            start: None,
+        }
+    }
+
+    pub fn top_team(&self) -> &str {
+        self.teams.first().map(String::as_str).unwrap_or_default()
+    }
+
+    pub fn bottom_team(&self) -> &str {
+        self.teams.get(1).map(String::as_str).unwrap_or_default()
+    }
+
+    //Gets the team that finished first, which is empty if the game has not been played.
+    pub fn winner(&self) -> &str {
+        self.ranking.first().map(String::as_str).unwrap_or_default()
+    }
+
+    //Gets the team that finished last, which is empty if the game has not been played or had no opponent.
+    pub fn loser(&self) -> String {
+        if self.ranking.len() < 2 {
+            String::new()
+        } else {
+            self.ranking[self.ranking.len() - 1].clone()
+        }
+    }
+
+    //Gets the teams that are in the game.
+    pub fn entrants(&self) -> Vec<String> {
+        self.teams.iter().filter(|team| !team.is_empty()).cloned().collect()
+    }
+
+    //Adds the team to the first empty slot. If every slot is filled,
     //returns an error.
     pub fn add_team(&mut self, name: String) -> Result<(), &str> {
-        if self.top_team == "" {
-            self.top_team = name;
-        } else if self.bottom_team == "" {
-            self.bottom_team = name;
-        } else {
-            return Err("Teams already set.");
+        match self.teams.iter_mut().find(|team| team.is_empty()) {
+            Some(slot) => *slot = name,
+            None => return Err("Teams already set."),
         }
         Ok(())
     }
@@ -39,18 +87,58 @@ impl Game {
         self.next_game = game;
     }
 
+    pub fn set_loser_game(&mut self, game: Option<Arc<Mutex<Game>>>) {
+        self.loser_game = game;
+    }
+
+    //Gets the ranking of a game the winner won, with everyone else after them in slot order.
+    pub fn ranking_for(&self, winner: &str) -> Vec<String> {
+        let mut ranking = vec![winner.to_owned()];
+        ranking.extend(self.entrants().into_iter().filter(|team| team != winner));
+        ranking
+    }
+
     //Sets the winner for the current game and updates the participants in the next game.
-    pub fn winner(&mut self, winner: String) {
-        self.winner = winner;
+    pub fn set_winner(&mut self, winner: String) {
+        let ranking = self.ranking_for(&winner);
+        self.declare(ranking);
+    }
+
+    //Sets the order the teams finished in and moves the best placed teams on to the next game, and the loser on to theirs.
+    pub fn declare(&mut self, ranking: Vec<String>) {
+        self.ranking = ranking;
 
-        match &self.next_game {
-            Some(game) => {
-                let mut guard = game.lock().expect("There was an unknown error.");
-                guard.add_team(self.winner.clone()).expect("Teams already full.");
+        if let Some(game) = &self.next_game {
+            let mut guard = game.lock().expect("There was an unknown error.");
+            for team in self.ranking.iter().take(self.advance) {
+                guard.add_team(team.clone()).expect("Teams already full.");
             }
-            
-            None => ()
         }
+        let loser = self.loser();
+        if let (Some(game), false) = (&self.loser_game, loser.is_empty()) {
+            game.lock().expect("There was an unknown error.").add_team(loser).expect("Teams already full.");
+        }
+    }
+
+    //Takes back the result of the current game and removes the teams that moved on from the next game, and the loser from theirs.
+    pub fn clear_winner(&mut self) {
+        if let Some(game) = &self.next_game {
+            let mut guard = game.lock().expect("There was an unknown error.");
+            for team in self.ranking.iter().take(self.advance) {
+                guard.remove_team(team);
+            }
+        }
+        let loser = self.loser();
+        if let (Some(game), false) = (&self.loser_game, loser.is_empty()) {
+            game.lock().expect("There was an unknown error.").remove_team(&loser);
+        }
+        self.ranking = Vec::new();
+    }
 
+    //Empties the spot of a team that was added with add_team.
+    pub fn remove_team(&mut self, name: &str) {
+        if let Some(slot) = self.teams.iter_mut().rev().find(|team| *team == name) {
+            *slot = String::new();
+        }
     }
 }
@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::collections::HashMap;
 
 use linked_hash_map::LinkedHashMap;
 use rand::{prelude::SliceRandom, thread_rng};
@@ -10,7 +10,7 @@ use serenity::framework::standard::{
     macros::command,
 };
 
-use super::manager::Manager;
+use super::{config, organizers::{self, Organizers}, grouping::{self, Remainder, Strategy}, history::GroupHistory, manager::Manager, participants::{self, Tags}, ratings::Ratings, results::{self, ExportFormat, SessionResult}};
 
 
 
@@ -18,16 +18,30 @@ use super::manager::Manager;
 #[command]
 #[description = "Makes numbered groups of people.\n\
     \n\
-    You must use `!stop` to stop adding people to groups.\n\
+    You must use `!stop` to stop adding people to groups. Only you can add people, and anyone with the organizer role can also stop.\n\
     \n\
-    To make groups of a size add the `-size` argument, and the number of groups will be automatically generated based on the number you entered as the first argument.
+    To make groups of a size add the `-size` argument, and the number of groups will be automatically generated based on the number you entered as the first argument \
+    after everyone has been entered. Extra people are spread out over the groups unless the `-overflow` argument is used to put them in one smaller group.
+    \n\
+    To keep group sizes in a range add `-min [number]` and/or `-max [number]`.
+    \n\
+    To keep people from being grouped with the same people as last time add the `-rotate` argument.
+    \n\
+    To balance the groups by tags add the `-stratify` argument. To only balance some tags, list them after an equals sign, like `-stratify=Mentor,EU`. \
+    Tags are the Discord roles of server members and words starting with # after an entered name, like `Sam #mentor #EU`.
+    \n\
+    To even out the groups by skill add the `-balance` argument. It uses the ratings from tournament games played in this server, or a rating column in an attached CSV file.
+    \n\
+    To send everyone a direct message with their group, the people in it and its channel add the `-dm` argument.
     \n\
     The following example adds everyone in the discord server to 10 randomly assigned groups, makes a role for each group, and makes a channel only for that role.\n
     \n\
     **Sample usage:** `!group 10 -random -all -channel -role`"]
-async fn group(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+async fn group(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 
     let guild_id = msg.guild_id.expect("Failed to get guild_id from msg.");
+    let config = config::get(ctx, guild_id).await;
+    let mut args = config.with_default_flags("group", args);
     let manager = Manager::new(ctx, guild_id, msg.channel_id);
 
 
@@ -54,6 +68,15 @@ async fn group(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let mut role = false;
     let mut channel = false;
     let mut size = false;
+    let mut rotate = false;
+    let mut remainder = Remainder::Spread;
+    let mut min: usize = 0;
+    let mut max: usize = usize::MAX;
+    let mut stratify = false;
+    let mut balance_tags: Vec<String> = Vec::new();
+    let mut export: Option<ExportFormat> = None;
+    let mut dm = false;
+    let mut balance = false;
 
     //Checking for flags
     while !args.is_empty() {
@@ -66,8 +89,40 @@ async fn group(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
                 role = true;
             } else if arg == "-channel" {
                 channel = true;
+            } else if arg == "-dm" {
+                dm = true;
+            } else if arg == "-balance" {
+                balance = true;
             } else if arg == "-size" {
                 size = true;
+            } else if arg == "-rotate" {
+                rotate = true;
+            } else if arg == "-overflow" {
+                remainder = Remainder::Overflow;
+            } else if arg == "-min" || arg == "-max" {
+                match args.single::<usize>() {
+                    Ok(bound) if arg == "-min" => min = bound,
+                    Ok(bound) => max = bound,
+                    Err(_) => {
+                        msg.channel_id.say(&ctx.http, format!("{} must be followed by a number.", arg)).await?;
+                        return Err(CommandError::from("Invalid arguments."));
+                    }
+                }
+            } else if arg == "-export" {
+                match args.single::<String>().ok().and_then(|name| ExportFormat::parse(&name)) {
+                    Some(format) => export = Some(format),
+                    None => {
+                        msg.channel_id.say(&ctx.http, "-export must be followed by csv or json.").await?;
+                        return Err(CommandError::from("Invalid arguments."));
+                    }
+                }
+            } else if arg == "-stratify" || arg.starts_with("-stratify=") {
+                stratify = true;
+                //The tags to balance can be given after an equals sign, like `-stratify=Mentor,EU`,
+                //so the word after the flag is still read as a name
+                if let Some(list) = arg.strip_prefix("-stratify=") {
+                    balance_tags = list.split(',').map(|tag| tag.trim().trim_start_matches('#').to_owned()).filter(|tag| !tag.is_empty()).collect();
+                }
             } else {
                 msg.channel_id.say(&ctx.http,format!("{} is not a valid argument.", arg)).await?;
                 return Err(CommandError::from("Invalid arguments."));
@@ -75,19 +130,30 @@ async fn group(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
         }
     }
 
+    if [rotate, stratify, balance].iter().filter(|&&used| used).count() > 1 {
+        msg.channel_id.say(&ctx.http, "Only one of -rotate, -stratify and -balance can be used at a time.").await?;
+        return Err(CommandError::from("Invalid arguments."));
+    }
+
     //Checking if the user is allowed to use the bot
-    if !member_permissions.manage_roles() && role {
+    //Organizers can make roles and channels without having the permissions themselves
+    let organizer = organizers::is_organizer(ctx, guild_id, &member).await;
+    if !member_permissions.manage_roles() && !organizer && role {
         msg.channel_id.say(&ctx.http,"You do not have sufficient permissions to make new roles.").await?;
         return Err(CommandError::from("Insufficient permissions for user."));
     }
 
-    if !member_permissions.manage_channels() && channel {
+    if !member_permissions.manage_channels() && !organizer && channel {
         msg.channel_id.say(&ctx.http,"You do not have sufficient permissions to make new channels.").await?;
         return Err(CommandError::from("Insufficient permissions for user."));
     }
 
     //Stores the people to get shuffled or not
     let mut people: Vec<String> = Vec::new();
+    //Stores the tags entered after people's names
+    let mut tags = Tags::new();
+    //Stores the ratings from attached CSV files, which are used over the server's ratings with -balance
+    let mut imported_ratings: HashMap<String, f64> = HashMap::new();
 
     //Adding everyone to teams if all flag is active
     if all {
@@ -101,58 +167,115 @@ async fn group(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     if size {
         //Asking the user to input names when the size argument was used
         msg.channel_id.say(&ctx.http, format!("{} is making groups of {} people each.\n\
-        Please enter the names to put in the groups or `!stop` to stop.\n\
-        You may enter names one at a time or as a comma separated list.", msg.author, num_groups)).await?;
+        Please enter the names to put in the groups or `{}` to stop.\n\
+        You may enter names one at a time or as a comma separated list.", msg.author, num_groups, config.command("stop"))).await?;
     } else {
         //Asking the user to input names when the size argument was not used
         msg.channel_id.say(&ctx.http, format!("{} is making {} groups.\n\
-        Please enter the names to put in the groups or `!stop` to stop.\n\
-        You may enter names one at a time or as a comma separated list.", msg.author, num_groups)).await?;
+        Please enter the names to put in the groups or `{}` to stop.\n\
+        You may enter names one at a time or as a comma separated list.", msg.author, num_groups, config.command("stop"))).await?;
     }
 
-    //Taking input with up to a 10 minute delay
-    let mut answer = msg.author.await_reply(&ctx).timeout(Duration::from_secs(600)).await;
+    //Taking input from the organizers with up to the server's timeout (10 minutes by default)
+    let organizers = Organizers::new(ctx, msg, "group").await;
+    let mut answer = organizers.await_reply(ctx, config.timeout()).await;
 
     // Stops the loop and outputting the groups if the user does `!stop`
     // or adds more group members from user inputs
     while let Some(message) = answer {
-        if message.content.as_str() == "!stop" {
+        if message.content == config.command("stop") {
             answer = None;
         } else {
             msg.channel_id.say(&ctx.http,"Adding them.").await?;
 
-            message.content.as_str().split(",").for_each(|s| {
-                people.push(String::from(s.trim()));
-            });
+            participants::parse_names(&message.content, &mut people, &mut tags);
+            //Attached CSV and text files are imported too
+            let import = manager.read_attachments(&message).await?;
+            people.extend(import.people);
+            participants::merge_tags(&mut tags, import.tags);
+            imported_ratings.extend(import.ratings);
 
-            answer = msg.author.await_reply(&ctx).timeout(Duration::from_secs(600)).await;
+            answer = organizers.await_reply(ctx, config.timeout()).await;
         }
     }
 
-    msg.channel_id.say(&ctx.http,"Making groups (may happen automatically after 10 minutes)...").await?;
+    msg.channel_id.say(&ctx.http,"Making groups (may happen automatically after the timeout)...").await?;
     //Shuffles the order of the people before team creation.
     if random {
         people.shuffle(&mut thread_rng());
     }
 
+    //Working out the group sizes now that everyone has been entered
+    let sizes = if size {
+        grouping::sizes_for_size(people.len(), num_groups as usize, &remainder, min, max)
+    } else {
+        grouping::sizes_for_count(people.len(), num_groups as usize, min, max)
+    };
+    let sizes = match sizes {
+        Ok(sizes) => sizes,
+        Err(error) => {
+            msg.channel_id.say(&ctx.http, &error).await?;
+            return Err(CommandError::from(error));
+        }
+    };
+
     let mut teams: LinkedHashMap<String, Vec<String>> = LinkedHashMap::new();
+    for i in 1..(sizes.len() + 1) {
+        teams.insert(format!("Group #{}", i), Vec::<String>::new());
+    }
 
-    if size {
-        let num = if people.len() < num_groups as usize { 2 } else { people.len() / num_groups as usize + 1 };
-        for i in 1..num {
-            teams.insert(format!("Group #{}", i), Vec::<String>::new());
+    //Server members are also tagged with their roles when stratifying
+    if stratify {
+        participants::merge_tags(&mut tags, manager.role_tags(&people).await?);
+        if !balance_tags.is_empty() {
+            participants::keep_tags(&mut tags, &balance_tags);
         }
-    } else {
-        for i in 1..(num_groups + 1) {
-            teams.insert(format!("Group #{}", i), Vec::<String>::new());
+        for tag in balance_tags.iter() {
+            let count = participants::count_tag(&tags, &people, tag);
+            if count < sizes.len() {
+                msg.channel_id.say(&ctx.http, format!("Only {} people have the tag {}, so not every group will get one.", count, tag)).await?;
+            }
         }
     }
-    
-    manager.publish_teams(&mut people, &mut teams).await?;
+
+    //The server's ratings from past tournament games even out the groups
+    let ratings = if balance { Ratings::load(guild_id).balance_ratings(&people, imported_ratings) } else { HashMap::new() };
+    if balance && ratings.len() < people.len() {
+        msg.channel_id.say(&ctx.http, format!("{} people have no rating yet, so they count as the average rating.", people.len() - ratings.len())).await?;
+    }
+
+    //Groups made in this server before are used to rotate people and are updated after
+    let mut history = GroupHistory::load(guild_id);
+    let strategy = if rotate {
+        Strategy::Rotate(&history)
+    } else if stratify {
+        Strategy::Stratified(&tags)
+    } else if balance {
+        Strategy::Balanced(&ratings)
+    } else {
+        Strategy::RoundRobin
+    };
+    manager.publish_teams(&people, &mut teams, &sizes, &strategy).await?;
+
+    if rotate {
+        let repeats = history.repeat_pairs(&teams);
+        msg.channel_id.say(&ctx.http, format!("{} repeat pairings could not be avoided.", repeats)).await?;
+    }
+    history.record(&teams);
+    history.save(guild_id)?;
+
+    //Saving the results so they can be exported now or later with `!export`
+    let result = SessionResult::from_teams(organizers.session.id, "group", msg.channel_id, &people, &teams);
+    results::save(guild_id, &result)?;
+    if let Some(format) = export {
+        results::send(ctx, msg.channel_id, &result, format).await?;
+    }
 
     //Adding roles and channels if the flag was included.
     //If both role and channel flags are included, channels are exclusive to the role.
     //Needs to be after manager.publish_teams because it fills the teams up.
+    //The channels made are kept so they can be sent to people with -dm.
+    let mut channels: HashMap<String, ChannelId> = HashMap::new();
     if role {
         for (name, team) in teams.iter() {
             let current_role = manager.add_role(name, team).await?;
@@ -170,14 +293,20 @@ async fn group(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
                     deny: Permissions::all(),
                     kind: PermissionOverwriteType::Role(msg.guild(&ctx.cache).await.expect("Failed to get guild.").role_by_name("@everyone").expect("Failed to get roll \"Everyone\"").id),
                 };
-                manager.add_channel(name, Some(vec![&member_permissions, &nonmember_permissions])).await?;
+                let created = manager.add_channel(name, Some(vec![&member_permissions, &nonmember_permissions])).await?;
+                channels.insert(name.clone(), created.id);
             }
         }
     } else if channel {
         for name in teams.iter() {
-            manager.add_channel(name.0, None).await?;
+            let created = manager.add_channel(name.0, None).await?;
+            channels.insert(name.0.clone(), created.id);
         }
     }
 
+    if dm {
+        manager.dm_teams(&teams, &channels).await?;
+    }
+
     Ok(())
 }
\ No newline at end of file
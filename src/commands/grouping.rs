@@ -0,0 +1,556 @@
+use std::{cmp::{Ordering, Reverse}, collections::HashMap};
+
+use linked_hash_map::LinkedHashMap;
+use rand::{prelude::SliceRandom, thread_rng};
+
+use super::{history::{GroupHistory, pair_key}, participants::{Preferences, Tags}};
+
+// How many times rotation starts over from a new random grouping before keeping the best one found.
+const ROTATE_ATTEMPTS: usize = 20;
+// Extra cost of putting two people together who have been grouped before.
+// It is much larger than any repeat count so the number of repeated pairs is minimized first,
+// and then the pairs that have been together the most are avoided.
+const REPEAT_COST: u64 = 1000;
+
+// How people are split up between teams.
+pub enum Strategy<'a> {
+    // Deals people out to the teams in order.
+    RoundRobin,
+    // Keeps people away from the people they have been grouped with before.
+    Rotate(&'a GroupHistory),
+    // Spreads the people with each tag as evenly as possible over the teams.
+    Stratified(&'a Tags),
+    // Puts people on the teams they want most while keeping the team sizes.
+    Preferences(&'a Preferences),
+    // Evens out the total rating of the teams. Ratings are keyed by name.
+    Balanced(&'a HashMap<String, f64>),
+}
+
+// What to do with the people left over when groups are made by size.
+pub enum Remainder {
+    // Adds the extra people to the full groups.
+    Spread,
+    // Puts the extra people in one smaller group of their own.
+    Overflow,
+}
+
+// Gets how many people each team should get so that team sizes differ by at most one.
+// Earlier teams get the extra people.
+pub fn even_sizes(num_people: usize, num_teams: usize) -> Vec<usize> {
+    if num_teams == 0 {
+        return Vec::new();
+    }
+    (0..num_teams)
+        .map(|index| num_people / num_teams + if index < num_people % num_teams { 1 } else { 0 })
+        .collect()
+}
+
+// Gets the sizes of num_groups groups made from num_people people.
+// Returns an error message if the sizes can't stay between min and max.
+pub fn sizes_for_count(num_people: usize, num_groups: usize, min: usize, max: usize) -> Result<Vec<usize>, String> {
+    let sizes = even_sizes(num_people, num_groups);
+    if within(&sizes, min, max) {
+        Ok(sizes)
+    } else {
+        Err(format!("{} people can't be split into {} groups of {} people.", num_people, num_groups, describe_limits(min, max)))
+    }
+}
+
+// Gets the sizes of groups with size people each made from num_people people.
+// The number of groups is picked so that every group stays between min and max people,
+// and the people left over are handled with remainder.
+// Returns an error message if there is no way to stay between min and max.
+pub fn sizes_for_size(num_people: usize, size: usize, remainder: &Remainder, min: usize, max: usize) -> Result<Vec<usize>, String> {
+    let error = format!("{} people can't be split into groups of {} people.", num_people, describe_limits(min, max));
+    if num_people == 0 {
+        return Ok(vec![0]);
+    }
+
+    if let Remainder::Overflow = remainder {
+        if size >= min && size <= max {
+            let mut sizes = vec![size; num_people / size];
+            let extra = num_people % size;
+            if extra >= min || sizes.is_empty() {
+                sizes.push(extra);
+            } else {
+                //The leftover group would be too small so its people go to the other groups
+                for index in 0..extra {
+                    let length = sizes.len();
+                    sizes[index % length] += 1;
+                }
+            }
+            if within(&sizes, min, max) {
+                return Ok(sizes.into_iter().filter(|&s| s > 0).collect());
+            }
+        }
+        return Err(error);
+    }
+
+    //Trying the number of groups closest to what size asks for first
+    let target = (num_people / size).max(1);
+    let mut counts: Vec<usize> = (1..=num_people).collect();
+    counts.sort_by_key(|&count| (count.abs_diff(target), count));
+    counts.into_iter()
+        .map(|count| even_sizes(num_people, count))
+        .find(|sizes| within(sizes, min, max))
+        .ok_or(error)
+}
+
+// Describes the group size limits for error messages.
+fn describe_limits(min: usize, max: usize) -> String {
+    if max == usize::MAX {
+        format!("at least {}", min)
+    } else if min == 0 {
+        format!("at most {}", max)
+    } else {
+        format!("{} to {}", min, max)
+    }
+}
+
+// Checks if every size is between min and max inclusive.
+fn within(sizes: &[usize], min: usize, max: usize) -> bool {
+    sizes.iter().all(|&size| size >= min && size <= max)
+}
+
+// Adds everyone in people to the teams using the strategy.
+// teams must already contain the team names to be assigned to, and sizes must have
+// the number of people for each team and add up to the number of people.
+pub fn assign(people: &[String], teams: &mut LinkedHashMap<String, Vec<String>>, sizes: &[usize], strategy: &Strategy) {
+    let team_names: Vec<String> = teams.keys().map(|k| k.to_owned()).collect();
+    let groups = match strategy {
+        Strategy::RoundRobin => round_robin(sizes),
+        Strategy::Rotate(history) => rotate(people, sizes, history),
+        Strategy::Stratified(tags) => stratified(people, sizes, tags),
+        Strategy::Preferences(preferences) => preferred(people, &team_names, sizes, preferences),
+        Strategy::Balanced(ratings) => balanced(people, sizes, ratings),
+    };
+    for ((_, team), members) in teams.iter_mut().zip(groups) {
+        for person in members {
+            team.push(people[person].to_owned());
+        }
+    }
+}
+
+// Deals the people out one at a time, skipping over teams that are full.
+fn round_robin(sizes: &[usize]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = vec![Vec::new(); sizes.len()];
+    let mut index = 0;
+    for person in 0..sizes.iter().sum() {
+        while groups[index].len() == sizes[index] {
+            index = (index + 1) % sizes.len();
+        }
+        groups[index].push(person);
+        index = (index + 1) % sizes.len();
+    }
+    groups
+}
+
+// Places people one at a time into the group with space that has the fewest people sharing their tags.
+// People with the rarest tags are placed first so they get spread out before the groups fill up.
+fn stratified(people: &[String], sizes: &[usize], tags: &Tags) -> Vec<Vec<usize>> {
+    let person_tags: Vec<Vec<String>> = people.iter()
+        .map(|person| tags.get(person).map(|t| t.iter().map(|tag| tag.to_lowercase()).collect()).unwrap_or_default())
+        .collect();
+    let mut frequency: HashMap<&str, usize> = HashMap::new();
+    for tag in person_tags.iter().flatten() {
+        *frequency.entry(tag.as_str()).or_insert(0) += 1;
+    }
+
+    //The sort is stable so people with equally rare tags keep their order
+    let mut order: Vec<usize> = (0..people.len()).collect();
+    order.sort_by_key(|&person| person_tags[person].iter().map(|tag| frequency[tag.as_str()]).min().unwrap_or(usize::MAX));
+
+    let mut groups: Vec<Vec<usize>> = vec![Vec::new(); sizes.len()];
+    let mut tag_counts: Vec<HashMap<&str, usize>> = vec![HashMap::new(); sizes.len()];
+    for person in order {
+        let index = (0..groups.len())
+            .filter(|&index| groups[index].len() < sizes[index])
+            .min_by_key(|&index| {
+                let shared: usize = person_tags[person].iter()
+                    .map(|tag| tag_counts[index].get(tag.as_str()).copied().unwrap_or(0))
+                    .sum();
+                (shared, Reverse(sizes[index] - groups[index].len()))
+            })
+            .expect("Group sizes do not add up to the number of people.");
+        groups[index].push(person);
+        for tag in person_tags[person].iter() {
+            *tag_counts[index].entry(tag.as_str()).or_insert(0) += 1;
+        }
+    }
+    groups
+}
+
+// Places people from the highest rated down into the group with space that has the lowest total rating for its size so far.
+// People without a rating count as the average of everyone who has one.
+fn balanced(people: &[String], sizes: &[usize], ratings: &HashMap<String, f64>) -> Vec<Vec<usize>> {
+    let known: Vec<f64> = people.iter().filter_map(|person| ratings.get(person).copied()).collect();
+    let average = if known.is_empty() { 0.0 } else { known.iter().sum::<f64>() / known.len() as f64 };
+    let rating = |person: usize| ratings.get(&people[person]).copied().unwrap_or(average);
+
+    let mut order: Vec<usize> = (0..people.len()).collect();
+    order.sort_by(|&first, &second| rating(second).partial_cmp(&rating(first)).unwrap_or(Ordering::Equal));
+
+    let mut groups: Vec<Vec<usize>> = vec![Vec::new(); sizes.len()];
+    let mut totals: Vec<f64> = vec![0.0; sizes.len()];
+    for person in order {
+        //Totals are compared per spot so smaller groups are not filled with the highest rated people,
+        //and groups with more space left go first on ties
+        let index = (0..groups.len())
+            .filter(|&index| groups[index].len() < sizes[index])
+            .min_by(|&first, &second| {
+                let per_spot = |index: usize| totals[index] / sizes[index] as f64;
+                per_spot(first).partial_cmp(&per_spot(second)).unwrap_or(Ordering::Equal)
+                    .then_with(|| (sizes[second] - groups[second].len()).cmp(&(sizes[first] - groups[first].len())))
+            })
+            .expect("Group sizes do not add up to the number of people.");
+        groups[index].push(person);
+        totals[index] += rating(person);
+    }
+    groups
+}
+
+// Matches people to teams so the total rank of the teams people get in their preferences is as low as possible.
+// This is a minimum cost flow from people to team spots, where each person is one unit of flow and
+// each team can take as many units as its size. People without preferences are happy anywhere,
+// and teams someone did not list cost more than any team they did list.
+fn preferred(people: &[String], team_names: &[String], sizes: &[usize], preferences: &Preferences) -> Vec<Vec<usize>> {
+    let num_people = people.len();
+    let num_teams = team_names.len();
+    let source = num_people + num_teams;
+    let sink = source + 1;
+    let mut flow = FlowGraph::new(sink + 1);
+
+    for (person, name) in people.iter().enumerate() {
+        flow.add_edge(source, person, 1, 0);
+        let wanted = preferences.get(name);
+        for (team, team_name) in team_names.iter().enumerate() {
+            let cost = match wanted {
+                Some(wanted) => wanted.iter()
+                    .position(|choice| choice.eq_ignore_ascii_case(team_name))
+                    .unwrap_or(num_teams) as i64,
+                None => 0,
+            };
+            flow.add_edge(person, num_people + team, 1, cost);
+        }
+    }
+    for (team, size) in sizes.iter().enumerate() {
+        flow.add_edge(num_people + team, sink, *size as i64, 0);
+    }
+    flow.min_cost_flow(source, sink);
+
+    let mut groups: Vec<Vec<usize>> = vec![Vec::new(); num_teams];
+    for person in 0..num_people {
+        for &edge in flow.adjacent[person].iter() {
+            let to = flow.edges[edge].to;
+            if to >= num_people && to < source && flow.edges[edge].capacity == 0 {
+                groups[to - num_people].push(person);
+            }
+        }
+    }
+    groups
+}
+
+// An edge in a FlowGraph. Every edge is stored next to its reverse edge.
+struct FlowEdge {
+    to: usize,
+    capacity: i64,
+    cost: i64,
+}
+
+// A graph for finding minimum cost flows.
+struct FlowGraph {
+    edges: Vec<FlowEdge>,
+    adjacent: Vec<Vec<usize>>,
+}
+
+impl FlowGraph {
+    fn new(num_nodes: usize) -> Self {
+        FlowGraph {
+            edges: Vec::new(),
+            adjacent: vec![Vec::new(); num_nodes],
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, capacity: i64, cost: i64) {
+        self.adjacent[from].push(self.edges.len());
+        self.edges.push(FlowEdge { to, capacity, cost });
+        self.adjacent[to].push(self.edges.len());
+        self.edges.push(FlowEdge { to: from, capacity: 0, cost: -cost });
+    }
+
+    // Sends as much flow as possible from source to sink, always along the cheapest path left.
+    fn min_cost_flow(&mut self, source: usize, sink: usize) {
+        loop {
+            //Bellman-Ford, since reverse edges can have negative costs
+            let mut distance = vec![i64::MAX; self.adjacent.len()];
+            let mut previous: Vec<Option<usize>> = vec![None; self.adjacent.len()];
+            distance[source] = 0;
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for node in 0..self.adjacent.len() {
+                    if distance[node] == i64::MAX {
+                        continue;
+                    }
+                    for &edge in self.adjacent[node].iter() {
+                        let FlowEdge { to, capacity, cost } = self.edges[edge];
+                        if capacity > 0 && distance[node] + cost < distance[to] {
+                            distance[to] = distance[node] + cost;
+                            previous[to] = Some(edge);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            if distance[sink] == i64::MAX {
+                return;
+            }
+
+            let mut amount = i64::MAX;
+            let mut node = sink;
+            while let Some(edge) = previous[node] {
+                amount = amount.min(self.edges[edge].capacity);
+                node = self.edges[edge ^ 1].to;
+            }
+            let mut node = sink;
+            while let Some(edge) = previous[node] {
+                self.edges[edge].capacity -= amount;
+                self.edges[edge ^ 1].capacity += amount;
+                node = self.edges[edge ^ 1].to;
+            }
+        }
+    }
+}
+
+// Splits people into groups of the given sizes with as few previously seen pairs as possible.
+// This is the social golfer problem, so a greedy grouping is improved by swapping people
+// between groups until no swap helps, and the best of several random attempts is kept.
+fn rotate(people: &[String], sizes: &[usize], history: &GroupHistory) -> Vec<Vec<usize>> {
+    let counts = history.pair_counts();
+    let num_people = people.len();
+    let mut costs = vec![vec![0u64; num_people]; num_people];
+    for first in 0..num_people {
+        for second in first + 1..num_people {
+            if let Some(count) = counts.get(&pair_key(&people[first], &people[second])) {
+                costs[first][second] = REPEAT_COST + *count as u64;
+                costs[second][first] = costs[first][second];
+            }
+        }
+    }
+
+    let mut best: Option<(u64, Vec<Vec<usize>>)> = None;
+    for _ in 0..ROTATE_ATTEMPTS {
+        let mut groups = greedy_groups(sizes, &costs);
+        improve_groups(&mut groups, &costs);
+        let cost: u64 = groups.iter().map(|group| group_cost(group, &costs)).sum();
+        match &best {
+            Some((best_cost, _)) if *best_cost <= cost => {}
+            _ => best = Some((cost, groups)),
+        }
+        if cost == 0 {
+            break;
+        }
+    }
+    best.map(|(_, groups)| groups).unwrap_or_default()
+}
+
+// Puts people in a random order into whichever group with space they have the fewest repeats with.
+fn greedy_groups(sizes: &[usize], costs: &[Vec<u64>]) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..costs.len()).collect();
+    order.shuffle(&mut thread_rng());
+
+    let mut groups: Vec<Vec<usize>> = vec![Vec::new(); sizes.len()];
+    for person in order {
+        let index = (0..groups.len())
+            .filter(|&index| groups[index].len() < sizes[index])
+            .min_by_key(|&index| (cost_with(person, &groups[index], None, costs), groups[index].len()))
+            .expect("Group sizes do not add up to the number of people.");
+        groups[index].push(person);
+    }
+    groups
+}
+
+// Swaps pairs of people between groups as long as the swap lowers the total cost.
+fn improve_groups(groups: &mut [Vec<usize>], costs: &[Vec<u64>]) {
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for first_group in 0..groups.len() {
+            for second_group in first_group + 1..groups.len() {
+                for first_slot in 0..groups[first_group].len() {
+                    for second_slot in 0..groups[second_group].len() {
+                        let first = groups[first_group][first_slot];
+                        let second = groups[second_group][second_slot];
+                        let before = cost_with(first, &groups[first_group], None, costs)
+                            + cost_with(second, &groups[second_group], None, costs);
+                        let after = cost_with(first, &groups[second_group], Some(second), costs)
+                            + cost_with(second, &groups[first_group], Some(first), costs);
+                        if after < before {
+                            groups[first_group][first_slot] = second;
+                            groups[second_group][second_slot] = first;
+                            improved = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Gets the cost of person being in group, ignoring the person being swapped out if there is one.
+fn cost_with(person: usize, group: &[usize], swapped_out: Option<usize>, costs: &[Vec<u64>]) -> u64 {
+    group.iter()
+        .filter(|&&member| member != person && Some(member) != swapped_out)
+        .map(|&member| costs[person][member])
+        .sum()
+}
+
+// Gets the cost of every pair of people in a group.
+fn group_cost(group: &[usize], costs: &[Vec<u64>]) -> u64 {
+    let mut cost = 0;
+    for (index, &first) in group.iter().enumerate() {
+        for &second in group[index + 1..].iter() {
+            cost += costs[first][second];
+        }
+    }
+    cost
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::history::GroupSession;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    // Makes groups named 1, 2, 3... for people with the strategy and returns who is in each.
+    fn groups(people: &[String], sizes: &[usize], strategy: &Strategy) -> Vec<Vec<String>> {
+        let mut teams: LinkedHashMap<String, Vec<String>> = LinkedHashMap::new();
+        for index in 0..sizes.len() {
+            teams.insert((index + 1).to_string(), Vec::new());
+        }
+        assign(people, &mut teams, sizes, strategy);
+        teams.into_iter().map(|(_, members)| members).collect()
+    }
+
+    fn history(sessions: &[&[&[&str]]]) -> GroupHistory {
+        GroupHistory {
+            sessions: sessions.iter()
+                .map(|groups| GroupSession { time: 0, groups: groups.iter().map(|group| names(group)).collect() })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn rotation_avoids_every_repeat_pair_when_it_can() {
+        let people = names(&["A", "B", "C", "D", "E", "F", "G", "H", "I"]);
+        let history = history(&[&[&["A", "B", "C"], &["D", "E", "F"], &["G", "H", "I"]]]);
+        let rotated = groups(&people, &[3, 3, 3], &Strategy::Rotate(&history));
+
+        assert!(rotated.iter().all(|group| group.len() == 3));
+        let mut teams: LinkedHashMap<String, Vec<String>> = LinkedHashMap::new();
+        for (index, group) in rotated.into_iter().enumerate() {
+            teams.insert(index.to_string(), group);
+        }
+        assert_eq!(history.repeat_pairs(&teams), 0);
+    }
+
+    #[test]
+    fn rotation_splits_the_pairs_grouped_most_often() {
+        //Every way to pair these four up repeats two pairs, so the pairs seen twice should be split up
+        let people = names(&["A", "B", "C", "D"]);
+        let history = history(&[
+            &[&["A", "B"], &["C", "D"]],
+            &[&["A", "B"], &["C", "D"]],
+            &[&["A", "C"], &["B", "D"]],
+            &[&["A", "D"], &["B", "C"]],
+        ]);
+        for _ in 0..10 {
+            let rotated = groups(&people, &[2, 2], &Strategy::Rotate(&history));
+            assert!(rotated.iter().all(|group| !(group.contains(&"A".to_owned()) && group.contains(&"B".to_owned()))));
+        }
+    }
+
+    #[test]
+    fn sizes_for_size_spreads_the_people_left_over() {
+        assert_eq!(sizes_for_size(10, 3, &Remainder::Spread, 0, usize::MAX), Ok(vec![4, 3, 3]));
+        assert_eq!(sizes_for_size(12, 4, &Remainder::Spread, 0, usize::MAX), Ok(vec![4, 4, 4]));
+        //Groups of 4 would be too big, so there is one more group
+        assert_eq!(sizes_for_size(10, 3, &Remainder::Spread, 0, 3), Ok(vec![3, 3, 2, 2]));
+        assert_eq!(sizes_for_size(2, 5, &Remainder::Spread, 0, usize::MAX), Ok(vec![2]));
+        assert_eq!(sizes_for_size(0, 3, &Remainder::Spread, 0, usize::MAX), Ok(vec![0]));
+    }
+
+    #[test]
+    fn sizes_for_size_puts_the_overflow_in_its_own_group() {
+        assert_eq!(sizes_for_size(10, 3, &Remainder::Overflow, 0, usize::MAX), Ok(vec![3, 3, 3, 1]));
+        assert_eq!(sizes_for_size(9, 3, &Remainder::Overflow, 0, usize::MAX), Ok(vec![3, 3, 3]));
+        //A group of 1 is below the minimum, so that person joins a full group
+        assert_eq!(sizes_for_size(10, 3, &Remainder::Overflow, 2, usize::MAX), Ok(vec![4, 3, 3]));
+    }
+
+    #[test]
+    fn sizes_for_size_fails_when_the_limits_cant_be_kept() {
+        assert!(sizes_for_size(5, 3, &Remainder::Spread, 3, 3).is_err());
+        assert!(sizes_for_size(5, 3, &Remainder::Overflow, 3, 3).is_err());
+        assert!(sizes_for_size(10, 5, &Remainder::Overflow, 0, 4).is_err());
+    }
+
+    #[test]
+    fn stratifying_spreads_each_tag_over_the_groups() {
+        //Dealing these out in order would put A and D, who are both mentors, in the same group
+        let people = names(&["A", "B", "C", "D", "E", "F"]);
+        let tags: Tags = [("A", "Mentor"), ("B", "mentor"), ("C", "EU"), ("D", "Mentor"), ("E", "EU"), ("F", "eu")].iter()
+            .map(|(name, tag)| (name.to_string(), vec![tag.to_string()]))
+            .collect();
+        let stratified = groups(&people, &[2, 2, 2], &Strategy::Stratified(&tags));
+
+        for group in stratified.iter() {
+            assert_eq!(group.len(), 2);
+            let mentors = group.iter().filter(|person| tags[*person][0].eq_ignore_ascii_case("mentor")).count();
+            assert_eq!(mentors, 1, "{:?} should have one mentor", group);
+        }
+    }
+
+    #[test]
+    fn stratifying_places_the_rarest_tags_first() {
+        //Only C and D have the rare tag, and dealing these out in order would put them together
+        let people = names(&["A", "C", "B", "D"]);
+        let tags: Tags = [("A", vec!["common"]), ("B", vec!["common"]), ("C", vec!["common", "rare"]), ("D", vec!["rare"])].iter()
+            .map(|(name, tags)| (name.to_string(), tags.iter().map(|tag| tag.to_string()).collect()))
+            .collect();
+        let stratified = groups(&people, &[2, 2], &Strategy::Stratified(&tags));
+        assert!(stratified.iter().all(|group| !(group.contains(&"C".to_owned()) && group.contains(&"D".to_owned()))));
+    }
+
+    #[test]
+    fn preferences_give_the_lowest_total_rank() {
+        //Red only has room for two, and C minds going to Blue the least since it is their second choice
+        let people = names(&["A", "B", "C", "D"]);
+        let preferences: Preferences = [("A", vec!["Red"]), ("B", vec!["red"]), ("C", vec!["Red", "Blue"])].iter()
+            .map(|(name, wanted)| (name.to_string(), wanted.iter().map(|team| team.to_string()).collect()))
+            .collect();
+        let mut teams: LinkedHashMap<String, Vec<String>> = LinkedHashMap::new();
+        teams.insert("Red".to_owned(), Vec::new());
+        teams.insert("Blue".to_owned(), Vec::new());
+        assign(&people, &mut teams, &[2, 2], &Strategy::Preferences(&preferences));
+
+        let mut red = teams["Red"].clone();
+        let mut blue = teams["Blue"].clone();
+        red.sort();
+        blue.sort();
+        assert_eq!(red, names(&["A", "B"]));
+        assert_eq!(blue, names(&["C", "D"]));
+    }
+
+    #[test]
+    fn preferences_keep_the_team_sizes() {
+        //Everyone wants the same team, which can only take one of them
+        let people = names(&["A", "B", "C"]);
+        let preferences: Preferences = people.iter().map(|name| (name.clone(), names(&["1"]))).collect();
+        let preferred = groups(&people, &[1, 1, 1], &Strategy::Preferences(&preferences));
+        assert!(preferred.iter().all(|group| group.len() == 1));
+    }
+}
@@ -0,0 +1,112 @@
+use serenity::{client::Context, framework::standard::CommandError, model::{channel::Message, id::ChannelId}};
+
+use super::{bracket::{Bracket, Declared, game_name}, config::GuildConfig, manager::Manager, organizers::Organizers};
+
+// Commands from other tournaments that are made for games between two teams, which heats don't have.
+const UNSUPPORTED: [&str; 10] = ["forfeit", "withdraw", "drop", "report", "accept", "dispute", "disputes", "correct", "schedule", "coorganizer"];
+
+// Runs a tournament of free-for-all heats made with Bracket::heats until the final heat is declared.
+// Organizers rank each heat with `!declare [round #]-[game #] [names in the order they finished]`, take back the last result with `!undo`,
+// and can end the tournament with `!stop`. Anyone who uses a command in UNSUPPORTED is told that heats don't have it. Returns early if the organizers stop the tournament or stop declaring results.
+pub async fn run(ctx: &Context, channel_id: ChannelId, config: &GuildConfig, organizers: &Organizers, manager: &Manager<'_>,
+    bracket: &Bracket, advance: usize) -> Result<(), CommandError> {
+    channel_id.say(&ctx.http, format!("**Heats**: everyone in a heat plays at once, and the top {} of each heat move on until the final heat.\n\
+        Use `{} [round #]-[game #] [names in the order they finished]` to rank a heat, like `{} 1-1 team3 team1 team4`. \
+        Separate the names with commas if they have spaces. Only the teams that move on have to be ranked, and the rest finish tied behind them.\n\
+        Use `{}` to take back the last result. If you want to end the tournament use `{}`.\n\
+        Heats don't have {}.",
+        advance, config.command("declare"), config.command("declare"), config.command("undo"), config.command("stop"), unsupported_text(config))).await?;
+    let mut board = manager.say_output(&bracket.text()).await?;
+    let mut posted: Vec<String> = Vec::new();
+    post_ready_heats(ctx, channel_id, bracket, &mut posted).await?;
+
+    let timeout = config.tournament_timeout();
+    while !bracket.is_finished() {
+        let (session, filter_config) = (organizers.clone(), config.clone());
+        let filter = move |message: &Message, content: &str| session.accepts_input(message, content) || unsupported(&filter_config, content).is_some();
+        let reply = match organizers.session.await_reply(ctx, timeout, filter).await {
+            Some(reply) => reply,
+            None => break,
+        };
+        if let Some(command) = unsupported(config, &reply.content) {
+            channel_id.say(&ctx.http, format!("`{}` can't be used in a tournament of heats. Heats don't have {}.", command, unsupported_text(config))).await?;
+            continue;
+        }
+        let words: Vec<&str> = reply.content.splitn(3, ' ').collect();
+        match words.as_slice() {
+            [command] if *command == config.command("stop") => break,
+            [command] if *command == config.command("undo") => {
+                match bracket.undo() {
+                    Some((heat, _)) => {
+                        channel_id.say(&ctx.http, format!("Took back the result of {}.", game_name(&heat))).await?;
+                        //The heat is posted again, and the next heat once it fills up again
+                        let next_id = bracket.next_id(&heat);
+                        posted.retain(|id| *id != heat && Some(id) != next_id.as_ref());
+                    }
+                    None => {
+                        channel_id.say(&ctx.http, "There are no results to take back.").await?;
+                    }
+                }
+            }
+            [command, heat, names] if *command == config.command("declare") => {
+                //Names are split by commas when there are any, so names with spaces can be ranked
+                let names: Vec<String> = if names.contains(',') {
+                    names.split(',').map(|name| name.trim().to_owned()).filter(|name| !name.is_empty()).collect()
+                } else {
+                    names.split_whitespace().map(str::to_owned).collect()
+                };
+                match bracket.declare_ranking(heat, &names) {
+                    Ok((ranking, Declared::NextGame(next_id))) => {
+                        let moving_on: Vec<String> = ranking.into_iter().take(advance).collect();
+                        channel_id.say(&ctx.http, format!("{} move on from {} to {}.", moving_on.join(", "), game_name(heat), game_name(&next_id))).await?;
+                    }
+                    Ok((ranking, _)) => {
+                        channel_id.say(&ctx.http, format!("{} won the tournament!", ranking[0])).await?;
+                    }
+                    Err(error) => {
+                        channel_id.say(&ctx.http, error.to_string()).await?;
+                        continue;
+                    }
+                }
+            }
+            _ => {
+                channel_id.say(&ctx.http, format!("You should use the format `{} [round #]-[game #] [names in the order they finished]`, like `{} 1-1 team3 team1 team4`.",
+                    config.command("declare"), config.command("declare"))).await?;
+                continue;
+            }
+        }
+        manager.edit_output(&mut board, &bracket.text()).await?;
+        post_ready_heats(ctx, channel_id, bracket, &mut posted).await?;
+    }
+
+    if !bracket.is_finished() {
+        channel_id.say(&ctx.http, "The tournament was ended before the final heat.").await?;
+    }
+    Ok(())
+}
+
+// Gets the command a message starts with if it is one heats don't have.
+fn unsupported(config: &GuildConfig, content: &str) -> Option<String> {
+    let command = content.split_whitespace().next().unwrap_or_default();
+    UNSUPPORTED.iter().map(|name| config.command(name)).find(|name| name == command)
+}
+
+// Lists the commands heats don't have, like "`!forfeit`, `!withdraw` ... or `!coorganizer`".
+fn unsupported_text(config: &GuildConfig) -> String {
+    let commands: Vec<String> = UNSUPPORTED.iter().map(|name| format!("`{}`", config.command(name))).collect();
+    format!("{} or {}", commands[..commands.len() - 1].join(", "), commands[commands.len() - 1])
+}
+
+// Posts the heats that are ready to be played and have not been posted yet.
+async fn post_ready_heats(ctx: &Context, channel_id: ChannelId, bracket: &Bracket, posted: &mut Vec<String>) -> Result<(), CommandError> {
+    for (id, teams) in bracket.ready_heats() {
+        if posted.contains(&id) {
+            continue;
+        }
+        let teams: Vec<String> = teams.into_iter().filter(|team| !team.is_empty()).collect();
+        let final_heat = if bracket.next_id(&id).is_none() { " (final heat)" } else { "" };
+        channel_id.say(&ctx.http, format!("**{}**{}: {}", game_name(&id), final_heat, teams.join(", "))).await?;
+        posted.push(id);
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use std::{collections::HashMap, io};
+
+use linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, Serialize};
+use serenity::model::id::GuildId;
+
+use super::storage;
+
+// A set of groups that were made at some point in time.
+#[derive(Serialize, Deserialize)]
+pub struct GroupSession {
+    pub time: u64,
+    pub groups: Vec<Vec<String>>,
+}
+
+// Every set of groups that has been made in a guild, oldest first.
+#[derive(Serialize, Deserialize, Default)]
+pub struct GroupHistory {
+    pub sessions: Vec<GroupSession>,
+}
+
+impl GroupHistory {
+    // Loads the group history of a guild.
+    pub fn load(guild_id: GuildId) -> Self {
+        storage::load(guild_id, "history")
+    }
+
+    // Saves the group history of a guild.
+    pub fn save(&self, guild_id: GuildId) -> io::Result<()> {
+        storage::save(guild_id, "history", self)
+    }
+
+    // Adds a new set of groups to the end of the history.
+    pub fn record(&mut self, teams: &LinkedHashMap<String, Vec<String>>) {
+        self.sessions.push(GroupSession {
+            time: storage::now(),
+            groups: teams.values().cloned().collect(),
+        });
+    }
+
+    // Counts how many times every pair of people has been in the same group.
+    // Pairs are keyed by their names as returned by pair_key.
+    pub fn pair_counts(&self) -> HashMap<(String, String), u32> {
+        let mut counts = HashMap::new();
+        for session in self.sessions.iter() {
+            for group in session.groups.iter() {
+                for (index, first) in group.iter().enumerate() {
+                    for second in group[index + 1..].iter() {
+                        *counts.entry(pair_key(first, second)).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+        counts
+    }
+
+    // Counts the pairs of people in teams that have been grouped together before.
+    pub fn repeat_pairs(&self, teams: &LinkedHashMap<String, Vec<String>>) -> usize {
+        let counts = self.pair_counts();
+        let mut repeats = 0;
+        for team in teams.values() {
+            for (index, first) in team.iter().enumerate() {
+                for second in team[index + 1..].iter() {
+                    if counts.contains_key(&pair_key(first, second)) {
+                        repeats += 1;
+                    }
+                }
+            }
+        }
+        repeats
+    }
+}
+
+// Makes the same key for a pair of people no matter which order they are given in.
+// Mentions and capitalization are normalized so "<@!1>" matches "<@1>" and "Bob" matches "bob".
+pub fn pair_key(first: &str, second: &str) -> (String, String) {
+    let first = normalize(first);
+    let second = normalize(second);
+    if first <= second {
+        (first, second)
+    } else {
+        (second, first)
+    }
+}
+
+// Makes the key a name is stored under, so "<@!1>" matches "<@1>" and "Bob" matches "bob".
+pub fn normalize(name: &str) -> String {
+    name.trim().replace("<@!", "<@").to_lowercase()
+}
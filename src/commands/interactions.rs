@@ -0,0 +1,532 @@
+use std::{collections::HashMap, convert::Infallible, net::SocketAddr, sync::{Arc, Mutex}};
+
+use hyper::{Body, Method, Request, Response, Server, StatusCode, service::{make_service_fn, service_fn}};
+use linked_hash_map::LinkedHashMap;
+use rand::{prelude::SliceRandom, thread_rng};
+use ring::signature::{ED25519, UnparsedPublicKey};
+use serde_json::{Value, json};
+use serenity::{cache::Cache, model::id::{ChannelId, GuildId, RoleId, UserId}, prelude::{RwLock, TypeMap}};
+
+use super::{bracket::{self, Bracket, Declared}, buttons, config, grouping::{self, Remainder, Strategy}, history::GroupHistory, manager::format_teams, organizers, participants::{self, Tags}, sessions, tournament};
+
+// Interaction and response types from the Discord API.
+const PING: u64 = 1;
+const APPLICATION_COMMAND: u64 = 2;
+const MESSAGE_COMPONENT: u64 = 3;
+const PONG: u64 = 1;
+const CHANNEL_MESSAGE: u64 = 4;
+const UPDATE_MESSAGE: u64 = 7;
+// Only the person who used the interaction can see the response.
+const EPHEMERAL: u64 = 64;
+
+// A tournament started with /tournament, kept until it has a champion.
+struct SlashTournament {
+    author: UserId,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    // The id the results are saved under, like a session id.
+    session: u32,
+    teams: Vec<String>,
+    bracket: Bracket,
+}
+
+// Everything the interactions endpoint needs to answer requests.
+pub struct Interactions {
+    public_key: Vec<u8>,
+    data: Arc<RwLock<TypeMap>>,
+    cache: Arc<Cache>,
+    // Running tournaments keyed by the id of the interaction that started them.
+    tournaments: Mutex<HashMap<String, SlashTournament>>,
+}
+
+impl Interactions {
+    // Constructor for the endpoint state. public_key is the hex encoded key from the Discord developer portal.
+    pub fn new(public_key: &str, data: Arc<RwLock<TypeMap>>, cache: Arc<Cache>) -> Result<Self, hex::FromHexError> {
+        Ok(Interactions {
+            public_key: hex::decode(public_key.trim())?,
+            data,
+            cache,
+            tournaments: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // Answers an interaction that has already had its signature checked.
+    async fn handle(&self, interaction: &Value) -> Value {
+        match interaction["type"].as_u64() {
+            Some(PING) => json!({ "type": PONG }),
+            Some(APPLICATION_COMMAND) => match interaction["data"]["name"].as_str() {
+                Some("group") => self.group(interaction).await,
+                Some("team") => team(interaction),
+                Some("tournament") => self.tournament(interaction).await,
+                _ => private_message("That is not a command."),
+            },
+            Some(MESSAGE_COMPONENT) => self.component(interaction).await,
+            _ => private_message("That interaction is not supported."),
+        }
+    }
+
+    // Makes groups like `!group`, recording them in the server's group history.
+    async fn group(&self, interaction: &Value) -> Value {
+        let guild_id = match guild_id(interaction) {
+            Some(guild_id) => guild_id,
+            None => return private_message("Groups can only be made in a server."),
+        };
+        let number = option(interaction, "number").and_then(Value::as_u64).unwrap_or(0);
+        if !(1..=255).contains(&number) {
+            return private_message("Please enter a number between 1 and 255.");
+        }
+
+        let mut people = names(interaction);
+        if flag(interaction, "random") {
+            people.shuffle(&mut thread_rng());
+        }
+        let sizes = if flag(interaction, "size") {
+            grouping::sizes_for_size(people.len(), number as usize, &Remainder::Spread, 0, usize::MAX)
+        } else {
+            grouping::sizes_for_count(people.len(), number as usize, 0, usize::MAX)
+        };
+        let sizes = match sizes {
+            Ok(sizes) => sizes,
+            Err(error) => return private_message(&error),
+        };
+
+        let mut teams: LinkedHashMap<String, Vec<String>> = LinkedHashMap::new();
+        for i in 1..(sizes.len() + 1) {
+            teams.insert(format!("Group #{}", i), Vec::<String>::new());
+        }
+
+        let rotate = flag(interaction, "rotate");
+        let mut history = GroupHistory::load(guild_id);
+        let strategy = if rotate { Strategy::Rotate(&history) } else { Strategy::RoundRobin };
+        grouping::assign(&people, &mut teams, &sizes, &strategy);
+
+        let mut out = format_teams(&teams);
+        if rotate {
+            out.push_str(&format!("\n{} repeat pairings could not be avoided.", history.repeat_pairs(&teams)));
+        }
+        history.record(&teams);
+        if let Err(why) = history.save(guild_id) {
+            println!("Error saving group history: {:?}", why);
+        }
+        message(&out, json!([]))
+    }
+
+    // Starts a tournament like `!tournament`, with a menu to pick the game to declare.
+    async fn tournament(&self, interaction: &Value) -> Value {
+        let channel_id = interaction["channel_id"].as_str().and_then(|id| id.parse().ok()).map(ChannelId);
+        let (guild_id, channel_id, author) = match (guild_id(interaction), channel_id, user_id(interaction)) {
+            (Some(guild_id), Some(channel_id), Some(author)) => (guild_id, channel_id, author),
+            _ => return private_message("Tournaments can only be run in a server."),
+        };
+        let mut teams = names(interaction);
+        if teams.is_empty() {
+            return private_message("Please enter at least 1 valid team name.");
+        }
+        if flag(interaction, "random") {
+            teams.shuffle(&mut thread_rng());
+        }
+
+        let id = interaction["id"].as_str().unwrap_or_default().to_owned();
+        let bracket = Bracket::new(&teams);
+        let out = format!("Making a tournament with {} participants.\n{}", teams.len(), bracket.text());
+        let components = game_menu(&id, &bracket);
+        let session = sessions::reserve_id(&self.data, guild_id).await;
+        self.tournaments.lock().expect("There was an unknown error.")
+            .insert(id, SlashTournament { author, guild_id, channel_id, session, teams, bracket });
+        message(&out, components)
+    }
+
+    // Handles the game menu and winner buttons on tournament messages.
+    // Custom ids are "game:[tournament id]" for the menu and "win:[tournament id]:[game id]:[top/bottom]" for buttons.
+    async fn component(&self, interaction: &Value) -> Value {
+        //Buttons posted by prefix command sessions are handled by the session
+        if let Some(response) = buttons::route(&self.data, interaction).await {
+            return response;
+        }
+        let custom_id = interaction["data"]["custom_id"].as_str().unwrap_or_default();
+        let parts: Vec<&str> = custom_id.split(':').collect();
+        let tournament_id = match parts.get(1) {
+            Some(id) => id.to_string(),
+            None => return private_message("That interaction is not supported."),
+        };
+
+        let (author, guild_id) = match self.tournaments.lock().expect("There was an unknown error.").get(&tournament_id) {
+            Some(tournament) => (tournament.author, tournament.guild_id),
+            None => return private_message("This tournament is over."),
+        };
+        if !self.is_organizer(interaction, author, guild_id).await {
+            return private_message("Only the organizers of this tournament can declare winners.");
+        }
+
+        match parts.as_slice() {
+            ["game", _] => {
+                let game = interaction["data"]["values"][0].as_str().unwrap_or_default();
+                //The lock is let go before looking up names for the buttons
+                let found = match self.tournaments.lock().expect("There was an unknown error.").get(&tournament_id) {
+                    Some(tournament) => tournament.bracket.ready_games().into_iter()
+                        .find(|(id, _, _)| id == game)
+                        .map(|ready| (tournament.bracket.text(), ready)),
+                    None => return private_message("This tournament is over."),
+                };
+                match found {
+                    Some((text, (game, top, bottom))) => {
+                        let out = format!("{}\nWho won game {}?", text, game);
+                        let components = winner_buttons(&tournament_id, &game, &buttons::label(&self.cache, &top).await, &buttons::label(&self.cache, &bottom).await);
+                        update_message(&out, components)
+                    }
+                    None => private_message("That game is not ready to be declared."),
+                }
+            }
+            ["win", _, game, slot] => self.declare(&tournament_id, game, slot).await,
+            _ => private_message("That interaction is not supported."),
+        }
+    }
+
+    // Declares the team in the top or bottom slot of a game as the winner when its button is clicked.
+    // Once the final is declared, the placements are posted and the results are saved and rated like a `!tournament`.
+    async fn declare(&self, tournament_id: &str, game: &str, slot: &str) -> Value {
+        let (mut out, finished) = {
+            let mut tournaments = self.tournaments.lock().expect("There was an unknown error.");
+            let bracket = match tournaments.get(tournament_id) {
+                Some(tournament) => &tournament.bracket,
+                None => return private_message("This tournament is over."),
+            };
+            let winner = match bracket.ready_games().into_iter().find(|(id, _, _)| id == game) {
+                Some((_, top, _)) if slot == "top" && !top.is_empty() => top,
+                Some((_, _, bottom)) if slot == "bottom" && !bottom.is_empty() => bottom,
+                _ => return private_message("That game is not ready to be declared."),
+            };
+            match bracket.declare(game, &winner) {
+                Ok(Declared::NextGame(next_id)) => {
+                    let out = format!("{}\n{} won game {} and moves on to game {}.", bracket.text(), winner, game, next_id);
+                    return update_message(&out, game_menu(tournament_id, bracket));
+                }
+                Ok(Declared::ThirdPlace) => {
+                    let out = format!("{}\n{} won the third place game.", bracket.text(), winner);
+                    return update_message(&out, game_menu(tournament_id, bracket));
+                }
+                Ok(Declared::Champion) => {
+                    let out = format!("{}\n{} is the champion!", bracket.text(), winner);
+                    (out, tournaments.remove(tournament_id).expect("The tournament was just found."))
+                }
+                Err(why) => return private_message(&why.to_string()),
+            }
+        };
+
+        let placements = finished.bracket.placements();
+        if !placements.is_empty() {
+            out.push_str(&format!("\n\nFinal placements:\n{}", bracket::placements_text(&placements)));
+        }
+        let config = config::get_from(&self.data, finished.guild_id).await;
+        match tournament::record_finished(finished.guild_id, finished.session, finished.channel_id, &config, &finished.teams, &finished.bracket) {
+            Ok((_, rated)) if rated > 0 => {
+                out.push_str(&format!("\nUpdated the ratings with {} games. Use `{}` to see them.", rated, config.command("leaderboard")));
+            }
+            Ok(_) => {}
+            Err(why) => println!("Error saving tournament results: {:?}", why),
+        }
+        update_message(&out, json!([]))
+    }
+
+    // Checks if the person who used an interaction started the tournament or has the organizer role.
+    async fn is_organizer(&self, interaction: &Value, author: UserId, guild_id: GuildId) -> bool {
+        if user_id(interaction) == Some(author) {
+            return true;
+        }
+        let role = match organizers::organizer_role_from(&self.cache, &self.data, guild_id).await {
+            Some(role) => role,
+            None => return false,
+        };
+        interaction["member"]["roles"].as_array()
+            .map(|roles| roles.iter().any(|id| id.as_str().and_then(|id| id.parse().ok()).map(RoleId) == Some(role)))
+            .unwrap_or(false)
+    }
+
+}
+
+// Makes teams like `!team`.
+fn team(interaction: &Value) -> Value {
+    let mut teams: LinkedHashMap<String, Vec<String>> = LinkedHashMap::new();
+    for name in option(interaction, "teams").and_then(Value::as_str).unwrap_or_default().split_whitespace() {
+        teams.insert(name.to_owned(), Vec::new());
+    }
+    if teams.is_empty() {
+        return private_message("Please enter at least 1 valid team name.");
+    }
+
+    let mut people = names(interaction);
+    if flag(interaction, "random") {
+        people.shuffle(&mut thread_rng());
+    }
+    let sizes = grouping::even_sizes(people.len(), teams.len());
+    grouping::assign(&people, &mut teams, &sizes, &Strategy::RoundRobin);
+    message(&format_teams(&teams), json!([]))
+}
+
+// Gets the value of a command option by name.
+fn option<'a>(interaction: &'a Value, name: &str) -> Option<&'a Value> {
+    interaction["data"]["options"].as_array()?
+        .iter()
+        .find(|option| option["name"] == name)
+        .map(|option| &option["value"])
+}
+
+// Checks if a true/false command option was set to true.
+fn flag(interaction: &Value, name: &str) -> bool {
+    option(interaction, name).and_then(Value::as_bool).unwrap_or(false)
+}
+
+// Gets the people entered in the names option, as a comma separated list like prefix commands take.
+fn names(interaction: &Value) -> Vec<String> {
+    let mut people: Vec<String> = Vec::new();
+    if let Some(text) = option(interaction, "names").and_then(Value::as_str) {
+        participants::parse_names(text, &mut people, &mut Tags::new());
+    }
+    people
+}
+
+fn guild_id(interaction: &Value) -> Option<GuildId> {
+    interaction["guild_id"].as_str()?.parse().ok().map(GuildId)
+}
+
+fn user_id(interaction: &Value) -> Option<UserId> {
+    interaction["member"]["user"]["id"].as_str()?.parse().ok().map(UserId)
+}
+
+// Makes a response that posts a message.
+fn message(content: &str, components: Value) -> Value {
+    json!({ "type": CHANNEL_MESSAGE, "data": { "content": content, "components": components } })
+}
+
+// Makes a response that only the person who used the interaction can see, used for errors.
+fn private_message(content: &str) -> Value {
+    json!({ "type": CHANNEL_MESSAGE, "data": { "content": content, "flags": EPHEMERAL } })
+}
+
+// Makes a response that edits the message the component is on.
+fn update_message(content: &str, components: Value) -> Value {
+    json!({ "type": UPDATE_MESSAGE, "data": { "content": content, "components": components } })
+}
+
+// Makes a select menu of the games that are ready to be declared. Discord allows up to 25 options.
+fn game_menu(tournament_id: &str, bracket: &Bracket) -> Value {
+    let options: Vec<Value> = bracket.ready_games().into_iter()
+        .take(25)
+        .map(|(game, top, bottom)| {
+            let label: String = match (top.is_empty(), bottom.is_empty()) {
+                (false, false) => format!("Game {}: {} vs {}", game, top, bottom),
+                _ => format!("Game {}: {} (no opponent)", game, top + &bottom),
+            }.chars().take(100).collect();
+            json!({ "label": label, "value": game })
+        })
+        .collect();
+    if options.is_empty() {
+        return json!([]);
+    }
+    json!([{ "type": 1, "components": [{
+        "type": 3,
+        "custom_id": format!("game:{}", tournament_id),
+        "placeholder": "Declare winner",
+        "options": options,
+    }]}])
+}
+
+// Makes a button for each team in a game. A team on its own only gets one button.
+fn winner_buttons(tournament_id: &str, game: &str, top: &str, bottom: &str) -> Value {
+    let buttons: Vec<(String, String)> = [(top, "top"), (bottom, "bottom")].iter()
+        .filter(|(team, _)| !team.is_empty())
+        .map(|(team, slot)| (team.to_string(), format!("win:{}:{}:{}", tournament_id, game, slot)))
+        .collect();
+    buttons::button_row(&buttons)
+}
+
+// Checks that a request was signed by Discord. The signature covers the timestamp followed by the body.
+pub fn verify_signature(public_key: &[u8], signature: &str, timestamp: &str, body: &[u8]) -> bool {
+    let signature = match hex::decode(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    let mut signed = timestamp.as_bytes().to_vec();
+    signed.extend_from_slice(body);
+    UnparsedPublicKey::new(&ED25519, public_key).verify(&signed, &signature).is_ok()
+}
+
+// Answers one HTTP request to the endpoint.
+async fn respond(interactions: Arc<Interactions>, request: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+    if request.method() != Method::POST {
+        return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok()).unwrap_or_default().to_owned();
+    let (signature, timestamp) = (header("X-Signature-Ed25519"), header("X-Signature-Timestamp"));
+    let body = hyper::body::to_bytes(request.into_body()).await?;
+
+    if !verify_signature(&interactions.public_key, &signature, &timestamp, &body) {
+        return Ok(status(StatusCode::UNAUTHORIZED));
+    }
+    let interaction: Value = match serde_json::from_slice(&body) {
+        Ok(interaction) => interaction,
+        Err(_) => return Ok(status(StatusCode::BAD_REQUEST)),
+    };
+
+    let answer = interactions.handle(&interaction).await;
+    Ok(Response::builder()
+        .header("Content-Type", "application/json")
+        .body(Body::from(answer.to_string()))
+        .expect("Failed to build response."))
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = code;
+    response
+}
+
+// Runs the interactions endpoint on a port until the bot stops.
+pub async fn serve(port: u16, interactions: Arc<Interactions>) {
+    let address = SocketAddr::from(([0, 0, 0, 0], port));
+    let service = make_service_fn(move |_| {
+        let interactions = Arc::clone(&interactions);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| respond(Arc::clone(&interactions), request)))
+        }
+    });
+    println!("Listening for interactions on port {}", port);
+    if let Err(why) = Server::bind(&address).serve(service).await {
+        println!("Interactions endpoint error: {:?}", why);
+    }
+}
+
+// Makes the slash commands and their options.
+fn command_definitions() -> Value {
+    // Option types from the Discord API.
+    const STRING: u64 = 3;
+    const INTEGER: u64 = 4;
+    const BOOLEAN: u64 = 5;
+    let names = json!({ "type": STRING, "name": "names", "description": "Comma separated list of people", "required": true });
+    let random = json!({ "type": BOOLEAN, "name": "random", "description": "Shuffle people first" });
+    json!([
+        {
+            "name": "group",
+            "description": "Makes numbered groups of people",
+            "options": [
+                { "type": INTEGER, "name": "number", "description": "How many groups to make, or people per group with size", "required": true },
+                names,
+                random,
+                { "type": BOOLEAN, "name": "size", "description": "Treat the number as the size of each group" },
+                { "type": BOOLEAN, "name": "rotate", "description": "Avoid pairing people that were grouped before" },
+            ],
+        },
+        {
+            "name": "team",
+            "description": "Makes named teams of people",
+            "options": [
+                { "type": STRING, "name": "teams", "description": "Team names separated by spaces", "required": true },
+                names,
+                random,
+            ],
+        },
+        {
+            "name": "tournament",
+            "description": "Makes and runs a single elimination tournament bracket",
+            "options": [
+                { "type": STRING, "name": "names", "description": "Comma separated list of participants", "required": true },
+                random,
+            ],
+        },
+    ])
+}
+
+// Registers the slash commands with Discord, replacing any that were registered before.
+pub async fn register_commands(application_id: &str, token: &str) -> Result<(), reqwest::Error> {
+    reqwest::Client::new()
+        .put(&format!("https://discord.com/api/v8/applications/{}/commands", application_id))
+        .header("Authorization", format!("Bot {}", token))
+        .json(&command_definitions())
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::{rand::SystemRandom, signature::{Ed25519KeyPair, KeyPair}};
+
+    use super::*;
+
+    const TIMESTAMP: &str = "1609459200";
+
+    fn key_pair() -> Ed25519KeyPair {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).expect("Failed to generate a key.");
+        Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).expect("Failed to read the generated key.")
+    }
+
+    fn sign(key: &Ed25519KeyPair, timestamp: &str, body: &str) -> String {
+        let mut signed = timestamp.as_bytes().to_vec();
+        signed.extend_from_slice(body.as_bytes());
+        hex::encode(key.sign(&signed))
+    }
+
+    fn endpoint(key: &Ed25519KeyPair) -> Arc<Interactions> {
+        let public_key = hex::encode(key.public_key());
+        Arc::new(Interactions::new(&public_key, Arc::new(RwLock::new(TypeMap::new())), Arc::new(Cache::default())).expect("Failed to read the public key."))
+    }
+
+    fn request(signature: &str, timestamp: &str, body: &str) -> Request<Body> {
+        Request::builder()
+            .method(Method::POST)
+            .header("X-Signature-Ed25519", signature)
+            .header("X-Signature-Timestamp", timestamp)
+            .body(Body::from(body.to_owned()))
+            .expect("Failed to build request.")
+    }
+
+    #[test]
+    fn accepts_a_valid_signature() {
+        let key = key_pair();
+        let body = r#"{"type":1}"#;
+        assert!(verify_signature(key.public_key().as_ref(), &sign(&key, TIMESTAMP, body), TIMESTAMP, body.as_bytes()));
+    }
+
+    #[test]
+    fn rejects_a_signature_from_another_key() {
+        let (key, other) = (key_pair(), key_pair());
+        let body = r#"{"type":1}"#;
+        assert!(!verify_signature(key.public_key().as_ref(), &sign(&other, TIMESTAMP, body), TIMESTAMP, body.as_bytes()));
+        assert!(!verify_signature(key.public_key().as_ref(), "not hex", TIMESTAMP, body.as_bytes()));
+        assert!(!verify_signature(key.public_key().as_ref(), "", TIMESTAMP, body.as_bytes()));
+    }
+
+    #[test]
+    fn rejects_a_tampered_body_or_timestamp() {
+        let key = key_pair();
+        let body = r#"{"type":1}"#;
+        let signature = sign(&key, TIMESTAMP, body);
+        assert!(!verify_signature(key.public_key().as_ref(), &signature, TIMESTAMP, br#"{"type":2}"#));
+        assert!(!verify_signature(key.public_key().as_ref(), &signature, "1609459201", body.as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn answers_a_ping_with_a_pong() {
+        let key = key_pair();
+        let body = r#"{"type":1}"#;
+        let response = respond(endpoint(&key), request(&sign(&key, TIMESTAMP, body), TIMESTAMP, body)).await.expect("The request failed.");
+        assert_eq!(response.status(), StatusCode::OK);
+        let answer: Value = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.expect("Failed to read the response."))
+            .expect("The response was not JSON.");
+        assert_eq!(answer, json!({ "type": PONG }));
+    }
+
+    #[tokio::test]
+    async fn refuses_unsigned_requests() {
+        let key = key_pair();
+        let body = r#"{"type":1}"#;
+        let tampered = respond(endpoint(&key), request(&sign(&key, TIMESTAMP, body), TIMESTAMP, r#"{"type":2}"#)).await.expect("The request failed.");
+        assert_eq!(tampered.status(), StatusCode::UNAUTHORIZED);
+        let unsigned = respond(endpoint(&key), request("", "", body)).await.expect("The request failed.");
+        assert_eq!(unsigned.status(), StatusCode::UNAUTHORIZED);
+
+        let get = Request::builder().method(Method::GET).body(Body::empty()).expect("Failed to build request.");
+        assert_eq!(respond(endpoint(&key), get).await.expect("The request failed.").status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
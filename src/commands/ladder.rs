@@ -0,0 +1,279 @@
+use std::{fmt, io};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::id::GuildId;
+
+use super::{bracket::DeclareError, participants::{find_person, same_person}, storage};
+
+// A result reported by one of the people in a challenge, waiting for the other to confirm it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LadderReport {
+    pub reporter: String,
+    pub winner: String,
+    // When the result counts without being confirmed, in seconds since the Unix epoch.
+    pub deadline: u64,
+    // Whether the other person disputed it, so the organizers have to declare the winner.
+    pub disputed: bool,
+}
+
+// A challenge between two people on a ladder that has not been played yet.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Challenge {
+    pub challenger: String,
+    pub defender: String,
+    // When the challenge expires if nobody has reported a result, in seconds since the Unix epoch.
+    pub deadline: u64,
+    pub report: Option<LadderReport>,
+}
+
+impl Challenge {
+    // Gets who a person in the challenge plays.
+    pub fn opponent(&self, name: &str) -> &str {
+        if same_person(&self.challenger, name) { &self.defender } else { &self.challenger }
+    }
+}
+
+// Why a challenge could not be made.
+pub enum ChallengeError {
+    NotOnLadder(String),
+    SamePerson,
+    // The person challenged is not above the challenger.
+    NotAbove,
+    // The person challenged is more ranks above than the guild allows.
+    TooFar(usize),
+    // One of them already has a challenge that has not been played.
+    Busy(String),
+}
+
+impl fmt::Display for ChallengeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChallengeError::NotOnLadder(name) => write!(f, "{} is not on the ladder.", name),
+            ChallengeError::SamePerson => write!(f, "You can't challenge yourself."),
+            ChallengeError::NotAbove => write!(f, "You can only challenge people above you on the ladder."),
+            ChallengeError::TooFar(range) => write!(f, "You can only challenge people up to {} places above you.", range),
+            ChallengeError::Busy(name) => write!(f, "{} already has a challenge that has not been played.", name),
+        }
+    }
+}
+
+// The people on a guild's ladder from first place down, and the challenges between them.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Ladder {
+    pub players: Vec<String>,
+    pub challenges: Vec<Challenge>,
+}
+
+impl Ladder {
+    // Loads the ladder of a guild.
+    pub fn load(guild_id: GuildId) -> Self {
+        storage::load(guild_id, "ladder")
+    }
+
+    // Saves the ladder of a guild.
+    pub fn save(&self, guild_id: GuildId) -> io::Result<()> {
+        storage::save(guild_id, "ladder", self)
+    }
+
+    // Finds the name a person was put on the ladder as, from the names they could have been entered as.
+    pub fn find(&self, names: &[String]) -> Option<String> {
+        find_person(&self.players, names).cloned()
+    }
+
+    // Gets the place of a person on the ladder, starting at 0.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.players.iter().position(|player| same_person(player, name))
+    }
+
+    // Adds a person to the bottom of the ladder. Returns false if they are already on it.
+    pub fn join(&mut self, name: &str) -> bool {
+        if self.position(name).is_some() {
+            return false;
+        }
+        self.players.push(name.to_owned());
+        true
+    }
+
+    // Takes a person and their challenges off the ladder. Returns false if they were not on it.
+    pub fn leave(&mut self, name: &str) -> bool {
+        match self.position(name) {
+            Some(index) => {
+                self.players.remove(index);
+                self.challenges.retain(|challenge| !same_person(&challenge.challenger, name) && !same_person(&challenge.defender, name));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Gets the challenge a person is in, if they are in one.
+    pub fn challenge_of(&self, name: &str) -> Option<&Challenge> {
+        self.challenges.iter().find(|challenge| same_person(&challenge.challenger, name) || same_person(&challenge.defender, name))
+    }
+
+    // Makes a challenge against someone at most range places above the challenger, which expires at the deadline.
+    pub fn challenge(&mut self, challenger: &str, defender: &str, range: usize, deadline: u64) -> Result<(), ChallengeError> {
+        let from = self.position(challenger).ok_or_else(|| ChallengeError::NotOnLadder(challenger.to_owned()))?;
+        let to = self.position(defender).ok_or_else(|| ChallengeError::NotOnLadder(defender.to_owned()))?;
+        if from == to {
+            return Err(ChallengeError::SamePerson);
+        }
+        if to > from {
+            return Err(ChallengeError::NotAbove);
+        }
+        if from - to > range {
+            return Err(ChallengeError::TooFar(range));
+        }
+        for name in [challenger, defender].iter() {
+            if self.challenge_of(name).is_some() {
+                return Err(ChallengeError::Busy((*name).to_owned()));
+            }
+        }
+        self.challenges.push(Challenge { challenger: self.players[from].clone(), defender: self.players[to].clone(), deadline, report: None });
+        Ok(())
+    }
+
+    // Reports the winner of a person's challenge, waiting for their opponent to confirm it until the deadline.
+    // If the opponent already reported someone else winning, the report is marked as disputed instead of being replaced,
+    // and disputed reports can't be replaced at all until the organizers declare the winner.
+    pub fn report(&mut self, name: &str, winner: &str, deadline: u64) -> Result<Challenge, DeclareError> {
+        let challenge = self.challenges.iter_mut()
+            .find(|challenge| same_person(&challenge.challenger, name) || same_person(&challenge.defender, name))
+            .ok_or(DeclareError::UnknownGame)?;
+        if !same_person(&challenge.challenger, winner) && !same_person(&challenge.defender, winner) {
+            return Err(DeclareError::NotInGame);
+        }
+        match challenge.report.as_mut() {
+            Some(report) if report.disputed => return Err(DeclareError::AlreadyDeclared),
+            Some(report) if !same_person(&report.reporter, name) && !same_person(&report.winner, winner) => report.disputed = true,
+            _ => challenge.report = Some(LadderReport { reporter: name.to_owned(), winner: winner.to_owned(), deadline, disputed: false }),
+        }
+        Ok(challenge.clone())
+    }
+
+    // Marks the result reported for a person's challenge as disputed. Only the opponent of the reporter can dispute it.
+    pub fn dispute(&mut self, name: &str) -> Result<Challenge, DeclareError> {
+        let challenge = self.challenges.iter_mut()
+            .find(|challenge| same_person(&challenge.challenger, name) || same_person(&challenge.defender, name))
+            .ok_or(DeclareError::UnknownGame)?;
+        match challenge.report.as_mut() {
+            Some(report) if !same_person(&report.reporter, name) => report.disputed = true,
+            Some(_) => return Err(DeclareError::NotInGame),
+            None => return Err(DeclareError::NotDeclared),
+        }
+        Ok(challenge.clone())
+    }
+
+    // Declares the winner of the challenge a person is in. If the challenger wins, they swap places with the defender.
+    // Returns the challenge that was played.
+    pub fn declare(&mut self, name: &str, winner: &str) -> Result<Challenge, DeclareError> {
+        let index = self.challenges.iter()
+            .position(|challenge| same_person(&challenge.challenger, name) || same_person(&challenge.defender, name))
+            .ok_or(DeclareError::UnknownGame)?;
+        let challenge = &self.challenges[index];
+        if !same_person(&challenge.challenger, winner) && !same_person(&challenge.defender, winner) {
+            return Err(DeclareError::NotInGame);
+        }
+        let challenge = self.challenges.remove(index);
+        if same_person(&challenge.challenger, winner) {
+            if let (Some(from), Some(to)) = (self.position(&challenge.challenger), self.position(&challenge.defender)) {
+                self.players.swap(from, to);
+            }
+        }
+        Ok(challenge)
+    }
+
+    // Takes away the challenges that nobody reported a result for before their deadline.
+    pub fn expire(&mut self, now: u64) -> Vec<Challenge> {
+        let (expired, kept) = self.challenges.drain(..).partition(|challenge| challenge.report.is_none() && challenge.deadline <= now);
+        self.challenges = kept;
+        expired
+    }
+
+    // Gets the challenges with reported results that nobody disputed before the report's deadline, so they can be declared.
+    pub fn unconfirmed(&self, now: u64) -> Vec<Challenge> {
+        self.challenges.iter()
+            .filter(|challenge| challenge.report.as_ref().map(|report| !report.disputed && report.deadline <= now).unwrap_or(false))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ladder(players: &[&str]) -> Ladder {
+        Ladder { players: players.iter().map(|name| name.to_string()).collect(), challenges: Vec::new() }
+    }
+
+    #[test]
+    fn challengers_who_win_swap_places() {
+        let mut ladder = ladder(&["A", "B", "C", "D"]);
+        assert!(ladder.challenge("D", "B", 2, 100).is_ok());
+        assert!(matches!(ladder.declare("b", "D"), Ok(challenge) if challenge.challenger == "D"));
+        assert_eq!(ladder.players, vec!["A", "D", "C", "B"]);
+        assert!(ladder.challenges.is_empty());
+
+        //Defenders who win keep their place
+        assert!(ladder.challenge("C", "D", 2, 100).is_ok());
+        assert!(ladder.declare("C", "D").is_ok());
+        assert_eq!(ladder.players, vec!["A", "D", "C", "B"]);
+    }
+
+    #[test]
+    fn challenges_have_to_be_up_the_ladder_and_in_range() {
+        let mut ladder = ladder(&["A", "B", "C", "<@!4>"]);
+        assert!(matches!(ladder.challenge("<@4>", "A", 2, 100), Err(ChallengeError::TooFar(2))));
+        assert!(matches!(ladder.challenge("A", "B", 2, 100), Err(ChallengeError::NotAbove)));
+        assert!(matches!(ladder.challenge("A", "a", 2, 100), Err(ChallengeError::SamePerson)));
+        assert!(matches!(ladder.challenge("E", "A", 2, 100), Err(ChallengeError::NotOnLadder(_))));
+        assert!(ladder.challenge("<@4>", "B", 2, 100).is_ok());
+        assert!(matches!(ladder.challenge("C", "B", 2, 100), Err(ChallengeError::Busy(name)) if name == "B"));
+    }
+
+    #[test]
+    fn challenges_without_a_result_expire() {
+        let mut ladder = ladder(&["A", "B", "C", "D"]);
+        ladder.challenge("B", "A", 1, 100).ok();
+        ladder.challenge("D", "C", 1, 200).ok();
+        assert!(ladder.expire(99).is_empty());
+
+        let expired = ladder.expire(100);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].challenger, "B");
+        assert_eq!(ladder.challenges.len(), 1);
+
+        //A reported result keeps the challenge from expiring while it waits to be confirmed
+        ladder.report("D", "D", 300).ok();
+        assert!(ladder.expire(250).is_empty());
+        assert_eq!(ladder.players, vec!["A", "B", "C", "D"]);
+    }
+
+    #[test]
+    fn reports_count_once_nobody_disputes_them_in_time() {
+        let mut ladder = ladder(&["A", "B"]);
+        ladder.challenge("B", "A", 1, 100).ok();
+        ladder.report("B", "B", 50).ok();
+        assert!(ladder.unconfirmed(49).is_empty());
+        assert_eq!(ladder.unconfirmed(50).len(), 1);
+
+        //The reporter can't dispute their own report, and disputed reports wait for the organizers
+        assert!(matches!(ladder.dispute("b"), Err(DeclareError::NotInGame)));
+        assert!(ladder.dispute("A").is_ok());
+        assert!(ladder.unconfirmed(50).is_empty());
+
+        //Reporting again can't get around the dispute
+        assert!(matches!(ladder.report("B", "B", 50), Err(DeclareError::AlreadyDeclared)));
+        assert!(ladder.unconfirmed(50).is_empty());
+
+        //Reporting the other winner disputes the first report instead of replacing it
+        let mut ladder = self::ladder(&["A", "B"]);
+        ladder.challenge("B", "A", 1, 100).ok();
+        ladder.report("B", "B", 50).ok();
+        let challenge = ladder.report("A", "A", 60).ok().and_then(|challenge| challenge.report).expect("The report is missing.");
+        assert!(challenge.disputed);
+        assert_eq!(challenge.winner, "B");
+        assert!(ladder.unconfirmed(60).is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+use serenity::{client::Context, framework::standard::{Args, CommandError, CommandResult, macros::command}, model::channel::Message, utils::parse_username};
+use serenity::model::id::UserId;
+
+use super::{config::{self, GuildConfig, RatingSystem}, manager::Manager, ratings::{Rating, Ratings}};
+
+// How many people `!leaderboard` shows when no number is given, and the most it shows.
+const DEFAULT_LENGTH: usize = 10;
+const MAX_LENGTH: usize = 50;
+
+// Makes the text of a rating, with how sure it is for Glicko-2.
+fn rating_text(config: &GuildConfig, rating: &Rating) -> String {
+    match config.rating_system {
+        RatingSystem::Elo => format!("{:.0}", rating.rating),
+        RatingSystem::Glicko2 => format!("{:.0} ± {:.0}", rating.rating, rating.deviation * 2.0),
+    }
+}
+
+
+
+
+#[command]
+#[description = "Shows the rating of a person or team from the tournament games played in this server.\n\
+    Leave out the name to see your own rating.\n\
+    \n\
+    **Sample usage:** `!rating @Sam`"]
+async fn rating(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+
+    let guild_id = msg.guild_id.expect("Failed to get guild_id from msg.");
+    let config = config::get(ctx, guild_id).await;
+    let manager = Manager::new(ctx, guild_id, msg.channel_id);
+    let ratings = Ratings::load(guild_id);
+
+    //Server members could have been entered by mention or by name
+    let name = args.rest().trim();
+    let names = match parse_username(name) {
+        Some(user_id) => manager.names_of(UserId(user_id)).await,
+        None if name.is_empty() => manager.names_of(msg.author.id).await,
+        None => vec![name.to_owned()],
+    };
+    let rating = match names.iter().find_map(|name| ratings.get(name)) {
+        Some(rating) => rating,
+        None => {
+            msg.channel_id.say(&ctx.http, format!("{} has not played any rated games yet.", names[0])).await?;
+            return Err(CommandError::from("No rating."));
+        }
+    };
+
+    let rank = ratings.rank(&rating.name).unwrap_or_default();
+    msg.channel_id.say(&ctx.http, format!("{} is rated {}, #{} of {}, with {} wins and {} losses.", rating.name, rating_text(&config, rating),
+        rank, ratings.leaderboard().len(), rating.wins, rating.losses)).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Shows the highest rated people and teams in this server.\n\
+    Ratings are updated with every game played in a tournament or ladder challenge, using Elo or Glicko-2 as set with `!config rating`.\n\
+    Add a number to show more or fewer places, up to 50.\n\
+    \n\
+    **Sample usage:** `!leaderboard 20`"]
+async fn leaderboard(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+
+    let guild_id = msg.guild_id.expect("Failed to get guild_id from msg.");
+    let config = config::get(ctx, guild_id).await;
+    let manager = Manager::new(ctx, guild_id, msg.channel_id);
+    let length = args.single::<usize>().unwrap_or(DEFAULT_LENGTH).clamp(1, MAX_LENGTH);
+
+    let ratings = Ratings::load(guild_id);
+    let leaderboard = ratings.leaderboard();
+    if leaderboard.is_empty() {
+        msg.channel_id.say(&ctx.http, "Nobody has played any rated games in this server yet.").await?;
+        return Ok(());
+    }
+
+    let mut out = String::from("**Leaderboard**\n");
+    for (index, rating) in leaderboard.iter().take(length).enumerate() {
+        out.push_str(&format!("{}. {} {} ({}-{})\n", index + 1, rating.name, rating_text(&config, rating), rating.wins, rating.losses));
+    }
+    manager.say_output(&out).await?;
+
+    Ok(())
+}
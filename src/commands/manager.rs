@@ -1,5 +1,12 @@
+use std::collections::HashMap;
+
 use linked_hash_map::LinkedHashMap;
-use serenity::{Error, client::Context, model::{channel::{GuildChannel, PermissionOverwrite}, guild::Role, id::{ChannelId, GuildId}}};
+use serenity::{Error, client::Context, model::{channel::{GuildChannel, Message, PermissionOverwrite}, guild::{Member, Role}, id::{ChannelId, GuildId, UserId}, misc::Mentionable}, utils::parse_username};
+
+use super::{bracket::Bracket, bracket_file::{BracketFile, ImportError}, config::{self, OutputStyle}, grouping::{self, Strategy}, participants::{self, Import, Tags}};
+
+// The largest file in bytes that participants can be imported from.
+const MAX_IMPORT_SIZE: u64 = 1_000_000;
 
 // A manager should make interacting with a discord server easier.
 pub struct Manager<'a> {
@@ -36,6 +43,26 @@ impl<'a> Manager<'a> {
         Ok(role)
     }
 
+    // Gets the names of the Discord roles of everyone in people that is in the server so they can be used as tags.
+    // Works for both regular user names and user id strings.
+    pub async fn role_tags(&self, people: &[String]) -> Result<Tags, Error> {
+        let roles = self.guild_id.to_partial_guild(&self.ctx.http).await?.roles;
+        let members = self.guild_id.members(&self.ctx.http, None, None).await?;
+        let mut tags = Tags::new();
+        for member in members.iter() {
+            let role_names: Vec<String> = member.roles.iter()
+                .filter_map(|id| roles.get(id).map(|role| role.name.clone()))
+                .collect();
+            let names = [member.user.to_string(), member.to_string().replace("@", "@!"), member.display_name().to_string()];
+            for name in names.iter() {
+                if people.contains(name) {
+                    tags.insert(name.to_owned(), role_names.clone());
+                }
+            }
+        }
+        Ok(tags)
+    }
+
     // Adds a new channel 'name' and adds all the users in people if they are in the server.
     // Works for both regular user names and user id strings.
     pub async fn add_channel(&self, name: &String, permissions: Option<Vec<&PermissionOverwrite>>) -> Result<GuildChannel, Error> {
@@ -57,42 +84,152 @@ impl<'a> Manager<'a> {
         channel
     }
 
-    // Takes in people and teams, and assigns all the people to teams and prints the result to the server.
+    // Takes in people and teams, and assigns all the people to teams using strategy and prints the result to the server.
     // Modifies the teams LinkedHashMap so that it contains all the people in people.
-    // teams must already contain the team names to be assigned to.
-    pub async fn publish_teams(&self, people: &Vec<String>, teams: &mut LinkedHashMap<String, Vec<String>>) -> Result<(), Error> {
-
+    // teams must already contain the team names to be assigned to, and sizes must have the number of people for each team.
+    pub async fn publish_teams(&self, people: &[String], teams: &mut LinkedHashMap<String, Vec<String>>, sizes: &[usize], strategy: &Strategy<'_>) -> Result<(), Error> {
 
         //Adding the people to groups
-        let mut index: usize = 0;
+        grouping::assign(people, teams, sizes, strategy);
     
-        let team_keys: Vec<String> = teams.keys().map(|k| k.to_owned()).collect();
-        let num_teams = team_keys.len();
+        let output = format_teams(teams);
     
-        for person in people.iter() {
-            teams.get_mut(&team_keys[index]).expect("Failed to get team from key").push(person.to_owned());
-            index += 1;
-            if index == num_teams as usize {
-                index = 0;
+        self.say_output(&output).await?;
+        Ok(())
+    }
+
+    // Reads the participants in the files attached to a message, saying which files and rows could not be read.
+    pub async fn read_attachments(&self, message: &Message) -> Result<Import, Error> {
+        let mut import = Import::default();
+        for attachment in message.attachments.iter() {
+            //JSON files are brackets, which `!tournament` reads itself
+            if attachment.filename.to_lowercase().ends_with(".json") {
+                continue;
             }
+            if attachment.size > MAX_IMPORT_SIZE {
+                self.channel_id.say(&self.ctx.http, format!("{} is too big to import.", attachment.filename)).await?;
+                continue;
+            }
+            let bytes = attachment.download().await?;
+            let file = participants::parse_file(&String::from_utf8_lossy(&bytes));
+            if !file.bad_rows.is_empty() {
+                let rows: Vec<String> = file.bad_rows.iter().map(|row| row.to_string()).collect();
+                self.channel_id.say(&self.ctx.http, format!("Could not read rows {} of {}.", rows.join(", "), attachment.filename)).await?;
+            }
+            self.channel_id.say(&self.ctx.http, format!("Imported {} people from {}.", file.people.len(), attachment.filename)).await?;
+            import.extend(file);
         }
-    
-        let mut output = String::from("");
-        //Adding the grouped people to the output
+        Ok(import)
+    }
+
+    // Reads the first bracket file attached to a message, if there is one. See bracket_file.rs for the layout.
+    pub async fn read_bracket(&self, message: &Message) -> Result<Option<Result<Bracket, ImportError>>, Error> {
+        let attachment = match message.attachments.iter().find(|attachment| attachment.filename.to_lowercase().ends_with(".json")) {
+            Some(attachment) => attachment,
+            None => return Ok(None),
+        };
+        if attachment.size > MAX_IMPORT_SIZE {
+            self.channel_id.say(&self.ctx.http, format!("{} is too big to import.", attachment.filename)).await?;
+            return Ok(None);
+        }
+        let bytes = attachment.download().await?;
+        Ok(Some(BracketFile::parse(&String::from_utf8_lossy(&bytes)).and_then(BracketFile::into_bracket)))
+    }
+
+    // Sends each person a direct message and posts how many were sent and who could not be messaged.
+    // messages has the name of each person and the text to send them. Works for both regular user names and user id strings.
+    pub async fn send_dms(&self, messages: &[(String, String)]) -> Result<(), Error> {
+        let members = self.guild_id.members(&self.ctx.http, None, None).await?;
+        let mut failed: Vec<String> = Vec::new();
+        for (name, text) in messages.iter() {
+            //People who are not in the server or have direct messages turned off can't be messaged
+            let sent = match members.iter().find(|member| is_member(member, name)) {
+                Some(member) => member.user.direct_message(self.ctx, |m| m.content(text)).await.is_ok(),
+                None => false,
+            };
+            if !sent {
+                failed.push(name.clone());
+            }
+        }
+
+        let mut out = format!("Sent {} of {} direct messages.", messages.len() - failed.len(), messages.len());
+        if !failed.is_empty() {
+            out.push_str(&format!(" Could not message {}.", failed.join(", ")));
+        }
+        self.channel_id.say(&self.ctx.http, out).await?;
+        Ok(())
+    }
+
+    // Sends everyone on a team a direct message with their team, teammates and the team's channel if one was made.
+    pub async fn dm_teams(&self, teams: &LinkedHashMap<String, Vec<String>>, channels: &HashMap<String, ChannelId>) -> Result<(), Error> {
+        let guild_name = self.guild_id.name(&self.ctx.cache).await.unwrap_or_default();
+        let mut messages: Vec<(String, String)> = Vec::new();
         for (team_name, team) in teams.iter() {
-            output.push_str(format!("{}:", team_name).as_str());
-            output.push('\n');
             for name in team.iter() {
-                output.push_str("    ");
-                output.push_str(name);
-                output.push('\n');
+                let mut text = format!("You were put in **{}** in {}.\n", team_name, guild_name);
+                let teammates: Vec<String> = team.iter().filter(|teammate| *teammate != name).cloned().collect();
+                if teammates.is_empty() {
+                    text.push_str("Nobody else is on it.\n");
+                } else {
+                    text.push_str(&format!("Your teammates are {}.\n", teammates.join(", ")));
+                }
+                if let Some(channel_id) = channels.get(team_name) {
+                    text.push_str(&format!("Your channel is {}.\n", channel_id.mention()));
+                }
+                messages.push((name.clone(), text));
             }
+        }
+        self.send_dms(&messages).await
+    }
+
+    // Gets the names a user could have been entered as: their mention, their nickname in the server and their user name.
+    pub async fn names_of(&self, user_id: UserId) -> Vec<String> {
+        let mut names = vec![user_id.mention().to_string()];
+        if let Ok(member) = self.guild_id.member(self.ctx, user_id).await {
+            names.push(member.display_name().to_string());
+            names.push(member.user.name.clone());
+        }
+        names
+    }
+
+    // Posts results like teams and brackets in the output style set for the server.
+    pub async fn say_output(&self, output: &str) -> Result<Message, Error> {
+        match config::get(self.ctx, self.guild_id).await.output_style {
+            OutputStyle::Plain => self.channel_id.say(&self.ctx.http, output).await,
+            OutputStyle::Code => self.channel_id.say(&self.ctx.http, format!("```\n{}\n```", output.trim_end())).await,
+            OutputStyle::Embed => self.channel_id.send_message(&self.ctx.http, |m| m.embed(|e| e.description(output))).await,
+        }
+    }
+
+    // Changes a message posted with say_output, like a bracket after a result.
+    pub async fn edit_output(&self, message: &mut Message, output: &str) -> Result<(), Error> {
+        match config::get(self.ctx, self.guild_id).await.output_style {
+            OutputStyle::Plain => message.edit(self.ctx, |m| m.content(output)).await,
+            OutputStyle::Code => message.edit(self.ctx, |m| m.content(format!("```\n{}\n```", output.trim_end()))).await,
+            OutputStyle::Embed => message.edit(self.ctx, |m| m.embed(|e| e.description(output))).await,
+        }
+    }
+
+}
+
+// Lists every team followed by its members, one per line.
+pub fn format_teams(teams: &LinkedHashMap<String, Vec<String>>) -> String {
+    let mut output = String::from("");
+    //Adding the grouped people to the output
+    for (team_name, team) in teams.iter() {
+        output.push_str(format!("{}:", team_name).as_str());
+        output.push('\n');
+        for name in team.iter() {
+            output.push_str("    ");
+            output.push_str(name);
             output.push('\n');
         }
-    
-        self.channel_id.say(&self.ctx.http, output).await?;
-    
-        Ok(())
+        output.push('\n');
     }
+    output
+}
 
+// Checks if a name entered for a person is the server member, by mention or by display name.
+fn is_member(member: &Member, name: &str) -> bool {
+    parse_username(name) == Some(member.user.id.0) || member.display_name().as_str() == name
 }
@@ -1,5 +1,29 @@
+pub mod buttons;
+pub mod challenge;
+pub mod config;
+pub mod draft;
+pub mod export;
 pub mod group;
+pub mod interactions;
+pub mod leaderboard;
+pub mod sessions;
+pub mod status;
 pub mod team;
 pub mod tournament;
+mod bracket;
+mod bracket_file;
+mod checkin;
 mod game;
-mod manager;
\ No newline at end of file
+mod grouping;
+mod heats;
+mod history;
+mod ladder;
+mod manager;
+mod organizers;
+mod participants;
+mod pools;
+mod ratings;
+mod reports;
+mod results;
+mod schedule;
+mod storage;
\ No newline at end of file
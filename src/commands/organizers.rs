@@ -0,0 +1,91 @@
+use std::{sync::Arc, time::Duration};
+
+use serenity::{cache::Cache, client::Context, model::{channel::Message, guild::Member, id::{GuildId, RoleId, UserId}}, prelude::{RwLock, TypeMap}};
+
+use super::{config, sessions::Session};
+
+// The people that can control a session: the person who started it, anyone they made a co-organizer,
+// and anyone with the server's organizer role.
+#[derive(Clone)]
+pub struct Organizers {
+    pub author: UserId,
+    pub co_organizers: Vec<UserId>,
+    pub role: Option<RoleId>,
+    // The server's command prefix. Members who only have the organizer role have to start their messages with it.
+    pub prefix: String,
+    // The session being controlled, used to wait for the messages meant for it.
+    pub session: Session,
+}
+
+impl Organizers {
+    // Constructor for the organizers of a session started by msg, which is added to the session registry as kind.
+    // Looks up the organizer role set with `!config organizer` if there is one.
+    pub async fn new(ctx: &Context, msg: &Message, kind: &str) -> Self {
+        let guild_id = msg.guild_id.expect("Failed to get guild_id from msg.");
+        Organizers {
+            author: msg.author.id,
+            co_organizers: Vec::new(),
+            role: organizer_role(ctx, guild_id).await,
+            prefix: config::get(ctx, guild_id).await.prefix,
+            session: Session::start(ctx, msg, kind).await,
+        }
+    }
+
+    // Checks if the author of a message is allowed to control the session.
+    pub fn accepts(&self, message: &Message) -> bool {
+        match &message.member {
+            Some(member) => self.accepts_user(message.author.id, &member.roles),
+            None => self.accepts_user(message.author.id, &[]),
+        }
+    }
+
+    // Checks if a user with the roles is allowed to control the session, like when they click a button.
+    pub fn accepts_user(&self, user: UserId, roles: &[RoleId]) -> bool {
+        user == self.author
+            || self.co_organizers.contains(&user)
+            || self.role.map(|role| roles.contains(&role)).unwrap_or(false)
+    }
+
+    // Checks if a message is input for the session. The person who started it and co-organizers can send anything, like names,
+    // but members who only have the organizer role have to use a command so what they say in the channel isn't taken as input.
+    pub fn accepts_input(&self, message: &Message, content: &str) -> bool {
+        message.author.id == self.author
+            || self.co_organizers.contains(&message.author.id)
+            || (content.starts_with(&self.prefix) && self.accepts(message))
+    }
+
+    // Checks if the author of a message has the organizer role.
+    pub fn has_role(&self, message: &Message) -> bool {
+        match (self.role, &message.member) {
+            (Some(role), Some(member)) => member.roles.contains(&role),
+            _ => false,
+        }
+    }
+
+    // Waits up to timeout for the next message for the session from someone that can control it, following accepts_input.
+    pub async fn await_reply(&self, ctx: &Context, timeout: Duration) -> Option<Arc<Message>> {
+        let organizers = self.clone();
+        self.session.await_reply(ctx, timeout, move |message, content| organizers.accepts_input(message, content)).await
+    }
+}
+
+// Gets the id of the server's organizer role if one is set and it exists.
+pub async fn organizer_role(ctx: &Context, guild_id: GuildId) -> Option<RoleId> {
+    organizer_role_from(&ctx.cache, &ctx.data, guild_id).await
+}
+
+// Gets the id of the server's organizer role from the client's cache and data when there is no Context.
+pub async fn organizer_role_from(cache: &Cache, data: &RwLock<TypeMap>, guild_id: GuildId) -> Option<RoleId> {
+    let name = config::get_from(data, guild_id).await.organizer_role?;
+    let guild = cache.guild(guild_id).await?;
+    guild.role_by_name(&name).map(|role| role.id)
+}
+
+// Checks if a member has the server's organizer role.
+// Organizers can use -role and -channel without the Manage Roles and Manage Channels permissions.
+pub async fn is_organizer(ctx: &Context, guild_id: GuildId, member: &Member) -> bool {
+    match organizer_role(ctx, guild_id).await {
+        Some(role) => member.roles.contains(&role),
+        None => false,
+    }
+}
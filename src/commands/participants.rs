@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use linked_hash_map::LinkedHashMap;
+use serenity::utils::parse_username;
+
+// The tags attached to each person, keyed by the person's name.
+pub type Tags = HashMap<String, Vec<String>>;
+
+// The teams each person would like to be on from most to least wanted, keyed by the person's name.
+pub type Preferences = HashMap<String, Vec<String>>;
+
+// Adds the names in a comma separated list to people.
+// Words starting with # after a name are tags for that person, so "Sam #mentor #EU, Alex"
+// adds Sam with the tags "mentor" and "EU", and Alex with no tags.
+pub fn parse_names(text: &str, people: &mut Vec<String>, tags: &mut Tags) {
+    for entry in text.split(',') {
+        let mut name: Vec<&str> = Vec::new();
+        let mut person_tags: Vec<String> = Vec::new();
+        for word in entry.split_whitespace() {
+            if word.len() > 1 && word.starts_with('#') {
+                person_tags.push(word[1..].to_owned());
+            } else {
+                name.push(word);
+            }
+        }
+
+        let name = name.join(" ");
+        if name.is_empty() {
+            continue;
+        }
+        if !person_tags.is_empty() {
+            tags.entry(name.clone()).or_default().extend(person_tags);
+        }
+        people.push(name);
+    }
+}
+
+// Everything read from an imported file of participants.
+#[derive(Default)]
+pub struct Import {
+    pub people: Vec<String>,
+    pub tags: Tags,
+    pub preferences: Preferences,
+    // Ratings from a rating column, keyed by the person's name.
+    pub ratings: HashMap<String, f64>,
+    // The line numbers of rows that could not be read, starting at 1.
+    pub bad_rows: Vec<usize>,
+}
+
+// Reads participants from the text of an imported file.
+// Files with a header row that has a "name" column are read as CSV, where the optional columns are
+// "rating", "tags" (separated by spaces or semicolons) and "teams" (preferred teams, most wanted first).
+// Other files are read a line at a time like entered names, so a line can be a comma separated list with # tags.
+pub fn parse_file(text: &str) -> Import {
+    let mut import = Import::default();
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).peekable();
+
+    let header: Vec<String> = match lines.peek() {
+        Some((_, line)) => csv_fields(line).iter().map(|field| field.trim().to_lowercase()).collect(),
+        None => return import,
+    };
+    let column = |names: &[&str]| header.iter().position(|field| names.contains(&field.as_str()));
+    let name_column = match column(&["name"]) {
+        Some(name_column) => name_column,
+        None => {
+            for (_, line) in lines {
+                parse_names(line, &mut import.people, &mut import.tags);
+            }
+            return import;
+        }
+    };
+    let rating_column = column(&["rating"]);
+    let tags_column = column(&["tags", "tag"]);
+    let teams_column = column(&["teams", "team", "preferences", "prefer"]);
+    lines.next();
+
+    let split_list = |text: &str| -> Vec<String> {
+        text.split(|c: char| c == ';' || c.is_whitespace())
+            .map(|item| item.trim_start_matches('#'))
+            .filter(|item| !item.is_empty())
+            .map(|item| item.to_owned())
+            .collect()
+    };
+    for (index, line) in lines {
+        let fields = csv_fields(line);
+        let field = |column: Option<usize>| column.and_then(|column| fields.get(column)).map(|field| field.trim()).unwrap_or_default();
+        let name = field(Some(name_column));
+        let rating = field(rating_column);
+        if name.is_empty() || (!rating.is_empty() && rating.parse::<f64>().is_err()) {
+            import.bad_rows.push(index + 1);
+            continue;
+        }
+
+        if let Ok(rating) = rating.parse::<f64>() {
+            import.ratings.insert(name.to_owned(), rating);
+        }
+        let person_tags = split_list(field(tags_column));
+        if !person_tags.is_empty() {
+            import.tags.entry(name.to_owned()).or_default().extend(person_tags);
+        }
+        let wanted = split_list(field(teams_column));
+        if !wanted.is_empty() {
+            import.preferences.insert(name.to_owned(), wanted);
+        }
+        import.people.push(name.to_owned());
+    }
+    import
+}
+
+impl Import {
+    // Adds everything from another import to this one.
+    pub fn extend(&mut self, other: Import) {
+        self.people.extend(other.people);
+        merge_tags(&mut self.tags, other.tags);
+        self.preferences.extend(other.preferences);
+        self.ratings.extend(other.ratings);
+    }
+}
+
+// Splits a line of a CSV file into its fields, handling quoted fields.
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().expect("Failed to get field.").push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => fields.last_mut().expect("Failed to get field.").push(c),
+        }
+    }
+    fields
+}
+
+// Adds more tags to the people in tags.
+pub fn merge_tags(tags: &mut Tags, more: Tags) {
+    for (person, person_tags) in more {
+        tags.entry(person).or_default().extend(person_tags);
+    }
+}
+
+// Removes every tag that is not in keep, ignoring capitalization.
+pub fn keep_tags(tags: &mut Tags, keep: &[String]) {
+    for person_tags in tags.values_mut() {
+        person_tags.retain(|tag| keep.iter().any(|k| k.eq_ignore_ascii_case(tag)));
+    }
+}
+
+// Counts how many of the people have a tag, ignoring capitalization.
+pub fn count_tag(tags: &Tags, people: &[String], tag: &str) -> usize {
+    people.iter()
+        .filter(|person| tags.get(*person).into_iter().flatten().any(|t| t.eq_ignore_ascii_case(tag)))
+        .count()
+}
+
+// Counts the people with preferences that ended up on their first choice of team.
+// Returns the number that got their first choice and the number of people with preferences.
+pub fn first_choices(preferences: &Preferences, teams: &LinkedHashMap<String, Vec<String>>) -> (usize, usize) {
+    let mut first = 0;
+    let mut total = 0;
+    for (team_name, team) in teams.iter() {
+        for person in team.iter() {
+            if let Some(wanted) = preferences.get(person) {
+                total += 1;
+                if matches!(wanted.first(), Some(choice) if choice.eq_ignore_ascii_case(team_name)) {
+                    first += 1;
+                }
+            }
+        }
+    }
+    (first, total)
+}
+
+// Checks if two entered names are the same person, treating "<@!id>" and "<@id>" mentions as the same.
+pub fn same_person(first: &str, second: &str) -> bool {
+    match (parse_username(first), parse_username(second)) {
+        (Some(first), Some(second)) => first == second,
+        _ => first.eq_ignore_ascii_case(second),
+    }
+}
+
+// Finds the entered name that is the person with one of the names, like their mention, nickname or user name.
+pub fn find_person<'a>(people: &'a [String], names: &[String]) -> Option<&'a String> {
+    people.iter().find(|person| names.iter().any(|name| same_person(person, name)))
+}
@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+
+use linked_hash_map::LinkedHashMap;
+use serde_json::json;
+use serenity::{client::Context, framework::standard::CommandError, model::{id::{ChannelId, GuildId, MessageId}, misc::Mentionable}};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+use super::{bracket::{self, DeclareError}, buttons::{self, Click}, config::GuildConfig, grouping::{self, Strategy}, manager::Manager, organizers::Organizers, participants::same_person, ratings::Ratings};
+
+// A game between two teams in a pool.
+pub struct PoolGame {
+    // The pool's letter and the game's number in the pool, like "A-3".
+    pub id: String,
+    pub top: String,
+    pub bottom: String,
+    // Empty until the game is declared.
+    pub winner: String,
+}
+
+// A team's record in its pool.
+pub struct Standing {
+    pub team: String,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+// A group of teams that all play each other once.
+pub struct Pool {
+    // The pool's letter, like "A".
+    pub name: String,
+    // The teams in the order they were put in the pool, which breaks ties that head-to-head games don't.
+    pub teams: Vec<String>,
+    pub games: Vec<PoolGame>,
+}
+
+impl Pool {
+    // Makes a pool where every team plays every other team once.
+    // Games are ordered by round with the circle method, so every team plays about as often early on.
+    fn new(name: String, teams: Vec<String>) -> Self {
+        let mut slots: Vec<Option<&String>> = teams.iter().map(Some).collect();
+        if slots.len() % 2 == 1 {
+            slots.push(None);
+        }
+        let mut games = Vec::new();
+        for _ in 1..slots.len() {
+            for index in 0..slots.len() / 2 {
+                if let (Some(top), Some(bottom)) = (slots[index], slots[slots.len() - 1 - index]) {
+                    games.push(PoolGame { id: format!("{}-{}", name, games.len() + 1), top: top.clone(), bottom: bottom.clone(), winner: String::new() });
+                }
+            }
+            //The first team stays put and everyone else moves one spot around the circle
+            let last = slots.pop().expect("Pools have at least two slots.");
+            slots.insert(1, last);
+        }
+        Pool { name, teams, games }
+    }
+
+    // Gets the teams from first place down. Teams with more wins go first, then fewer losses.
+    // Two teams that are still tied are put in order by the game between them, and otherwise the order they were put in the pool.
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = self.teams.iter()
+            .map(|team| Standing {
+                team: team.clone(),
+                wins: self.games.iter().filter(|game| game.winner == *team).count() as u32,
+                losses: self.games.iter().filter(|game| !game.winner.is_empty() && game.winner != *team && (game.top == *team || game.bottom == *team)).count() as u32,
+            })
+            .collect();
+        standings.sort_by_key(|standing| (std::cmp::Reverse(standing.wins), standing.losses));
+
+        let mut index = 0;
+        while index < standings.len() {
+            let tied = standings[index..].iter()
+                .take_while(|standing| standing.wins == standings[index].wins && standing.losses == standings[index].losses)
+                .count();
+            if tied == 2 && self.beat(&standings[index + 1].team, &standings[index].team) {
+                standings.swap(index, index + 1);
+            }
+            index += tied;
+        }
+        standings
+    }
+
+    // Checks if a team won its game against another team.
+    fn beat(&self, winner: &str, loser: &str) -> bool {
+        self.games.iter().any(|game| game.winner == winner && (game.top == loser || game.bottom == loser))
+    }
+}
+
+// The pools of a tournament's group stage.
+pub struct PoolStage {
+    pub pools: Vec<Pool>,
+}
+
+impl PoolStage {
+    // Makes pools named A, B, C... from the teams in each group.
+    pub fn new(groups: Vec<Vec<String>>) -> Self {
+        let pools = groups.into_iter().enumerate()
+            .map(|(index, teams)| Pool::new(pool_letter(index), teams))
+            .collect();
+        PoolStage { pools }
+    }
+
+    // Gets a game by its id.
+    fn game_mut(&mut self, id: &str) -> Option<&mut PoolGame> {
+        self.pools.iter_mut().flat_map(|pool| pool.games.iter_mut()).find(|game| game.id.eq_ignore_ascii_case(id))
+    }
+
+    // Declares the winner of a game. With correct, a game that was already declared is changed.
+    // Returns the game's id and the winner as they were entered in the pool.
+    pub fn declare(&mut self, id: &str, winner: &str, correct: bool) -> Result<(String, String), DeclareError> {
+        let game = self.game_mut(id).ok_or(DeclareError::UnknownGame)?;
+        if !game.winner.is_empty() && !correct {
+            return Err(DeclareError::AlreadyDeclared);
+        }
+        let winner = if same_person(&game.top, winner) {
+            game.top.clone()
+        } else if same_person(&game.bottom, winner) {
+            game.bottom.clone()
+        } else {
+            return Err(DeclareError::NotInGame);
+        };
+        if game.winner == winner {
+            return Err(DeclareError::SameWinner);
+        }
+        game.winner = winner.clone();
+        Ok((game.id.clone(), winner))
+    }
+
+    // Gets the games that have not been declared.
+    pub fn unplayed(&self) -> Vec<&PoolGame> {
+        self.pools.iter().flat_map(|pool| pool.games.iter()).filter(|game| game.winner.is_empty()).collect()
+    }
+
+    // Gets the teams that move on from each pool, from first place down.
+    pub fn qualifiers(&self, advance: usize) -> Vec<Vec<String>> {
+        self.pools.iter()
+            .map(|pool| pool.standings().into_iter().take(advance).map(|standing| standing.team).collect())
+            .collect()
+    }
+
+    // Makes the text of every pool's standings, marking the places that move on.
+    pub fn text(&self, advance: usize) -> String {
+        let mut out = String::new();
+        for pool in self.pools.iter() {
+            out.push_str(&format!("\nPool {}:\n", pool.name));
+            for (index, standing) in pool.standings().iter().enumerate() {
+                let mark = if index < advance { " (advances)" } else { "" };
+                out.push_str(&format!("\t{}. {} {}-{}{}\n", index + 1, standing.team, standing.wins, standing.losses, mark));
+            }
+        }
+        out
+    }
+}
+
+// Gets the letter of a pool from its index, like "B" for 1.
+fn pool_letter(index: usize) -> String {
+    ((b'A' + index as u8) as char).to_string()
+}
+
+// Makes the name of a pool game from its id, like "Pool A Game 3" for "A-3".
+pub fn pool_game_name(id: &str) -> String {
+    let mut parts = id.split('-');
+    format!("Pool {} Game {}", parts.next().unwrap_or_default(), parts.next().unwrap_or_default())
+}
+
+// Splits the teams into pools with the strategy, runs every pool game, and returns the teams that move on in bracket order.
+// Organizers declare the games with the buttons posted for each one or with `!declare [pool game] [winner's name]`,
+// and can change a result with `!correct`. Returns None if the organizers stop the tournament or stop declaring results.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(ctx: &Context, guild_id: GuildId, channel_id: ChannelId, config: &GuildConfig, organizers: &Organizers, manager: &Manager<'_>,
+    teams: &[String], num_pools: usize, advance: usize, strategy: &Strategy<'_>) -> Result<Option<Vec<String>>, CommandError> {
+    let mut groups: LinkedHashMap<String, Vec<String>> = LinkedHashMap::new();
+    for index in 0..num_pools {
+        groups.insert(pool_letter(index), Vec::new());
+    }
+    grouping::assign(teams, &mut groups, &grouping::even_sizes(teams.len(), num_pools), strategy);
+    let mut stage = PoolStage::new(groups.into_iter().map(|(_, pool)| pool).collect());
+
+    channel_id.say(&ctx.http, format!("**Group stage**: every team plays everyone else in its pool once, and the top {} of each pool move on to the bracket.\n\
+        Click the winner of each game, or use `{} [pool game] [winner's name]`, like `{} A-1 team1`. Use `{} [pool game] [winner's name]` to change a result.\n\
+        If you want to end the tournament use `{}`.", advance, config.command("declare"), config.command("declare"), config.command("correct"), config.command("stop"))).await?;
+    let mut board = manager.say_output(&stage.text(advance)).await?;
+
+    //Clicks on the winner buttons are sent here until the group stage ends
+    let session = board.id.to_string();
+    let (sender, mut clicks) = mpsc::unbounded_channel();
+    buttons::register(&ctx.data, session.clone(), sender).await;
+    let mut game_messages: HashMap<String, MessageId> = HashMap::new();
+    for game in stage.unplayed() {
+        let mut choices: Vec<(String, String)> = Vec::new();
+        for (team, slot) in [(&game.top, "top"), (&game.bottom, "bottom")].iter() {
+            choices.push((buttons::label(&ctx.cache, team).await, format!("declare:{}:{}:{}", session, game.id, slot)));
+        }
+        let text = format!("**{}**: {} vs {}", pool_game_name(&game.id), game.top, game.bottom);
+        game_messages.insert(game.id.clone(), buttons::send(&ctx.http, channel_id, &text, &buttons::button_row(&choices)).await?);
+    }
+
+    let mut finished = false;
+    while !finished {
+        let (game, winner, correct) = match next_result(ctx, config, organizers, &mut clicks, &stage).await {
+            Some(PoolInput::Result(game, winner, correct)) => (game, winner, correct),
+            Some(PoolInput::Invalid(text)) => {
+                channel_id.say(&ctx.http, text).await?;
+                continue;
+            }
+            Some(PoolInput::Stop) | None => break,
+        };
+        match stage.declare(&game, &winner, correct) {
+            Ok((game, winner)) => {
+                channel_id.say(&ctx.http, format!("{} won {}.", winner, pool_game_name(&game))).await?;
+                manager.edit_output(&mut board, &stage.text(advance)).await?;
+                if let Some(message_id) = game_messages.remove(&game) {
+                    let text = format!("**{}**: {} won", pool_game_name(&game), winner);
+                    buttons::edit(&ctx.http, channel_id, message_id, &text, &json!([])).await?;
+                }
+            }
+            Err(error) => {
+                channel_id.say(&ctx.http, error.to_string()).await?;
+            }
+        }
+        finished = stage.unplayed().is_empty();
+    }
+    buttons::unregister(&ctx.data, &session).await;
+
+    if !finished {
+        for (game, message_id) in game_messages.iter() {
+            buttons::edit(&ctx.http, channel_id, *message_id, &format!("**{}**: not played", pool_game_name(game)), &json!([])).await?;
+        }
+        channel_id.say(&ctx.http, "The tournament was ended during the group stage.").await?;
+        return Ok(None);
+    }
+
+    //Pool games count toward the server's ratings like bracket games
+    let mut ratings = Ratings::load(guild_id);
+    for game in stage.pools.iter().flat_map(|pool| pool.games.iter()) {
+        let loser = if game.winner == game.top { &game.bottom } else { &game.top };
+        ratings.record(&game.winner, loser, config);
+    }
+    ratings.save(guild_id)?;
+
+    let qualifiers = stage.qualifiers(advance);
+    let mut out = String::from("The group stage is over. Moving on to the bracket:\n");
+    for (pool, teams) in stage.pools.iter().zip(qualifiers.iter()) {
+        let places: Vec<String> = teams.iter().enumerate().map(|(index, team)| format!("{}{} {}", pool.name, index + 1, team)).collect();
+        out.push_str(&format!("Pool {}: {}\n", pool.name, places.join(", ")));
+    }
+    channel_id.say(&ctx.http, out).await?;
+    Ok(Some(bracket::cross_pool_order(&qualifiers)))
+}
+
+// Something the organizers did during the group stage.
+enum PoolInput {
+    // A game's id, the winner's name and whether it corrects an earlier result.
+    Result(String, String, bool),
+    // A message that could not be read, with what to tell the organizers.
+    Invalid(String),
+    Stop,
+}
+
+// Waits for the organizers to declare a pool game by clicking a button or sending a command.
+async fn next_result(ctx: &Context, config: &GuildConfig, organizers: &Organizers, clicks: &mut UnboundedReceiver<Click>, stage: &PoolStage) -> Option<PoolInput> {
+    let timeout = config.tournament_timeout();
+    tokio::select! {
+        reply = organizers.await_reply(ctx, timeout) => {
+            let reply = reply?;
+            let words: Vec<&str> = reply.content.splitn(3, ' ').collect();
+            match words.as_slice() {
+                [command] if *command == config.command("stop") => Some(PoolInput::Stop),
+                [command, game, winner] if *command == config.command("declare") || *command == config.command("correct") =>
+                    Some(PoolInput::Result((*game).to_owned(), winner.trim().to_owned(), *command == config.command("correct"))),
+                _ => Some(PoolInput::Invalid(format!("You should use the format `{} [pool game] [winner's name]`, like `{} A-1 team1`.",
+                    config.command("declare"), config.command("declare")))),
+            }
+        }
+        click = clicks.recv() => {
+            let click = click?;
+            if !organizers.accepts_user(click.user, &click.roles) {
+                return Some(PoolInput::Invalid(format!("{}, only the organizers of this tournament can declare winners.", click.user.mention())));
+            }
+            match stage.pools.iter().flat_map(|pool| pool.games.iter()).find(|game| game.id == click.target) {
+                Some(game) => {
+                    let winner = if click.choice == "top" { &game.top } else { &game.bottom };
+                    Some(PoolInput::Result(game.id.clone(), winner.clone(), false))
+                }
+                None => Some(PoolInput::Invalid("That game is not in the group stage.".to_owned())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn every_team_plays_everyone_once() {
+        for size in 2..=7 {
+            let teams: Vec<String> = (1..=size).map(|team| team.to_string()).collect();
+            let pool = Pool::new("A".to_owned(), teams.clone());
+            assert_eq!(pool.games.len(), size * (size - 1) / 2);
+            for (index, first) in teams.iter().enumerate() {
+                for second in teams[index + 1..].iter() {
+                    let played = pool.games.iter()
+                        .filter(|game| (game.top == *first && game.bottom == *second) || (game.top == *second && game.bottom == *first))
+                        .count();
+                    assert_eq!(played, 1, "{} and {} should play once in a pool of {}", first, second, size);
+                }
+            }
+        }
+    }
+
+    // Gets the id of the game between two teams in the first pool.
+    fn game_between(stage: &PoolStage, first: &str, second: &str) -> String {
+        stage.pools[0].games.iter()
+            .find(|game| (game.top == first && game.bottom == second) || (game.top == second && game.bottom == first))
+            .map(|game| game.id.clone())
+            .expect("Every team plays everyone in their pool.")
+    }
+
+    #[test]
+    fn ties_are_broken_by_the_game_between_them() {
+        let mut stage = PoolStage::new(vec![names(&["A", "B", "C", "D"])]);
+        for (winner, loser) in [("B", "A"), ("A", "C"), ("C", "B"), ("A", "D"), ("B", "D"), ("C", "D")].iter() {
+            assert!(stage.declare(&game_between(&stage, winner, loser), winner, false).is_ok());
+        }
+        assert!(stage.unplayed().is_empty());
+
+        //A, B and C are all 2-1 and beat each other in a circle, so they keep the order they were entered in
+        let order = |stage: &PoolStage| -> Vec<String> { stage.pools[0].standings().into_iter().map(|standing| standing.team).collect() };
+        assert_eq!(order(&stage), names(&["A", "B", "C", "D"]));
+        assert_eq!(stage.qualifiers(2), vec![names(&["A", "B"])]);
+
+        //Changing the result of C's game against D breaks the three way tie. B beat A and D beat C, so they go first in their ties
+        let c_vs_d = game_between(&stage, "C", "D");
+        assert!(matches!(stage.declare(&c_vs_d, "D", false), Err(DeclareError::AlreadyDeclared)));
+        assert!(stage.declare(&c_vs_d, "d", true).is_ok());
+        assert_eq!(order(&stage), names(&["B", "A", "D", "C"]));
+    }
+}
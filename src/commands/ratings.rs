@@ -0,0 +1,282 @@
+use std::{cmp::Ordering, collections::HashMap, f64::consts::PI, io};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::id::GuildId;
+
+use super::{config::{GuildConfig, RatingSystem}, history::normalize, results::SessionResult, storage};
+
+// The rating everyone starts with.
+pub const START_RATING: f64 = 1500.0;
+// How unsure a new Glicko-2 rating is and how much it is expected to swing.
+const START_DEVIATION: f64 = 350.0;
+const START_VOLATILITY: f64 = 0.06;
+// Limits how fast Glicko-2 volatility can change. Lower values keep ratings steadier.
+const TAU: f64 = 0.5;
+// Converts ratings to and from the scale Glicko-2 does its math on.
+const GLICKO2_SCALE: f64 = 173.7178;
+// How close the Glicko-2 volatility has to get before it is kept.
+const CONVERGENCE: f64 = 0.000001;
+
+// The rating of a person or team.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Rating {
+    // The name they were last entered as.
+    pub name: String,
+    pub rating: f64,
+    // How unsure the rating is. Only Glicko-2 changes it.
+    pub deviation: f64,
+    // How much the rating is expected to swing. Only Glicko-2 changes it.
+    pub volatility: f64,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+impl Rating {
+    fn new(name: &str) -> Self {
+        Rating {
+            name: name.to_owned(),
+            rating: START_RATING,
+            deviation: START_DEVIATION,
+            volatility: START_VOLATILITY,
+            wins: 0,
+            losses: 0,
+        }
+    }
+}
+
+// The ratings of everyone who has played a rated game in a guild.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Ratings {
+    // Keyed by name as returned by history::normalize.
+    players: HashMap<String, Rating>,
+}
+
+impl Ratings {
+    // Loads the ratings of a guild.
+    pub fn load(guild_id: GuildId) -> Self {
+        storage::load(guild_id, "ratings")
+    }
+
+    // Saves the ratings of a guild.
+    pub fn save(&self, guild_id: GuildId) -> io::Result<()> {
+        storage::save(guild_id, "ratings", self)
+    }
+
+    // Gets the rating of a person or team by name.
+    pub fn get(&self, name: &str) -> Option<&Rating> {
+        self.players.get(&normalize(name))
+    }
+
+    // Gets the rating of everyone in names that has one, keyed by the names as they were given.
+    pub fn values_for(&self, names: &[String]) -> HashMap<String, f64> {
+        names.iter()
+            .filter_map(|name| self.get(name).map(|rating| (name.clone(), rating.rating)))
+            .collect()
+    }
+
+    // Gets the ratings to balance or seed people by. Imported ratings, like from a CSV file, are used over the guild's ratings.
+    pub fn balance_ratings(&self, people: &[String], imported: HashMap<String, f64>) -> HashMap<String, f64> {
+        let mut ratings = self.values_for(people);
+        ratings.extend(imported.into_iter().filter(|(name, _)| people.contains(name)));
+        ratings
+    }
+
+    // Gets everyone's ratings from highest to lowest.
+    pub fn leaderboard(&self) -> Vec<&Rating> {
+        let mut ratings: Vec<&Rating> = self.players.values().collect();
+        ratings.sort_by(|first, second| second.rating.partial_cmp(&first.rating).unwrap_or(Ordering::Equal).then_with(|| first.name.cmp(&second.name)));
+        ratings
+    }
+
+    // Gets where someone is on the leaderboard, starting at 1.
+    pub fn rank(&self, name: &str) -> Option<usize> {
+        let key = normalize(name);
+        self.leaderboard().iter().position(|rating| normalize(&rating.name) == key).map(|index| index + 1)
+    }
+
+    // Updates the ratings of both teams in a game with the guild's rating system.
+    pub fn record(&mut self, winner: &str, loser: &str, config: &GuildConfig) {
+        let first = self.players.get(&normalize(winner)).cloned().unwrap_or_else(|| Rating::new(winner));
+        let second = self.players.get(&normalize(loser)).cloned().unwrap_or_else(|| Rating::new(loser));
+        //Both ratings are worked out from the ratings before the game
+        let (mut first_new, mut second_new) = match config.rating_system {
+            RatingSystem::Elo => (elo(&first, &second, 1.0, config.rating_k), elo(&second, &first, 0.0, config.rating_k)),
+            RatingSystem::Glicko2 => (glicko2(&first, &[(&second, 1.0)]), glicko2(&second, &[(&first, 0.0)])),
+        };
+        first_new.name = winner.to_owned();
+        first_new.wins += 1;
+        second_new.name = loser.to_owned();
+        second_new.losses += 1;
+        self.players.insert(normalize(winner), first_new);
+        self.players.insert(normalize(loser), second_new);
+    }
+
+    // Updates the ratings with every game played in a finished tournament, in the order they were declared.
+    // Forfeits are left out since nothing was played. Returns how many games were rated.
+    pub fn record_result(&mut self, result: &SessionResult, config: &GuildConfig) -> usize {
+        let mut games: Vec<_> = result.games.iter()
+            .filter(|game| !game.winner.is_empty() && ((!game.top.is_empty() && !game.bottom.is_empty()) || game.teams.len() > 1) && !game.forfeit)
+            .collect();
+        games.sort_by_key(|game| (game.declared_at, game.round));
+        for game in games.iter() {
+            if game.teams.is_empty() {
+                let loser = if game.winner == game.top { &game.bottom } else { &game.top };
+                self.record(&game.winner, loser, config);
+                continue;
+            }
+            //A heat counts as a win over everyone who finished behind, with the teams left out of the ranking tied last
+            let mut finish: Vec<&String> = game.ranking.iter().collect();
+            finish.extend(game.teams.iter().filter(|team| !game.ranking.contains(team)));
+            for (index, winner) in finish.iter().enumerate().take(game.ranking.len()) {
+                for loser in finish.iter().skip(index + 1) {
+                    self.record(winner, loser, config);
+                }
+            }
+        }
+        games.len()
+    }
+}
+
+// Gets a player's new Elo rating after a game with score 1 for a win and 0 for a loss.
+fn elo(player: &Rating, opponent: &Rating, score: f64, k: f64) -> Rating {
+    let expected = 1.0 / (1.0 + 10f64.powf((opponent.rating - player.rating) / 400.0));
+    Rating { rating: player.rating + k * (score - expected), ..player.clone() }
+}
+
+// Gets a player's new Glicko-2 rating after a rating period, following the steps in Glickman's paper on Glicko-2.
+// games has each opponent in the period with a score of 1 for a win and 0 for a loss. The bot rates every game as its own period.
+fn glicko2(player: &Rating, games: &[(&Rating, f64)]) -> Rating {
+    let mu = (player.rating - START_RATING) / GLICKO2_SCALE;
+    let phi = player.deviation / GLICKO2_SCALE;
+
+    //The weight of each game and the score the player was expected to get in it
+    let weighted: Vec<(f64, f64, f64)> = games.iter()
+        .map(|(opponent, score)| {
+            let opponent_mu = (opponent.rating - START_RATING) / GLICKO2_SCALE;
+            let opponent_phi = opponent.deviation / GLICKO2_SCALE;
+            let g = 1.0 / (1.0 + 3.0 * opponent_phi.powi(2) / PI.powi(2)).sqrt();
+            (g, 1.0 / (1.0 + (-g * (mu - opponent_mu)).exp()), *score)
+        })
+        .collect();
+    let variance = 1.0 / weighted.iter().map(|(g, expected, _)| g.powi(2) * expected * (1.0 - expected)).sum::<f64>();
+    let improvement: f64 = weighted.iter().map(|(g, expected, score)| g * (score - expected)).sum();
+    let delta = variance * improvement;
+
+    //Finding the new volatility with the Illinois algorithm
+    let a = player.volatility.powi(2).ln();
+    let f = |x: f64| {
+        let ex = x.exp();
+        ex * (delta.powi(2) - phi.powi(2) - variance - ex) / (2.0 * (phi.powi(2) + variance + ex).powi(2)) - (x - a) / TAU.powi(2)
+    };
+    let mut lower = a;
+    let mut upper = if delta.powi(2) > phi.powi(2) + variance {
+        (delta.powi(2) - phi.powi(2) - variance).ln()
+    } else {
+        let mut k = 1.0;
+        while f(a - k * TAU) < 0.0 {
+            k += 1.0;
+        }
+        a - k * TAU
+    };
+    let mut f_lower = f(lower);
+    let mut f_upper = f(upper);
+    while (upper - lower).abs() > CONVERGENCE {
+        let middle = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+        let f_middle = f(middle);
+        if f_middle * f_upper <= 0.0 {
+            lower = upper;
+            f_lower = f_upper;
+        } else {
+            f_lower /= 2.0;
+        }
+        upper = middle;
+        f_upper = f_middle;
+    }
+    let volatility = (lower / 2.0).exp();
+
+    let before = (phi.powi(2) + volatility.powi(2)).sqrt();
+    let new_phi = 1.0 / (1.0 / before.powi(2) + 1.0 / variance).sqrt();
+    let new_mu = mu + new_phi.powi(2) * improvement;
+    Rating {
+        rating: new_mu * GLICKO2_SCALE + START_RATING,
+        deviation: new_phi * GLICKO2_SCALE,
+        volatility,
+        ..player.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::results::GameResult;
+
+    fn rating(rating: f64, deviation: f64) -> Rating {
+        Rating { rating, deviation, ..Rating::new("") }
+    }
+
+    fn config(rating_system: RatingSystem) -> GuildConfig {
+        GuildConfig { rating_system, rating_k: 32.0, ..GuildConfig::default() }
+    }
+
+    #[test]
+    fn glicko2_matches_glickmans_example() {
+        //The example in Glickman's paper: a 1500 player with a deviation of 200 beats a 1400 player, then loses to a 1550 and a 1700 player
+        let player = rating(1500.0, 200.0);
+        let opponents = [rating(1400.0, 30.0), rating(1550.0, 100.0), rating(1700.0, 300.0)];
+        let rated = glicko2(&player, &[(&opponents[0], 1.0), (&opponents[1], 0.0), (&opponents[2], 0.0)]);
+        assert!((rated.rating - 1464.06).abs() < 0.01, "rating was {}", rated.rating);
+        assert!((rated.deviation - 151.52).abs() < 0.01, "deviation was {}", rated.deviation);
+        assert!((rated.volatility - 0.05999).abs() < 0.00001, "volatility was {}", rated.volatility);
+    }
+
+    #[test]
+    fn elo_moves_both_players_by_the_same_amount() {
+        let mut ratings = Ratings::default();
+        ratings.record("Sam", "Alex", &config(RatingSystem::Elo));
+        assert_eq!(ratings.get("sam").map(|rating| rating.rating), Some(1516.0));
+        assert_eq!(ratings.get("Alex").map(|rating| rating.rating), Some(1484.0));
+
+        //Beating someone rated 200 lower is expected about 76% of the time
+        let rated = elo(&rating(1600.0, 0.0), &rating(1400.0, 0.0), 1.0, 32.0);
+        assert!((rated.rating - 1607.69).abs() < 0.01, "rating was {}", rated.rating);
+    }
+
+    #[test]
+    fn glicko2_games_between_new_players_follow_the_paper() {
+        let mut ratings = Ratings::default();
+        ratings.record("Sam", "Alex", &config(RatingSystem::Glicko2));
+        let (winner, loser) = (ratings.get("Sam").unwrap(), ratings.get("Alex").unwrap());
+        //Two unrated players meeting move the same amount in opposite directions, and both become more certain
+        assert!((winner.rating - START_RATING - (START_RATING - loser.rating)).abs() < 0.000001);
+        assert!((winner.rating - 1662.31).abs() < 0.01, "rating was {}", winner.rating);
+        assert!(winner.deviation < START_DEVIATION && (winner.deviation - 290.32).abs() < 0.01, "deviation was {}", winner.deviation);
+        assert_eq!((winner.wins, winner.losses, loser.wins, loser.losses), (1, 0, 0, 1));
+    }
+
+    #[test]
+    fn heats_count_as_a_win_over_everyone_behind() {
+        let heat = GameResult {
+            id: "1-1".to_owned(),
+            round: 1,
+            top: String::new(),
+            bottom: String::new(),
+            winner: "A".to_owned(),
+            declared_at: Some(1),
+            next_game: None,
+            forfeit: false,
+            loser_game: None,
+            teams: vec!["A".to_owned(), "B".to_owned(), "C".to_owned(), "D".to_owned()],
+            ranking: vec!["A".to_owned(), "B".to_owned()],
+        };
+        let result = SessionResult { session: 1, kind: "tournament".to_owned(), channel_id: 1, finished_at: 1, participants: Vec::new(), games: vec![heat] };
+        let mut ratings = Ratings::default();
+        assert_eq!(ratings.record_result(&result, &config(RatingSystem::Elo)), 1);
+
+        let record = |name: &str| ratings.get(name).map(|rating| (rating.wins, rating.losses));
+        //C and D tied behind the ranked teams, so they don't play each other
+        assert_eq!(record("A"), Some((3, 0)));
+        assert_eq!(record("B"), Some((2, 1)));
+        assert_eq!(record("C"), Some((0, 2)));
+        assert_eq!(record("D"), Some((0, 2)));
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+
+use serenity::model::id::MessageId;
+
+// A result reported by one of the teams in a game, waiting for the other team to confirm it.
+#[derive(Clone)]
+pub struct Report {
+    pub game: String,
+    // The team that reported the result.
+    pub reporter: String,
+    // The team that has to confirm it.
+    pub opponent: String,
+    pub winner: String,
+    // When the result counts without being confirmed, in seconds since the Unix epoch.
+    pub deadline: u64,
+    // The message with the confirm and dispute buttons.
+    pub message: Option<MessageId>,
+}
+
+// A reported result that the other team said was wrong, waiting for the organizers.
+#[derive(Clone)]
+pub struct Dispute {
+    pub game: String,
+    // The team that disputed the result.
+    pub team: String,
+    pub winner: String,
+    pub reason: String,
+}
+
+// Why a report could not be made, confirmed or disputed.
+pub enum ReportError {
+    NoReport,
+    NotOpponent,
+    // The game's result was disputed and is waiting for the organizers.
+    Disputed,
+}
+
+impl fmt::Display for ReportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportError::NoReport => write!(f, "No result has been reported for that game."),
+            ReportError::NotOpponent => write!(f, "Only the other team in the game can confirm or dispute the result."),
+            ReportError::Disputed => write!(f, "The result of that game was disputed, so only the organizers can declare it now."),
+        }
+    }
+}
+
+// The results reported by teams in a tournament and the disputes waiting for the organizers.
+#[derive(Default)]
+pub struct Reports {
+    pending: Vec<Report>,
+    disputes: Vec<Dispute>,
+}
+
+impl Reports {
+    // Adds a reported result, replacing any report for the same game. Returns the report it replaced.
+    // A game with an open dispute can't be reported again, so the dispute stays until the organizers declare the game.
+    pub fn report(&mut self, report: Report) -> Result<Option<Report>, ReportError> {
+        if self.is_disputed(&report.game) {
+            return Err(ReportError::Disputed);
+        }
+        let replaced = self.pending.iter().position(|pending| pending.game == report.game).map(|index| self.pending.remove(index));
+        self.pending.push(report);
+        Ok(replaced)
+    }
+
+    // Checks if a game has a dispute waiting for the organizers.
+    pub fn is_disputed(&self, game: &str) -> bool {
+        self.disputes.iter().any(|dispute| dispute.game == game)
+    }
+
+    // Confirms a reported result for the other team. Returns the report so its winner can be declared.
+    pub fn accept(&mut self, game: &str, team: &str) -> Result<Report, ReportError> {
+        let index = self.find(game, team)?;
+        Ok(self.pending.remove(index))
+    }
+
+    // Disputes a reported result for the other team, adding it to the queue for the organizers.
+    pub fn dispute(&mut self, game: &str, team: &str, reason: &str) -> Result<Report, ReportError> {
+        let index = self.find(game, team)?;
+        let report = self.pending.remove(index);
+        self.disputes.push(Dispute { game: game.to_owned(), team: team.to_owned(), winner: report.winner.clone(), reason: reason.to_owned() });
+        Ok(report)
+    }
+
+    // Takes away the reports and disputes for games that are not in games, like games that were declared. Returns the reports that were taken away.
+    pub fn keep_games(&mut self, games: &[String]) -> Vec<Report> {
+        self.disputes.retain(|dispute| games.contains(&dispute.game));
+        let (kept, removed) = self.pending.drain(..).partition(|report| games.contains(&report.game));
+        self.pending = kept;
+        removed
+    }
+
+    // Takes out the reports that were not confirmed or disputed in time, so they can be declared.
+    pub fn expired(&mut self, now: u64) -> Vec<Report> {
+        let (expired, pending) = self.pending.drain(..).partition(|report| report.deadline <= now);
+        self.pending = pending;
+        expired
+    }
+
+    // Gets when the next report counts without being confirmed.
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.pending.iter().map(|report| report.deadline).min()
+    }
+
+    // Gets the disputes waiting for the organizers, oldest first.
+    pub fn disputes(&self) -> &[Dispute] {
+        &self.disputes
+    }
+
+    // Gets the index of the report for a game that a team can confirm or dispute.
+    fn find(&self, game: &str, team: &str) -> Result<usize, ReportError> {
+        let index = self.pending.iter().position(|report| report.game == game).ok_or(ReportError::NoReport)?;
+        if self.pending[index].opponent != team {
+            return Err(ReportError::NotOpponent);
+        }
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(game: &str, reporter: &str, opponent: &str, winner: &str, deadline: u64) -> Report {
+        Report { game: game.to_owned(), reporter: reporter.to_owned(), opponent: opponent.to_owned(), winner: winner.to_owned(), deadline, message: None }
+    }
+
+    #[test]
+    fn only_the_opponent_can_accept_or_dispute() {
+        let mut reports = Reports::default();
+        assert!(matches!(reports.accept("1-1", "B"), Err(ReportError::NoReport)));
+        assert!(matches!(reports.report(report("1-1", "A", "B", "A", 100)), Ok(None)));
+        assert!(matches!(reports.accept("1-1", "A"), Err(ReportError::NotOpponent)));
+        assert!(matches!(reports.dispute("1-1", "C", "No"), Err(ReportError::NotOpponent)));
+        assert!(matches!(reports.accept("1-1", "B"), Ok(accepted) if accepted.winner == "A"));
+        assert!(reports.next_deadline().is_none());
+    }
+
+    #[test]
+    fn reporting_again_replaces_the_report() {
+        let mut reports = Reports::default();
+        reports.report(report("1-1", "A", "B", "A", 100)).ok();
+        assert!(matches!(reports.report(report("1-1", "B", "A", "B", 200)), Ok(Some(replaced)) if replaced.winner == "A"));
+        assert_eq!(reports.next_deadline(), Some(200));
+    }
+
+    #[test]
+    fn disputed_games_cant_be_reported_again() {
+        let mut reports = Reports::default();
+        reports.report(report("1-1", "A", "B", "A", 100)).ok();
+        assert!(reports.dispute("1-1", "B", "B won").is_ok());
+        assert_eq!(reports.disputes().len(), 1);
+        assert!(reports.next_deadline().is_none());
+
+        //The reporter can't wipe out the dispute by reporting again, so nothing counts without the organizers
+        assert!(matches!(reports.report(report("1-1", "A", "B", "A", 100)), Err(ReportError::Disputed)));
+        assert_eq!(reports.disputes().len(), 1);
+        assert!(reports.expired(1000).is_empty());
+    }
+
+    #[test]
+    fn reports_count_once_their_deadline_passes() {
+        let mut reports = Reports::default();
+        reports.report(report("1-1", "A", "B", "A", 100)).ok();
+        reports.report(report("1-2", "C", "D", "D", 200)).ok();
+        assert_eq!(reports.next_deadline(), Some(100));
+        assert!(reports.expired(99).is_empty());
+
+        let expired = reports.expired(100);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].game, "1-1");
+        assert_eq!(reports.next_deadline(), Some(200));
+    }
+
+    #[test]
+    fn declared_games_lose_their_reports_and_disputes() {
+        let mut reports = Reports::default();
+        reports.report(report("1-1", "A", "B", "A", 100)).ok();
+        reports.report(report("1-2", "C", "D", "D", 100)).ok();
+        reports.dispute("1-2", "D", "").ok();
+        reports.report(report("1-3", "E", "F", "E", 100)).ok();
+
+        //The organizers declared 1-1 and 1-2, so only 1-3 is still ready
+        let removed = reports.keep_games(&["1-3".to_owned()]);
+        assert_eq!(removed.iter().map(|report| report.game.as_str()).collect::<Vec<&str>>(), vec!["1-1"]);
+        assert!(reports.disputes().is_empty());
+        assert!(matches!(reports.report(report("1-2", "C", "D", "D", 100)), Ok(None)));
+    }
+}
@@ -0,0 +1,295 @@
+use std::io;
+
+use linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, Serialize};
+use serenity::{client::Context, model::id::{ChannelId, GuildId}, utils::parse_username};
+
+use super::{bracket::{self, Bracket}, bracket_file::BracketFile, storage};
+
+// How many finished sessions are kept for each guild.
+const KEPT_RESULTS: usize = 50;
+
+// A file format that results can be exported as.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    // A bracket file that other bracket tools can read, described in bracket_file.rs.
+    Bracket,
+}
+
+impl ExportFormat {
+    // Gets the format named by a `-export` argument.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            "bracket" => Some(ExportFormat::Bracket),
+            _ => None,
+        }
+    }
+}
+
+// Someone in a finished session.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Participant {
+    pub name: String,
+    // The Discord user id if the name is a mention.
+    pub user_id: Option<u64>,
+    // Where the person was in the order used to make the teams or bracket, starting at 1.
+    pub seed: usize,
+    // The group or team the person was put on. Tournament participants have none.
+    pub team: Option<String>,
+    // Whether the person withdrew from a tournament.
+    #[serde(default)]
+    pub withdrawn: bool,
+    // The place the person finished a tournament in, like "1st" or "5th-8th". None if the final was not played.
+    #[serde(default)]
+    pub placement: Option<String>,
+}
+
+// A game in a finished tournament.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GameResult {
+    pub id: String,
+    pub round: u32,
+    pub top: String,
+    pub bottom: String,
+    // Empty if the game was not played.
+    pub winner: String,
+    // When the winner was declared in seconds since the Unix epoch.
+    pub declared_at: Option<u64>,
+    // The id of the game the winner moves on to. Results saved before this was added have none.
+    #[serde(default)]
+    pub next_game: Option<String>,
+    // Whether the game was won by forfeit.
+    #[serde(default)]
+    pub forfeit: bool,
+    // The id of the game the loser moves on to, like the third place game.
+    #[serde(default)]
+    pub loser_game: Option<String>,
+    // Everyone in a free-for-all heat, whose top and bottom are empty. Games between two teams have none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub teams: Vec<String>,
+    // The order a heat finished in, with the winner first. Teams left out finished behind everyone in it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ranking: Vec<String>,
+}
+
+// The results of a finished session, saved so they can be exported later.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SessionResult {
+    pub session: u32,
+    // The command that ran the session.
+    pub kind: String,
+    pub channel_id: u64,
+    // When the session finished in seconds since the Unix epoch.
+    pub finished_at: u64,
+    pub participants: Vec<Participant>,
+    pub games: Vec<GameResult>,
+}
+
+impl SessionResult {
+    // Makes the results of a session that put people on groups or teams. people is the order they were assigned in.
+    pub fn from_teams(session: u32, kind: &str, channel_id: ChannelId, people: &[String], teams: &LinkedHashMap<String, Vec<String>>) -> Self {
+        let participants = people.iter().enumerate()
+            .map(|(index, name)| Participant {
+                name: name.clone(),
+                user_id: parse_username(name),
+                seed: index + 1,
+                team: teams.iter().find(|(_, members)| members.contains(name)).map(|(team, _)| team.clone()),
+                withdrawn: false,
+                placement: None,
+            })
+            .collect();
+        SessionResult { session, kind: kind.to_owned(), channel_id: channel_id.0, finished_at: storage::now(), participants, games: Vec::new() }
+    }
+
+    // Makes the results of a tournament. teams is the order they were put in the bracket.
+    pub fn from_bracket(session: u32, channel_id: ChannelId, teams: &[String], bracket: &Bracket) -> Self {
+        let placements = bracket.placements();
+        let participants = teams.iter().enumerate()
+            .map(|(index, name)| Participant {
+                name: name.clone(),
+                user_id: parse_username(name),
+                seed: index + 1,
+                team: None,
+                withdrawn: bracket.is_withdrawn(name),
+                placement: placements.iter()
+                    .find(|(_, _, placed)| placed.contains(name))
+                    .map(|(first, last, _)| bracket::place_name(*first, *last)),
+            })
+            .collect();
+        let games = bracket.results().into_iter()
+            .map(|(id, teams, ranking, declared_at)| {
+                //Heats keep everyone in them and the order they finished in, and games between two teams a top and bottom
+                let heat = teams.len() > 2;
+                let slot = |index: usize| if heat { String::new() } else { teams.get(index).cloned().unwrap_or_default() };
+                GameResult {
+                    round: id.split('-').next().and_then(|round| round.parse().ok()).unwrap_or(0),
+                    next_game: bracket.next_id(&id),
+                    loser_game: bracket.loser_id(&id),
+                    forfeit: bracket.is_forfeit(&id),
+                    top: slot(0),
+                    bottom: slot(1),
+                    winner: ranking.first().cloned().unwrap_or_default(),
+                    teams: if heat { teams.iter().filter(|team| !team.is_empty()).cloned().collect() } else { Vec::new() },
+                    ranking: if heat { ranking } else { Vec::new() },
+                    id,
+                    declared_at,
+                }
+            })
+            .collect();
+        SessionResult { session, kind: "tournament".to_owned(), channel_id: channel_id.0, finished_at: storage::now(), participants, games }
+    }
+
+    // Gets the seed of a participant by name.
+    fn seed(&self, name: &str) -> Option<usize> {
+        self.participants.iter().find(|participant| participant.name == name).map(|participant| participant.seed)
+    }
+
+    // Makes a CSV file of the results.
+    // Groups and teams have a row for each person, and tournaments have a row for each game.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::new();
+        if self.games.is_empty() {
+            out.push_str("session,finished_at,team,name,user_id,seed\n");
+            for participant in self.participants.iter() {
+                let row = [
+                    self.session.to_string(),
+                    self.finished_at.to_string(),
+                    participant.team.clone().unwrap_or_default(),
+                    participant.name.clone(),
+                    optional(participant.user_id),
+                    participant.seed.to_string(),
+                ];
+                out.push_str(&csv_row(&row));
+            }
+        } else {
+            out.push_str("session,game,round,top,top_user_id,top_seed,bottom,bottom_user_id,bottom_seed,winner,declared_at,forfeit,teams,ranking\n");
+            //Games nobody made it to are only kept for bracket files
+            for game in self.games.iter().filter(|game| !game.top.is_empty() || !game.bottom.is_empty() || !game.teams.is_empty()) {
+                let row = [
+                    self.session.to_string(),
+                    game.id.clone(),
+                    game.round.to_string(),
+                    game.top.clone(),
+                    optional(parse_username(&game.top)),
+                    optional(self.seed(&game.top)),
+                    game.bottom.clone(),
+                    optional(parse_username(&game.bottom)),
+                    optional(self.seed(&game.bottom)),
+                    game.winner.clone(),
+                    optional(game.declared_at),
+                    game.forfeit.to_string(),
+                    //Heats have everyone in them and the order they finished in instead of a top and bottom
+                    game.teams.join(";"),
+                    game.ranking.join(";"),
+                ];
+                out.push_str(&csv_row(&row));
+            }
+        }
+        out
+    }
+
+    // Makes the file of the results in a format and the name to attach it as.
+    pub fn export(&self, format: ExportFormat) -> (Vec<u8>, String) {
+        match format {
+            ExportFormat::Csv => (self.to_csv().into_bytes(), format!("{}-{}.csv", self.kind, self.session)),
+            ExportFormat::Json => {
+                let text = serde_json::to_string_pretty(self).unwrap_or_default();
+                (text.into_bytes(), format!("{}-{}.json", self.kind, self.session))
+            }
+            //Bracket files only have games between two teams, so heats are exported as JSON
+            ExportFormat::Bracket if self.games.iter().any(|game| !game.teams.is_empty()) => self.export(ExportFormat::Json),
+            ExportFormat::Bracket => {
+                let text = serde_json::to_string_pretty(&BracketFile::from_result(self)).unwrap_or_default();
+                (text.into_bytes(), format!("{}-{}-bracket.json", self.kind, self.session))
+            }
+        }
+    }
+}
+
+// Makes a CSV field from a value that might be missing.
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+// Makes a line of a CSV file, quoting fields that have commas, quotes or new lines in them.
+fn csv_row(fields: &[String]) -> String {
+    let fields: Vec<String> = fields.iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect();
+    format!("{}\n", fields.join(","))
+}
+
+// Saves the results of a finished session so `!export` can find them. Only the latest results are kept.
+pub fn save(guild_id: GuildId, result: &SessionResult) -> io::Result<()> {
+    let mut results: Vec<SessionResult> = storage::load(guild_id, "results");
+    results.push(result.clone());
+    if results.len() > KEPT_RESULTS {
+        results.drain(..results.len() - KEPT_RESULTS);
+    }
+    storage::save(guild_id, "results", &results)
+}
+
+// Finds the latest saved results of a session in a guild, or of the last session if no id is given.
+pub fn find(guild_id: GuildId, session: Option<u32>) -> Option<SessionResult> {
+    let results: Vec<SessionResult> = storage::load(guild_id, "results");
+    results.into_iter().rev().find(|result| session.is_none_or(|session| result.session == session))
+}
+
+// Posts the results of a session as an attached file.
+pub async fn send(ctx: &Context, channel_id: ChannelId, result: &SessionResult, format: ExportFormat) -> Result<(), serenity::Error> {
+    let (file, name) = result.export(format);
+    channel_id.send_files(&ctx.http, vec![(file.as_slice(), name.as_str())], |m| m.content(format!("Results of session {}.", result.session))).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn teams_have_a_row_for_each_person() {
+        let people: Vec<String> = vec!["<@!12>".to_owned(), "Sam".to_owned(), "Alex \"Al\" Smith".to_owned()];
+        let mut teams: LinkedHashMap<String, Vec<String>> = LinkedHashMap::new();
+        teams.insert("Team 1".to_owned(), vec![people[0].clone(), people[2].clone()]);
+        teams.insert("Team, 2".to_owned(), vec![people[1].clone()]);
+        let mut result = SessionResult::from_teams(7, "team", ChannelId(1), &people, &teams);
+        result.finished_at = 100;
+
+        assert_eq!(result.to_csv(), "session,finished_at,team,name,user_id,seed\n\
+            7,100,Team 1,<@!12>,12,1\n\
+            7,100,\"Team, 2\",Sam,,2\n\
+            7,100,Team 1,\"Alex \"\"Al\"\" Smith\",,3\n");
+    }
+
+    #[test]
+    fn tournaments_have_a_row_for_each_game() {
+        let teams: Vec<String> = vec!["<@12>".to_owned(), "Sam".to_owned(), "Alex".to_owned()];
+        let bracket = Bracket::new(&teams);
+        let (id, top, bottom) = bracket.ready_games().into_iter().find(|(_, top, bottom)| !top.is_empty() && !bottom.is_empty())
+            .expect("Two teams should play in the first round.");
+        bracket.declare(&id, &bottom).ok();
+        let mut result = SessionResult::from_bracket(3, ChannelId(1), &teams, &bracket);
+        for game in result.games.iter_mut() {
+            game.declared_at = game.declared_at.map(|_| 50);
+        }
+
+        let csv = result.to_csv();
+        assert_eq!(csv.lines().next(), Some("session,game,round,top,top_user_id,top_seed,bottom,bottom_user_id,bottom_seed,winner,declared_at,forfeit,teams,ranking"));
+        let seed = |name: &str| teams.iter().position(|team| team == name).map(|index| index + 1).unwrap();
+        let user_id = |name: &str| parse_username(name).map(|id| id.to_string()).unwrap_or_default();
+        let played = format!("3,{},1,{},{},{},{},{},{},{},50,false,,", id, top, user_id(&top), seed(&top), bottom, user_id(&bottom), seed(&bottom), bottom);
+        assert!(csv.lines().any(|line| line == played), "{} should have {}", csv, played);
+        //The final has a team in it but has not been played
+        assert!(csv.lines().any(|line| line.starts_with("3,2-1,2,") && line.ends_with(",,,false,,")));
+    }
+}
@@ -0,0 +1,267 @@
+use std::{collections::HashSet, sync::Weak, time::Duration};
+
+use serenity::{client::Context, model::{id::{ChannelId, GuildId}, misc::Mentionable}};
+
+use super::{bracket::{Bracket, game_name}, config, storage};
+
+// How often the scheduler looks for games to remind people about.
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+// A change to a tournament's schedule asked for with `!schedule`.
+pub enum ScheduleRequest {
+    // Sets when the game with the id starts, or takes it off the schedule.
+    Game(String, Option<u64>),
+    // Schedules every round, starting at the time and spacing rounds by the number of seconds.
+    Rounds(u64, u64),
+}
+
+// Reads the text after `!schedule`, like "2-1 2026-10-20 19:00 UTC", "2-1 none" or "rounds 2026-10-20 19:00 UTC 1h".
+pub fn parse_request(text: &str) -> Option<ScheduleRequest> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    match words.as_slice() {
+        ["rounds", time @ .., spacing] => Some(ScheduleRequest::Rounds(parse_time(time)?, parse_duration(spacing)?)),
+        [game, "none"] | [game, "clear"] => Some(ScheduleRequest::Game((*game).to_owned(), None)),
+        [game, time @ ..] => Some(ScheduleRequest::Game((*game).to_owned(), Some(parse_time(time)?))),
+        _ => None,
+    }
+}
+
+// Reads a date, time and optional time zone like ["2026-10-20", "19:00", "UTC"] as seconds since the Unix epoch.
+// The time zone can be UTC, GMT or an offset like +02:00, -0500 or UTC+2. Times without one are in UTC.
+pub fn parse_time(words: &[&str]) -> Option<u64> {
+    let (date, time, zone) = match words {
+        [date, time] => (*date, *time, ""),
+        [date, time, zone] => (*date, *time, *zone),
+        _ => return None,
+    };
+
+    let mut date = date.split('-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    if date.next().is_some() || !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    let mut time = time.split(':').map(|part| part.parse::<i64>().ok());
+    let (hour, minute) = (time.next()??, time.next()??);
+    if time.next().is_some() || !(0..24).contains(&hour) || !(0..60).contains(&minute) {
+        return None;
+    }
+
+    let local = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60;
+    let seconds = local - zone_offset(zone)?;
+    if seconds < 0 {
+        None
+    } else {
+        Some(seconds as u64)
+    }
+}
+
+// Reads a length of time like "90m", "1h30m" or "2d" as seconds. Numbers without a unit are minutes.
+pub fn parse_duration(text: &str) -> Option<u64> {
+    if let Ok(minutes) = text.parse::<u64>() {
+        return Some(minutes * 60).filter(|seconds| *seconds > 0);
+    }
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'd' => 86400,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        seconds += number.parse::<u64>().ok()? * unit;
+        number.clear();
+    }
+    if number.is_empty() && seconds > 0 {
+        Some(seconds)
+    } else {
+        None
+    }
+}
+
+// Makes the text of a time that Discord shows in each person's own time zone.
+pub fn timestamp(seconds: u64) -> String {
+    format!("<t:{}:f>", seconds)
+}
+
+// Makes the text of a time that Discord shows as how long from now it is, like "in 15 minutes".
+pub fn relative(seconds: u64) -> String {
+    format!("<t:{}:R>", seconds)
+}
+
+// Gets the offset in seconds of a time zone like "UTC", "+02:00" or "UTC-5".
+fn zone_offset(zone: &str) -> Option<i64> {
+    let upper = zone.to_uppercase();
+    let offset = upper.trim_start_matches("UTC").trim_start_matches("GMT");
+    if offset.is_empty() || offset == "Z" {
+        return Some(0);
+    }
+    let sign = match offset.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits = offset[1..].replace(':', "");
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.parse::<i64>().ok()?, 0),
+        3 | 4 => (digits[..digits.len() - 2].parse::<i64>().ok()?, digits[digits.len() - 2..].parse::<i64>().ok()?),
+        _ => return None,
+    };
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+// Gets the number of days in a month.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Gets the number of days between the Unix epoch and a date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    //Counting years from March so the leap day is at the end of the year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// Starts a task that pings both teams of a scheduled game before it starts and posts a notice when it is overdue.
+// The task stops once the tournament is over and the bracket has been dropped.
+// With roles, teams are pinged by the server role that has their name, like the roles made by `!team -role`.
+pub fn spawn(ctx: Context, guild_id: GuildId, channel_id: ChannelId, bracket: Weak<Bracket>, roles: bool) {
+    tokio::spawn(async move {
+        //Games are kept with their start so they are reminded again if they are moved
+        let mut reminded: HashSet<(String, u64)> = HashSet::new();
+        let mut overdue: HashSet<(String, u64)> = HashSet::new();
+        loop {
+            tokio::time::delay_for(CHECK_INTERVAL).await;
+            let games = match bracket.upgrade() {
+                Some(bracket) => bracket.scheduled(),
+                None => break,
+            };
+            let config = config::get_from(&ctx.data, guild_id).await;
+            let now = storage::now();
+            for (id, top, bottom, start) in games {
+                if top.is_empty() || bottom.is_empty() {
+                    continue;
+                }
+                let text = if now >= start + config.overdue {
+                    if !overdue.insert((id.clone(), start)) {
+                        continue;
+                    }
+                    format!("{} was scheduled for {} and has no result yet. Organizers, please declare the winner or use `{}` to move it.",
+                        game_name(&id), timestamp(start), config.command("schedule"))
+                } else if now + config.reminder >= start {
+                    if !reminded.insert((id.clone(), start)) {
+                        continue;
+                    }
+                    format!("{} and {}, {} starts {}.", mention(&ctx, guild_id, &top, roles).await,
+                        mention(&ctx, guild_id, &bottom, roles).await, game_name(&id), relative(start))
+                } else {
+                    continue;
+                };
+                if let Err(why) = channel_id.say(&ctx.http, text).await {
+                    println!("Error sending a game reminder: {:?}", why);
+                }
+            }
+        }
+    });
+}
+
+// Makes the text that pings a team, using the role with its name if roles is true and there is one.
+async fn mention(ctx: &Context, guild_id: GuildId, team: &str, roles: bool) -> String {
+    if roles {
+        if let Some(guild) = guild_id.to_guild_cached(&ctx.cache).await {
+            if let Some(role) = guild.role_by_name(team) {
+                return role.mention().to_string();
+            }
+        }
+    }
+    team.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leap_days_are_only_in_leap_years() {
+        assert_eq!(parse_time(&["2024-02-29", "00:00"]), Some(1709164800));
+        assert_eq!(parse_time(&["2024-03-01", "00:00"]), Some(1709164800 + 86400));
+        assert_eq!(parse_time(&["2000-02-29", "12:00"]), Some(951825600));
+        assert!(parse_time(&["2026-02-29", "00:00"]).is_none());
+        assert!(parse_time(&["2100-02-29", "00:00"]).is_none());
+    }
+
+    #[test]
+    fn times_need_a_valid_date_and_time() {
+        assert!(parse_time(&["2026-13-01", "19:00"]).is_none());
+        assert!(parse_time(&["2026-04-31", "19:00"]).is_none());
+        assert!(parse_time(&["2026-10-20", "24:00"]).is_none());
+        assert!(parse_time(&["2026-10-20", "19:60"]).is_none());
+        assert!(parse_time(&["2026-10-20"]).is_none());
+        assert!(parse_time(&["1970-01-01", "00:30", "+01:00"]).is_none());
+    }
+
+    #[test]
+    fn offsets_east_of_utc_are_earlier() {
+        let utc = parse_time(&["2026-10-20", "19:00"]).expect("Failed to read the time.");
+        assert_eq!(parse_time(&["2026-10-20", "19:00", "UTC"]), Some(utc));
+        assert_eq!(parse_time(&["2026-10-20", "19:00", "gmt"]), Some(utc));
+        assert_eq!(parse_time(&["2026-10-20", "19:00", "UTC+2"]), Some(utc - 2 * 3600));
+        assert_eq!(parse_time(&["2026-10-20", "19:00", "+02:00"]), Some(utc - 2 * 3600));
+        assert_eq!(parse_time(&["2026-10-20", "19:00", "-0500"]), Some(utc + 5 * 3600));
+        assert_eq!(parse_time(&["2026-10-20", "19:00", "UTC-5:30"]), Some(utc + 5 * 3600 + 30 * 60));
+    }
+
+    #[test]
+    fn zones_need_a_sign_and_a_real_offset() {
+        assert_eq!(zone_offset(""), Some(0));
+        assert_eq!(zone_offset("Z"), Some(0));
+        assert_eq!(zone_offset("+0530"), Some(5 * 3600 + 30 * 60));
+        assert_eq!(zone_offset("utc-3"), Some(-3 * 3600));
+        assert!(zone_offset("0200").is_none());
+        assert!(zone_offset("+15").is_none());
+        assert!(zone_offset("+02:60").is_none());
+        assert!(zone_offset("EST").is_none());
+    }
+
+    #[test]
+    fn durations_need_a_unit_after_each_number() {
+        assert_eq!(parse_duration("90"), Some(90 * 60));
+        assert_eq!(parse_duration("90m"), Some(90 * 60));
+        assert_eq!(parse_duration("1h30m"), Some(90 * 60));
+        assert_eq!(parse_duration("2d"), Some(2 * 86400));
+        //A trailing number could be minutes or seconds, so it is refused
+        assert!(parse_duration("1h30").is_none());
+        assert!(parse_duration("0").is_none());
+        assert!(parse_duration("h").is_none());
+        assert!(parse_duration("1w").is_none());
+    }
+
+    #[test]
+    fn requests_set_clear_or_space_out_games() {
+        let time = parse_time(&["2026-10-20", "19:00"]).expect("Failed to read the time.");
+        assert!(matches!(parse_request("2-1 none"), Some(ScheduleRequest::Game(game, None)) if game == "2-1"));
+        assert!(matches!(parse_request("2-1 clear"), Some(ScheduleRequest::Game(game, None)) if game == "2-1"));
+        assert!(matches!(parse_request("2-1 2026-10-20 19:00 UTC"), Some(ScheduleRequest::Game(game, Some(start))) if game == "2-1" && start == time));
+        assert!(matches!(parse_request("rounds 2026-10-20 19:00 1h"), Some(ScheduleRequest::Rounds(start, 3600)) if start == time));
+        assert!(parse_request("rounds 2026-10-20 19:00").is_none());
+        assert!(parse_request("2-1").is_none());
+        assert!(parse_request("2-1 tomorrow").is_none());
+    }
+}
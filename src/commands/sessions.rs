@@ -0,0 +1,184 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use serenity::{client::Context, framework::standard::{CommandResult, macros::command}, model::{channel::Message, id::{ChannelId, GuildId, UserId}, misc::Mentionable}, prelude::{RwLock, TypeMap, TypeMapKey}};
+
+use super::storage;
+
+// A running session like `!group` or `!tournament`.
+#[derive(Clone)]
+pub struct SessionInfo {
+    pub id: u32,
+    // The command that started the session.
+    pub kind: String,
+    pub guild_id: GuildId,
+    pub channel_id: ChannelId,
+    pub author: UserId,
+    // When the session started in seconds since the Unix epoch.
+    pub started: u64,
+}
+
+// Every running session in every guild.
+#[derive(Default)]
+pub struct Registry {
+    next_id: u32,
+    sessions: Vec<SessionInfo>,
+}
+
+impl Registry {
+    // Gets an id for a new session in a guild.
+    // The last id used in each guild is saved, so ids keep going up after a restart and saved results never share one.
+    fn new_id(&mut self, guild_id: GuildId) -> u32 {
+        let saved: u32 = storage::load(guild_id, "last_session");
+        self.next_id = self.next_id.max(saved) + 1;
+        if let Err(why) = storage::save(guild_id, "last_session", &self.next_id) {
+            println!("Error saving the last session id: {:?}", why);
+        }
+        self.next_id
+    }
+
+    // Gets the sessions running in a channel.
+    pub fn in_channel(&self, channel_id: ChannelId) -> Vec<SessionInfo> {
+        self.sessions.iter().filter(|session| session.channel_id == channel_id).cloned().collect()
+    }
+
+    // Works out which session a message in a channel is for, and its text without the session id.
+    // Messages starting with "[id]:" are for that session, and other messages are for the only session in the channel.
+    // When several sessions are running in a channel, messages without an id are not for any of them.
+    fn route(&self, channel_id: ChannelId, content: &str) -> Option<(u32, String)> {
+        let running = self.in_channel(channel_id);
+        if let Some((id, rest)) = content.split_once(':') {
+            if let Ok(id) = id.trim().parse::<u32>() {
+                if running.iter().any(|session| session.id == id) {
+                    return Some((id, rest.trim().to_owned()));
+                }
+            }
+        }
+        match running.as_slice() {
+            [session] => Some((session.id, content.to_owned())),
+            _ => None,
+        }
+    }
+}
+
+// Key for the session registry, kept in the client's data.
+pub struct ActiveSessions;
+
+impl TypeMapKey for ActiveSessions {
+    type Value = Arc<Mutex<Registry>>;
+}
+
+// Takes a session out of the registry when the last copy of its Session is dropped, even if the command stopped early.
+struct Entry {
+    id: u32,
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        if let Ok(mut registry) = self.registry.lock() {
+            registry.sessions.retain(|session| session.id != self.id);
+        }
+    }
+}
+
+// A handle to a running session that can wait for the messages meant for it.
+#[derive(Clone)]
+pub struct Session {
+    pub id: u32,
+    pub channel_id: ChannelId,
+    entry: Arc<Entry>,
+}
+
+impl Session {
+    // Adds a session started by msg to the registry.
+    // If other sessions are already running in the channel, tells the user how to send messages to this one.
+    pub async fn start(ctx: &Context, msg: &Message, kind: &str) -> Self {
+        let registry = registry(ctx).await;
+        let (id, others) = {
+            let mut guard = registry.lock().expect("There was an unknown error.");
+            let id = guard.new_id(msg.guild_id.unwrap_or_default());
+            let others = guard.in_channel(msg.channel_id).len();
+            guard.sessions.push(SessionInfo {
+                id,
+                kind: kind.to_owned(),
+                guild_id: msg.guild_id.unwrap_or_default(),
+                channel_id: msg.channel_id,
+                author: msg.author.id,
+                started: storage::now(),
+            });
+            (id, others)
+        };
+        if others > 0 {
+            let _ = msg.channel_id.say(&ctx.http, format!("This is session {}. Other sessions are running in this channel, \
+                so start messages for this one with `{}:`, like `{}: Sam, Alex`.", id, id, id)).await;
+        }
+        Session { id, channel_id: msg.channel_id, entry: Arc::new(Entry { id, registry }) }
+    }
+
+    // Waits up to timeout for the next message meant for this session that passes filter.
+    // filter gets the message and its text without the session id, and the message returned has the id taken off too.
+    pub async fn await_reply<F>(&self, ctx: &Context, timeout: Duration, filter: F) -> Option<Arc<Message>>
+    where F: Fn(&Message, &str) -> bool + Send + Sync + 'static {
+        let (id, channel_id, registry) = (self.id, self.channel_id, Arc::clone(&self.entry.registry));
+        let route = move |message: &Message| {
+            registry.lock().expect("There was an unknown error.")
+                .route(channel_id, &message.content)
+                .filter(|(session, _)| *session == id)
+                .map(|(_, content)| content)
+        };
+        let matches = route.clone();
+        let reply = self.channel_id.await_reply(&ctx)
+            .filter(move |message| matches(message).is_some_and(|content| filter(message, &content)))
+            .timeout(timeout)
+            .await?;
+
+        let mut message = (*reply).clone();
+        message.content = route(&reply).unwrap_or_else(|| reply.content.clone());
+        Some(Arc::new(message))
+    }
+}
+
+// Gets the session registry from the client's data.
+pub async fn registry(ctx: &Context) -> Arc<Mutex<Registry>> {
+    registry_from(&ctx.data).await
+}
+
+// Gets the session registry when there is no Context, like for slash commands.
+async fn registry_from(data: &RwLock<TypeMap>) -> Arc<Mutex<Registry>> {
+    Arc::clone(data.read().await.get::<ActiveSessions>().expect("The session registry was not set up."))
+}
+
+// Gets an id for something that is saved with the results of sessions but doesn't wait for messages, like a `/tournament`.
+pub async fn reserve_id(data: &RwLock<TypeMap>, guild_id: GuildId) -> u32 {
+    registry_from(data).await.lock().expect("There was an unknown error.").new_id(guild_id)
+}
+
+
+
+
+#[command]
+#[description = "Lists the groupings, drafts and tournaments running in this server.\n\
+    \n\
+    When more than one is running in a channel, start messages for one with its id, like `3: Sam, Alex` or `3: !stop`.\n\
+    \n\
+    **Sample usage:** `!sessions`"]
+async fn sessions(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.expect("Failed to get guild_id from msg.");
+    let running: Vec<SessionInfo> = registry(ctx).await.lock().expect("There was an unknown error.")
+        .sessions.iter()
+        .filter(|session| session.guild_id == guild_id)
+        .cloned()
+        .collect();
+
+    if running.is_empty() {
+        msg.channel_id.say(&ctx.http, "There are no sessions running in this server.").await?;
+        return Ok(());
+    }
+    let mut out = String::from("**Sessions**\n");
+    for session in running.iter() {
+        out.push_str(&format!("`{}` !{} in {} by {}, started {} minutes ago\n", session.id, session.kind,
+            session.channel_id.mention(), session.author.mention(), storage::now().saturating_sub(session.started) / 60));
+    }
+    msg.channel_id.say(&ctx.http, out).await?;
+    Ok(())
+}
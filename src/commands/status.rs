@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use serenity::{client::Context, framework::standard::{Args, CommandError, CommandResult, macros::command}, model::channel::Message};
+
+use super::{bracket::{Bracket, TeamGame, THIRD_PLACE, game_name, place_name}, manager::Manager, results::{self, ExportFormat, SessionResult}, schedule, tournament::RunningTournaments};
+
+// Gets the bracket of the tournament running in the message's channel, telling the user if there is none.
+// When several tournaments are running in the channel, the first argument must be the session id of one.
+// Returns the session id and the bracket.
+async fn running(ctx: &Context, msg: &Message, args: &mut Args) -> Result<(u32, Arc<Bracket>), CommandError> {
+    let running: Vec<(u32, Arc<Bracket>)> = ctx.data.read().await
+        .get::<RunningTournaments>()
+        .map(|tournaments| tournaments.iter()
+            .filter(|(_, (channel_id, _))| *channel_id == msg.channel_id)
+            .map(|(id, (_, bracket))| (*id, Arc::clone(bracket)))
+            .collect())
+        .unwrap_or_default();
+
+    if let Ok(id) = args.parse::<u32>() {
+        if let Some((_, bracket)) = running.iter().find(|(session, _)| *session == id) {
+            args.advance();
+            return Ok((id, Arc::clone(bracket)));
+        }
+    }
+    match running.as_slice() {
+        [(id, bracket)] => Ok((*id, Arc::clone(bracket))),
+        [] => {
+            msg.channel_id.say(&ctx.http, "There is no tournament running in this channel.").await?;
+            Err(CommandError::from("No tournament running."))
+        }
+        _ => {
+            let ids: Vec<String> = running.iter().map(|(id, _)| id.to_string()).collect();
+            msg.channel_id.say(&ctx.http, format!("Several tournaments are running in this channel. \
+                Put the session id of one first, like `{} {}`. The sessions are {}.", msg.content.split_whitespace().next().unwrap_or_default(), ids[0], ids.join(", "))).await?;
+            Err(CommandError::from("Several tournaments running."))
+        }
+    }
+}
+
+// Gets the text of where a team finished in a heat, like "2nd", or that they finished behind the teams that were ranked.
+fn finish_text(game: &TeamGame) -> String {
+    match game.place {
+        Some(place) => place_name(place + 1, place + 1),
+        None => "behind the ranked teams".to_owned(),
+    }
+}
+
+// Gets the team named in the arguments, telling the user if it is missing or not in the bracket.
+async fn team_arg(ctx: &Context, msg: &Message, args: &Args, bracket: &Bracket) -> Result<String, CommandError> {
+    let team = args.rest().trim().to_owned();
+    if team.is_empty() || bracket.path(&team).is_empty() {
+        msg.channel_id.say(&ctx.http, "Please enter the name of a team in the tournament.").await?;
+        return Err(CommandError::from("Unknown team."));
+    }
+    Ok(team)
+}
+
+
+
+
+#[command("bracket")]
+#[description = "Posts the bracket of the tournament running in this channel again.\n\
+    If more than one tournament is running here, put its session id first, like `!bracket 3`.\n\
+    Add `file` to attach a bracket file that other bracket tools or `!tournament` can import.\n\
+    \n\
+    **Sample usage:** `!bracket`"]
+async fn show_bracket(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (session, bracket) = running(ctx, msg, &mut args).await?;
+    let guild_id = msg.guild_id.expect("Failed to get guild_id from msg.");
+    if args.rest().trim() == "file" {
+        let result = SessionResult::from_bracket(session, msg.channel_id, &bracket.teams, &bracket);
+        results::send(ctx, msg.channel_id, &result, ExportFormat::Bracket).await?;
+    } else {
+        Manager::new(ctx, guild_id, msg.channel_id).say_output(&bracket.text()).await?;
+    }
+    Ok(())
+}
+
+#[command]
+#[description = "Shows the next game of a team in the tournament running in this channel and who they play.\n\
+    \n\
+    **Sample usage:** `!next team1`"]
+async fn next(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (_, bracket) = running(ctx, msg, &mut args).await?;
+    let team = team_arg(ctx, msg, &args, &bracket).await?;
+
+    //The last game a team is in is the one they are playing or the one they went out in
+    let path = bracket.path(&team);
+    let game = path.last().expect("Failed to get the team's games.");
+    let id = &game.id;
+    let when = bracket.start_of(id).map(|start| format!(", scheduled for {}", schedule::timestamp(start))).unwrap_or_default();
+    let out = if bracket.is_withdrawn(&team) {
+        format!("{} withdrew from the tournament.", team)
+    } else if game.winner.is_empty() && game.opponents.is_empty() {
+        format!("{} is waiting for an opponent in {}{}.", team, game_name(id), when)
+    } else if game.winner.is_empty() {
+        format!("{} plays {} in {}{}.", team, game.opponent_text(), game_name(id), when)
+    } else if game.is_heat() && game.place != Some(0) {
+        format!("{} finished {} in {}.", team, finish_text(game), game_name(id))
+    } else if game.place != Some(0) && bracket.is_forfeit(id) {
+        format!("{} forfeited {} to {}.", team, game_name(id), game.winner)
+    } else if game.place != Some(0) {
+        format!("{} was knocked out by {} in {}.", team, game.winner, game_name(id))
+    } else if id == THIRD_PLACE {
+        format!("{} took third place!", team)
+    } else {
+        format!("{} won the tournament!", team)
+    };
+    msg.channel_id.say(&ctx.http, out).await?;
+    Ok(())
+}
+
+#[command]
+#[description = "Lists the games in the tournament running in this channel that are ready to be played.\n\
+    \n\
+    **Sample usage:** `!pending`"]
+async fn pending(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (_, bracket) = running(ctx, msg, &mut args).await?;
+
+    //Heats are listed with everyone in them
+    let ready = bracket.ready_heats();
+    if ready.is_empty() {
+        msg.channel_id.say(&ctx.http, "No games are ready to be played.").await?;
+        return Ok(());
+    }
+    let mut out = String::from("Games ready to be played:\n");
+    for (id, slots) in ready.iter() {
+        let when = bracket.start_of(id).map(|start| format!(" at {}", schedule::timestamp(start))).unwrap_or_default();
+        let teams: Vec<&String> = slots.iter().filter(|team| !team.is_empty()).collect();
+        match teams.as_slice() {
+            [team] => out.push_str(&format!("{}: {} (no opponent){}\n", game_name(id), team, when)),
+            _ if slots.len() > 2 => out.push_str(&format!("{}: {}{}\n", game_name(id), teams.iter().map(|team| team.as_str()).collect::<Vec<&str>>().join(", "), when)),
+            _ => out.push_str(&format!("{}: {}{}\n", game_name(id), teams.iter().map(|team| team.as_str()).collect::<Vec<&str>>().join(" vs "), when)),
+        }
+    }
+    msg.channel_id.say(&ctx.http, out).await?;
+    Ok(())
+}
+
+#[command("history")]
+#[description = "Shows every game a team has played in the tournament running in this channel.\n\
+    \n\
+    **Sample usage:** `!history team1`"]
+async fn team_history(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (_, bracket) = running(ctx, msg, &mut args).await?;
+    let team = team_arg(ctx, msg, &args, &bracket).await?;
+
+    let mut out = format!("{}'s games:\n", team);
+    if bracket.is_withdrawn(&team) {
+        out = format!("{}'s games (withdrawn):\n", team);
+    }
+    for game in bracket.path(&team).iter() {
+        let opponent = game.opponent_text();
+        let result = if game.winner.is_empty() && game.opponents.is_empty() {
+            "waiting for an opponent".to_owned()
+        } else if game.winner.is_empty() {
+            format!("plays {}", opponent)
+        } else if game.is_heat() && game.advanced {
+            format!("finished {} and moved on", finish_text(game))
+        } else if game.is_heat() {
+            format!("finished {}", finish_text(game))
+        } else if game.place != Some(0) && bracket.is_forfeit(&game.id) {
+            format!("forfeited to {}", opponent)
+        } else if game.place != Some(0) {
+            format!("lost to {}", opponent)
+        } else if opponent.is_empty() {
+            "moved on with no opponent".to_owned()
+        } else if bracket.is_forfeit(&game.id) {
+            format!("beat {} by forfeit", opponent)
+        } else {
+            format!("beat {}", opponent)
+        };
+        out.push_str(&format!("{}: {}\n", game_name(&game.id), result));
+    }
+    msg.channel_id.say(&ctx.http, out).await?;
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use std::{env, fs, io, path::PathBuf, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::{Serialize, de::DeserializeOwned};
+use serenity::model::id::GuildId;
+
+// Gets the folder that data for a guild is saved in.
+// Uses the DATA_DIR environment variable as the base folder if it is set, otherwise "data".
+fn guild_dir(guild_id: GuildId) -> PathBuf {
+    let base = env::var("DATA_DIR").unwrap_or_else(|_| "data".to_owned());
+    PathBuf::from(base).join(guild_id.to_string())
+}
+
+// Loads the value saved as 'name' for a guild.
+// Returns the default value if nothing has been saved yet or the saved file can't be read.
+pub fn load<T: DeserializeOwned + Default>(guild_id: GuildId, name: &str) -> T {
+    let path = guild_dir(guild_id).join(format!("{}.json", name));
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_default(),
+        Err(_) => T::default(),
+    }
+}
+
+// Saves a value as 'name' for a guild, replacing anything that was saved before.
+pub fn save<T: Serialize>(guild_id: GuildId, name: &str, value: &T) -> io::Result<()> {
+    let dir = guild_dir(guild_id);
+    fs::create_dir_all(&dir)?;
+    let text = serde_json::to_string_pretty(value)?;
+    fs::write(dir.join(format!("{}.json", name)), text)
+}
+
+// Gets the current time in seconds since the Unix epoch, used for the times in saved data.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0)
+}
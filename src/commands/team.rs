@@ -1,10 +1,10 @@
-use std::time::Duration;
+use std::{collections::HashMap, sync::Arc};
 
 use linked_hash_map::LinkedHashMap;
 use rand::{prelude::SliceRandom, thread_rng};
-use serenity::{client::Context, framework::standard::{Args, CommandError, CommandResult, macros::command}, model::channel::Message, model::{Permissions, channel::{PermissionOverwrite, PermissionOverwriteType}}};
+use serenity::{client::Context, framework::standard::{Args, CommandError, CommandResult, macros::command}, model::channel::Message, model::{Permissions, id::ChannelId, channel::{PermissionOverwrite, PermissionOverwriteType}}};
 
-use super::manager::Manager;
+use super::{config::{self, GuildConfig}, organizers::{self, Organizers}, grouping::{self, Strategy}, manager::Manager, participants::{self, Preferences, Tags}, ratings::Ratings, results::{self, ExportFormat, SessionResult}};
 
 
 
@@ -12,14 +12,26 @@ use super::manager::Manager;
 #[command]
 #[description = "Makes named teams of people.\n\
     \n\
-    You must use `!stop` to stop adding people to teams.\n\
+    You must use `!stop` to stop adding people to teams. Only you can add people, and anyone with the organizer role can also stop.\n\
+    \n\
+    To balance the teams by tags add the `-stratify` argument. To only balance some tags, list them after an equals sign, like `-stratify=Mentor,EU`. \
+    Tags are the Discord roles of server members and words starting with # after an entered name, like `Sam #mentor #EU`.
+    \n\
+    To let people pick their teams add the `-prefer` argument. Anyone in the channel can then join with `!prefer [team] [team]...`, \
+    listing the teams from most to least wanted, and people are matched to the teams they want most while keeping the teams even.
+    \n\
+    To even out the teams by skill add the `-balance` argument. It uses the ratings from tournament games played in this server, or a rating column in an attached CSV file.
+    \n\
+    To send everyone a direct message with their team, teammates and channel add the `-dm` argument.
     \n\
     The following example adds everyone in the discord server to randomly assigned teams, makes a role for each team, and makes a channel only for that role.\n
     \n\
     **Sample usage:** `!team team1 team2 team3 team4 -random -all -channel -role`"]
-async fn team(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+async fn team(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 
     let guild_id = msg.guild_id.expect("Failed to get guild_id from msg");
+    let config = config::get(ctx, guild_id).await;
+    let mut args = config.with_default_flags("team", args);
     let manager = Manager::new(ctx, guild_id, msg.channel_id);
 
 
@@ -37,6 +49,12 @@ async fn team(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let mut random = false;
     let mut role = false;
     let mut channel = false;
+    let mut stratify = false;
+    let mut balance_tags: Vec<String> = Vec::new();
+    let mut prefer = false;
+    let mut export: Option<ExportFormat> = None;
+    let mut dm = false;
+    let mut balance = false;
 
     while !args.is_empty() {
         if let Ok(arg) = args.single::<String>(){
@@ -48,6 +66,27 @@ async fn team(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
                 role = true;
             } else if arg == "-channel" {
                 channel = true;
+            } else if arg == "-dm" {
+                dm = true;
+            } else if arg == "-balance" {
+                balance = true;
+            } else if arg == "-export" {
+                match args.single::<String>().ok().and_then(|name| ExportFormat::parse(&name)) {
+                    Some(format) => export = Some(format),
+                    None => {
+                        msg.channel_id.say(&ctx.http, "-export must be followed by csv or json.").await?;
+                        return Err(CommandError::from("Invalid arguments."));
+                    }
+                }
+            } else if arg == "-stratify" || arg.starts_with("-stratify=") {
+                stratify = true;
+                //The tags to balance can be given after an equals sign, like `-stratify=Mentor,EU`,
+                //so the word after the flag is still read as a name
+                if let Some(list) = arg.strip_prefix("-stratify=") {
+                    balance_tags = list.split(',').map(|tag| tag.trim().trim_start_matches('#').to_owned()).filter(|tag| !tag.is_empty()).collect();
+                }
+            } else if arg == "-prefer" {
+                prefer = true;
             } else if args.len() > 0 && &arg.as_str()[0..1] == "-" {
                 msg.channel_id.say(&ctx.http,format!("{} is not a valid argument.", arg)).await?;
             } else {
@@ -57,13 +96,20 @@ async fn team(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     }
 
 
+    if [stratify, prefer, balance].iter().filter(|&&used| used).count() > 1 {
+        msg.channel_id.say(&ctx.http, "Only one of -stratify, -prefer and -balance can be used at a time.").await?;
+        return Err(CommandError::from("Invalid arguments."));
+    }
+
     //Checking if the user is allowed to use the bot
-    if !member_permissions.manage_roles() && role {
+    //Organizers can make roles and channels without having the permissions themselves
+    let organizer = organizers::is_organizer(ctx, guild_id, &member).await;
+    if !member_permissions.manage_roles() && !organizer && role {
         msg.channel_id.say(&ctx.http,"You do not have sufficient permissions to make new roles.").await?;
         return Err(CommandError::from("Insufficient permissions for user."));
     }
 
-    if !member_permissions.manage_channels() && channel {
+    if !member_permissions.manage_channels() && !organizer && channel {
         msg.channel_id.say(&ctx.http,"You do not have sufficient permissions to make new channels.").await?;
         return Err(CommandError::from("Insufficient permissions for user."));
     }
@@ -80,6 +126,12 @@ async fn team(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 
     //Stores the people to get shuffled or not
     let mut people: Vec<String> = Vec::new();
+    //Stores the tags entered after people's names
+    let mut tags = Tags::new();
+    //Stores the ratings from attached CSV files, which are used over the server's ratings with -balance
+    let mut imported_ratings: HashMap<String, f64> = HashMap::new();
+    //Stores the teams people asked for with `!prefer`
+    let mut preferences = Preferences::new();
 
     if all {
         let members = guild_id.members(&ctx.http, None, None).await?;
@@ -91,38 +143,112 @@ async fn team(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     
     //Asking the user to input names
     msg.channel_id.say(&ctx.http, format!("{} is making {} teams.\n\
-        Please enter the names to put in the teams or `!stop` to stop.\n\
-        You may enter names one at a time or as a comma separated list.", msg.author, num_teams)).await?;
-    //Taking input with up to a 10 minute delay
-    let mut answer = msg.author.await_reply(&ctx).timeout(Duration::from_secs(600)).await;
+        Please enter the names to put in the teams or `{}` to stop.\n\
+        You may enter names one at a time or as a comma separated list.", msg.author, num_teams, config.command("stop"))).await?;
+    if prefer {
+        msg.channel_id.say(&ctx.http, format!("Anyone can join and pick their teams with `{} [team] [team]...`, most wanted first.", config.command("prefer"))).await?;
+    }
+    //Taking input with up to the server's timeout (10 minutes by default)
+    let organizers = Organizers::new(ctx, msg, "team").await;
+    let mut answer = next_entry(ctx, &config, &organizers, prefer).await;
 
     // Stops the loop and outputting the teams if the user does `!stop`
-    // or adds more team members from user inputs
+    // or adds more team members and preferences from user inputs
     while let Some(message) = answer {
-        if message.content.as_str() == "!stop" {
+        if message.content == config.command("stop") {
             answer = None;
         } else {
-            msg.channel_id.say(&ctx.http,"Adding them.").await?;
+            if message.content.starts_with(&config.command("prefer")) {
+                let wanted: Vec<String> = message.content.split_whitespace().skip(1).map(|s| s.to_owned()).collect();
+                let unknown: Vec<&String> = wanted.iter()
+                    .filter(|name| !teams.keys().any(|team| team.eq_ignore_ascii_case(name)))
+                    .collect();
+                if wanted.is_empty() || !unknown.is_empty() {
+                    msg.channel_id.say(&ctx.http, format!("{}, please list teams from {}.", message.author, teams.keys().cloned().collect::<Vec<String>>().join(", "))).await?;
+                } else {
+                    let person = message.author.to_string();
+                    if !people.contains(&person) {
+                        people.push(person.clone());
+                    }
+                    preferences.insert(person, wanted);
+                    msg.channel_id.say(&ctx.http, format!("Saved the preferences for {}.", message.author)).await?;
+                }
+            } else {
+                msg.channel_id.say(&ctx.http,"Adding them.").await?;
 
-            message.content.as_str().split(",").for_each(|s| {
-                people.push(String::from(s.trim()));
-            });
+                participants::parse_names(&message.content, &mut people, &mut tags);
+                //Attached CSV and text files are imported too, with the preferred teams that are in this session
+                let import = manager.read_attachments(&message).await?;
+                people.extend(import.people);
+                participants::merge_tags(&mut tags, import.tags);
+                imported_ratings.extend(import.ratings);
+                for (person, mut wanted) in import.preferences {
+                    wanted.retain(|name| teams.keys().any(|team| team.eq_ignore_ascii_case(name)));
+                    if prefer && !wanted.is_empty() {
+                        preferences.insert(person, wanted);
+                    }
+                }
+            }
 
-            answer = msg.author.await_reply(&ctx).timeout(Duration::from_secs(600)).await;
+            answer = next_entry(ctx, &config, &organizers, prefer).await;
         }
     }
 
-    msg.channel_id.say(&ctx.http,"Making teams (may happen automatically after 10 minutes)...").await?;
+    msg.channel_id.say(&ctx.http,"Making teams (may happen automatically after the timeout)...").await?;
     //Shuffles the order of the people before team creation.
     if random {
         people.shuffle(&mut thread_rng());
     }
 
-    manager.publish_teams(&people, &mut teams).await?;
+    //Server members are also tagged with their roles when stratifying
+    if stratify {
+        participants::merge_tags(&mut tags, manager.role_tags(&people).await?);
+        if !balance_tags.is_empty() {
+            participants::keep_tags(&mut tags, &balance_tags);
+        }
+        for tag in balance_tags.iter() {
+            let count = participants::count_tag(&tags, &people, tag);
+            if count < num_teams {
+                msg.channel_id.say(&ctx.http, format!("Only {} people have the tag {}, so not every team will get one.", count, tag)).await?;
+            }
+        }
+    }
+
+    //The server's ratings from past tournament games even out the teams
+    let ratings = if balance { Ratings::load(guild_id).balance_ratings(&people, imported_ratings) } else { HashMap::new() };
+    if balance && ratings.len() < people.len() {
+        msg.channel_id.say(&ctx.http, format!("{} people have no rating yet, so they count as the average rating.", people.len() - ratings.len())).await?;
+    }
+
+    let sizes = grouping::even_sizes(people.len(), teams.len());
+    let strategy = if stratify {
+        Strategy::Stratified(&tags)
+    } else if prefer {
+        Strategy::Preferences(&preferences)
+    } else if balance {
+        Strategy::Balanced(&ratings)
+    } else {
+        Strategy::RoundRobin
+    };
+    manager.publish_teams(&people, &mut teams, &sizes, &strategy).await?;
+
+    if prefer {
+        let (first, total) = participants::first_choices(&preferences, &teams);
+        msg.channel_id.say(&ctx.http, format!("{} of {} people got their first choice.", first, total)).await?;
+    }
+
+    //Saving the results so they can be exported now or later with `!export`
+    let result = SessionResult::from_teams(organizers.session.id, "team", msg.channel_id, &people, &teams);
+    results::save(guild_id, &result)?;
+    if let Some(format) = export {
+        results::send(ctx, msg.channel_id, &result, format).await?;
+    }
 
     //Adding roles and channels if the flag was included.
     //If both role and channel flags are included, channels are exclusive to the role.
     //Needs to be after manager.publish_teams because it fills the teams up.
+    //The channels made are kept so they can be sent to people with -dm.
+    let mut channels: HashMap<String, ChannelId> = HashMap::new();
     if role {
         for (name, team) in teams.iter() {
             let current_role = manager.add_role(name, team).await?;
@@ -140,14 +266,32 @@ async fn team(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
                     deny: Permissions::all(),
                     kind: PermissionOverwriteType::Role(msg.guild(&ctx.cache).await.expect("Failed to get guild.").role_by_name("@everyone").expect("Failed to get roll \"Everyone\"").id),
                 };
-                manager.add_channel(name, Some(vec![&member_permissions, &nonmember_permissions])).await?;
+                let created = manager.add_channel(name, Some(vec![&member_permissions, &nonmember_permissions])).await?;
+                channels.insert(name.clone(), created.id);
             }
         }
     } else if channel {
         for name in teams.iter() {
-            manager.add_channel(name.0, None).await?;
+            let created = manager.add_channel(name.0, None).await?;
+            channels.insert(name.0.clone(), created.id);
         }
     }
 
+    if dm {
+        manager.dm_teams(&teams, &channels).await?;
+    }
+
     Ok(())
 }
+
+// Waits up to the server's timeout for the next message from the organizers making the teams.
+// When preferences are being taken, `!prefer` messages from anyone in the channel are also returned.
+async fn next_entry(ctx: &Context, config: &GuildConfig, organizers: &Organizers, prefer: bool) -> Option<Arc<Message>> {
+    if prefer {
+        let session = organizers.clone();
+        let prefer_command = config.command("prefer");
+        organizers.session.await_reply(ctx, config.timeout(), move |message, content| session.accepts_input(message, content) || content.starts_with(&prefer_command)).await
+    } else {
+        organizers.await_reply(ctx, config.timeout()).await
+    }
+}
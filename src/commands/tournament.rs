@@ -1,14 +1,22 @@
-use std::{time::Duration, sync::{Arc, Mutex}};
+use std::{collections::HashMap, io, sync::Arc, time::Duration};
 
-use linked_hash_map::LinkedHashMap;
 use rand::{prelude::SliceRandom, thread_rng};
-use serenity::{client::Context, framework::standard::{Args, CommandError, CommandResult, macros::command}, model::channel::Message};
+use serde_json::json;
+use serenity::{client::Context, framework::standard::{Args, CommandError, CommandResult, macros::command}, model::{channel::Message, id::{ChannelId, GuildId, MessageId}, misc::Mentionable}, prelude::TypeMapKey};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
 
-use super::game::Game;
+use super::{bracket::{self, Bracket, DeclareError, Declared, game_name}, buttons::{self, Click}, checkin, config::{self, GuildConfig}, grouping::Strategy, heats, manager::Manager, organizers::Organizers, participants, pools, ratings::Ratings, reports::{Report, ReportError, Reports}, results::{self, ExportFormat, SessionResult}, schedule::{self, ScheduleRequest}, storage};
 
 
 
 
+// Key for the channels and brackets of the tournaments that are running, kept in the client's data by session id.
+pub struct RunningTournaments;
+
+impl TypeMapKey for RunningTournaments {
+    type Value = HashMap<u32, (ChannelId, Arc<Bracket>)>;
+}
+
 #[command]
 #[description = "Makes and runs a single elimination tournament bracket.\n\
     \n\
@@ -16,8 +24,42 @@ use super::game::Game;
     \n\
     **You will need to advance rounds with only one participant.**
     \n\
+    Participants can also be imported from a CSV or text file attached to the command. A CSV file with a \"rating\" column seeds the bracket by rating.
+    Add `-pools [number]` to start with a group stage, where every team plays everyone in its pool once. \
+    The top 2 of each pool, or as many as set with `-advance [number]`, move on to the bracket, where pool winners play teams from other pools first.
+    \n\
+    Add `-balance` to seed the bracket by the ratings from tournament games played in this server. Every game played updates those ratings, \
+    which anyone can see with `!rating` and `!leaderboard`.
+    A bracket file from `!export [session] bracket` or from another bracket tool can be attached instead to carry on with that bracket.
+    Add `-heats [size]` for free-for-all games like races and battle royales, where up to that many teams play each heat at once \
+    and the top half of each heat, or as many as set with `-advance [number]`, move on until the final heat. Heats are ranked with \
+    `!declare [round #]-[game #] [names in the order they finished]`. Forfeits, withdrawing, reported results, corrections, \
+    scheduling and co-organizers are only for games between two teams, so they can't be used with heats.
+    Add `-third` to have the losers of the semifinals play for third place, as game `3P`. \
+    When the tournament is over every team's place is posted and saved with the results, like 1st, 2nd, 3rd, 4th and 5th-8th.
+    \n\
+    Each game that is ready is posted with a button for each team to click the winner.\n\
+    Add `-checkin [time]` to give participants that long (10 minutes by default) to check in by reacting or with `!checkin`. \
+    Only the ones that check in are put in the bracket, and organizers can `!start` early. Participants can `!drop` out later, which forfeits their games.\n\
+    Organizers can use `!forfeit [round #]-[game #] [team]` to give a game to the other team or `!withdraw [team]` to take a team out, \
+    which forfeits the games they have left.\n\
+    Teams can report their own results with `!report [round #]-[game #] [win/loss]`. The other team confirms with `!accept [round #]-[game #]` \
+    or disputes with `!dispute [round #]-[game #] [reason]`, and reports nobody disputes count after a while. Organizers see disputes with `!disputes`.\n\
+    Add `-dm` to send every participant a direct message with their first game and opponent.\n\
+    Games can be scheduled with `!schedule [round #]-[game #] [date] [time] [time zone]`, like `!schedule 2-1 2026-10-20 19:00 UTC`, \
+    or every round at once with `!schedule rounds [date] [time] [time zone] [time between rounds]`. \
+    Both teams are pinged before their game, by the role with their name if `-role` is used, and organizers are told when a game is overdue.\n\
+    Anyone can use `!bracket`, `!next [team]`, `!pending` and `!history [team]` to see how the tournament is going.\n\
+    Use `!undo` to take back the last result or `!correct [round #]-[game #] [winner's name]` to change one. \
+    If games after it were already played their results are taken back too, after you `!confirm`.\n\
+    You, anyone with the organizer role, and co-organizers added with `!coorganizer @user` can declare results or stop the tournament.
+    \n\
     **Sample usage:** `!tournament team1 team2 team3 team4`"]
-async fn tournament(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+async fn tournament(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+
+    let guild_id = msg.guild_id.expect("Failed to get guild_id from msg.");
+    let config = config::get(ctx, guild_id).await;
+    let mut args = config.with_default_flags("tournament", args);
 
     let mut teams: Vec<String> = vec![];
 
@@ -26,6 +68,15 @@ async fn tournament(ctx: &Context, msg: &Message, mut args: Args) -> CommandResu
     //random shuffles the groups
     let mut random = false;
     let mut all = false;
+    let mut export: Option<ExportFormat> = None;
+    let mut dm = false;
+    let mut role = false;
+    let mut checkin: Option<Duration> = None;
+    let mut balance = false;
+    let mut pools: Option<usize> = None;
+    let mut advance: Option<usize> = None;
+    let mut third_place = false;
+    let mut heat_size: Option<usize> = None;
 
     while !args.is_empty() {
         if let Ok(arg) = args.single::<String>(){
@@ -33,6 +84,44 @@ async fn tournament(ctx: &Context, msg: &Message, mut args: Args) -> CommandResu
                 random = true;
             } else if arg == "-all" {
                 all = true;
+            } else if arg == "-dm" {
+                dm = true;
+            } else if arg == "-role" {
+                role = true;
+            } else if arg == "-balance" {
+                balance = true;
+            } else if arg == "-third" {
+                third_place = true;
+            } else if arg == "-pools" || arg == "-advance" || arg == "-heats" {
+                match args.single::<usize>() {
+                    Ok(number) if arg == "-pools" && (2..=26).contains(&number) => pools = Some(number),
+                    Ok(number) if arg == "-advance" && number > 0 => advance = Some(number),
+                    Ok(number) if arg == "-heats" && number > 2 => heat_size = Some(number),
+                    _ => {
+                        let range = match arg.as_str() {
+                            "-pools" => " between 2 and 26",
+                            "-heats" => " above 2",
+                            _ => "",
+                        };
+                        msg.channel_id.say(&ctx.http, format!("{} must be followed by a number{}.", arg, range)).await?;
+                        return Err(CommandError::from("Invalid arguments."));
+                    }
+                }
+            } else if arg == "-checkin" {
+                //How long check-in stays open can follow, like `-checkin 15m`
+                checkin = Some(checkin::DEFAULT_WINDOW);
+                if let Some(window) = args.current().and_then(schedule::parse_duration) {
+                    checkin = Some(Duration::from_secs(window));
+                    args.advance();
+                }
+            } else if arg == "-export" {
+                match args.single::<String>().ok().and_then(|name| ExportFormat::parse(&name)) {
+                    Some(format) => export = Some(format),
+                    None => {
+                        msg.channel_id.say(&ctx.http, "-export must be followed by csv or json.").await?;
+                        return Err(CommandError::from("Invalid arguments."));
+                    }
+                }
             } else if args.len() > 0 && &arg.as_str()[0..1] == "-" {
                 msg.channel_id.say(&ctx.http,format!("{} is not a valid argument.", arg)).await?;
             } else {
@@ -42,200 +131,704 @@ async fn tournament(ctx: &Context, msg: &Message, mut args: Args) -> CommandResu
     }
 
     if all {
-        let members = msg.guild_id.unwrap().members(&ctx.http, None, None).await?;
+        let members = guild_id.members(&ctx.http, None, None).await?;
         msg.channel_id.say(&ctx.http,"-\nAdding all channel members to the tournament\n-").await?;
         for member in members.iter() {
             teams.push(member.user.to_string());
         }
     }
 
+    //Participants can also be imported from CSV and text files attached to the command
+    let manager = Manager::new(ctx, guild_id, msg.channel_id);
+    let import = manager.read_attachments(msg).await?;
+    teams.extend(import.people);
+
+    //A bracket file carries on a bracket from another tool or an earlier export, with its teams and results
+    let imported = match manager.read_bracket(msg).await? {
+        Some(Ok(bracket)) if teams.is_empty() => Some(bracket),
+        Some(Ok(_)) => {
+            msg.channel_id.say(&ctx.http, "Please either attach a bracket file or enter teams, not both.").await?;
+            return Err(CommandError::from("Invalid arguments."));
+        }
+        Some(Err(error)) => {
+            msg.channel_id.say(&ctx.http, error.to_string()).await?;
+            return Err(CommandError::from("Invalid bracket file."));
+        }
+        None => None,
+    };
+    if let Some(bracket) = imported.as_ref() {
+        teams = bracket.teams.clone();
+    }
+
 
     if teams.len() == 0 {
         msg.channel_id.say(&ctx.http, "Please enter at least 1 valid team name.").await?;
         return Err(CommandError::from("Not enough teams."));
     }
 
+    if pools.is_some() && imported.is_some() {
+        msg.channel_id.say(&ctx.http, "-pools can't be used with a bracket file.").await?;
+        return Err(CommandError::from("Invalid arguments."));
+    }
+    if let Some(size) = heat_size {
+        if pools.is_some() || third_place || dm || imported.is_some() {
+            msg.channel_id.say(&ctx.http, "-heats can't be used with -pools, -third, -dm or a bracket file.").await?;
+            return Err(CommandError::from("Invalid arguments."));
+        }
+        //Every round needs fewer heats than the one before it
+        if advance.map(|advance| advance * 2 > size).unwrap_or(false) {
+            msg.channel_id.say(&ctx.http, format!("At most half of each heat can move on, so -advance can be at most {} with heats of {}.", size / 2, size)).await?;
+            return Err(CommandError::from("Invalid arguments."));
+        }
+    }
+    //Half of each heat moves on unless -advance says otherwise, and pools send their top 2
+    let advance = advance.unwrap_or_else(|| heat_size.map(|size| size / 2).unwrap_or(2));
+
+    //Only the participants that check in are put in the bracket
+    let mut organizers = Organizers::new(ctx, msg, "tournament").await;
+    if let Some(window) = checkin {
+        if imported.is_some() {
+            msg.channel_id.say(&ctx.http, "-checkin can't be used with a bracket file.").await?;
+            return Err(CommandError::from("Invalid arguments."));
+        }
+        teams = checkin::run(ctx, msg.channel_id, &config, &organizers, &manager, &teams, window).await?;
+        if teams.is_empty() {
+            msg.channel_id.say(&ctx.http, "Nobody checked in, so the tournament has been ended.").await?;
+            return Ok(());
+        }
+    }
+
     let num_teams = teams.len();
     msg.channel_id.say(&ctx.http,format!("Making a tournament with {} participants.\n", num_teams)).await?;
 
+    //Imported ratings and the server's ratings with -balance seed the bracket or even out the pools
+    let ratings = if balance { Ratings::load(guild_id).balance_ratings(&teams, import.ratings) } else { import.ratings };
+
     //Shuffles the order of the people before team creation.
-    if random {
+    //A bracket file already decided where everyone plays
+    if random && imported.is_none() {
         teams.shuffle(&mut thread_rng());
+    } else if imported.is_none() && pools.is_none() && !ratings.is_empty() {
+        //Ratings seed the bracket so the highest rated teams meet as late as possible
+        teams = bracket::seed_order(&teams, &ratings);
     }
 
-    let mut tournament: LinkedHashMap<String, Arc<Mutex<Game>>> = LinkedHashMap::new();
-    let mut all_games: LinkedHashMap<String, Arc<Mutex<Game>>> = LinkedHashMap::new();
-    let mut current_game = Arc::new(Mutex::new(Game::new("1-1".to_owned(), "".to_owned(), "".to_owned(), None)));
+    //A group stage decides who makes the bracket and where they are seeded
+    if let Some(num_pools) = pools {
+        if teams.len() < num_pools * 2 || teams.len() / num_pools < advance {
+            msg.channel_id.say(&ctx.http, format!("{} participants are not enough for {} pools with {} teams moving on from each.", teams.len(), num_pools, advance)).await?;
+            return Err(CommandError::from("Not enough teams."));
+        }
+        //Pools are dealt out in the order teams were entered unless there are ratings to even them out by
+        let strategy = if ratings.is_empty() { Strategy::RoundRobin } else { Strategy::Balanced(&ratings) };
+        teams = match pools::run(ctx, guild_id, msg.channel_id, &config, &organizers, &manager, &teams, num_pools, advance, &strategy).await? {
+            Some(qualifiers) => qualifiers,
+            None => return Ok(()),
+        };
+    }
 
-    for (index, team_name) in teams.iter().enumerate() {
+    //Free-for-all heats are ranked by the organizers instead of played out between two teams at a time
+    if let Some(size) = heat_size {
+        let bracket = Arc::new(Bracket::heats(&teams, size, advance));
+        if let Some(tournaments) = ctx.data.write().await.get_mut::<RunningTournaments>() {
+            tournaments.insert(organizers.session.id, (msg.channel_id, Arc::clone(&bracket)));
+        }
+        let played = heats::run(ctx, msg.channel_id, &config, &organizers, &manager, &bracket, advance).await;
+        if let Some(tournaments) = ctx.data.write().await.get_mut::<RunningTournaments>() {
+            tournaments.remove(&organizers.session.id);
+        }
+        played?;
+        return finish(ctx, guild_id, msg.channel_id, &config, &manager, &organizers, &teams, &bracket, export).await;
+    }
 
-        if index % 2 != 0 {
-            let mut guard = tournament[&format!("{}-{}", 1, index / 2 + 1)].lock().expect("There was an unknown error.");
-            guard.add_team(team_name.to_owned()).expect("Teams already filled.");
-        } else {
-            tournament.insert(format!("{}-{}", 1, index / 2 + 1), Arc::clone(&current_game));
-            all_games.insert(format!("{}-{}", 1, index / 2 + 1), Arc::clone(&current_game));
-            current_game = Arc::new(Mutex::new(Game::new(format!("{}-{}", 1, index / 2 + 1), "".to_owned(), "".to_owned(), None)));
-            let mut guard = tournament[&format!("{}-{}", 1, index / 2 + 1)].lock().expect("There was an unknown error.");
-            guard.add_team(team_name.to_owned()).expect("Teams already filled.");
-        }
-    }
-    
-    
-    fill_tournament(&mut tournament, 1, &mut all_games);
-    // trim_tournament(&mut tournament, &mut all_games);
-    
-    let mut out: String = String::new();
-    for key in tournament.keys() {
-        let mut nums = key.split("-");
-        let round = nums.next().expect("There was an unknown error.");
-        let game = nums.next().expect("There was an unknown error.");
-        let top_team: String;
-        let bottom_team: String;
-        {
-            let guard = tournament[key].lock().expect("There was an unknown error.");
-            top_team = guard.top_team.clone();
-            bottom_team = guard.bottom_team.clone();
-        }
-        out.push_str(format!("\nRound {} Game {}:\n\t{}\n\t{}\n", round, game, top_team, bottom_team).as_str());
-    }
-    msg.channel_id.say(&ctx.http, out).await?;
+    let mut bracket = imported.unwrap_or_else(|| Bracket::new(&teams));
+    if third_place && !bracket.add_third_place() && !bracket.games.contains_key(bracket::THIRD_PLACE) {
+        msg.channel_id.say(&ctx.http, "-third needs at least 4 participants in the bracket.").await?;
+        return Err(CommandError::from("Not enough teams."));
+    }
+    let bracket = Arc::new(bracket);
+    //Other commands like `!bracket` and `!next` read the bracket while the tournament runs
+    if let Some(tournaments) = ctx.data.write().await.get_mut::<RunningTournaments>() {
+        tournaments.insert(organizers.session.id, (msg.channel_id, Arc::clone(&bracket)));
+    }
+    let mut board = manager.say_output(&bracket.text()).await?;
+
+    //Clicks on the winner buttons are sent here until the tournament ends
+    let session = board.id.to_string();
+    let (sender, mut clicks) = mpsc::unbounded_channel();
+    buttons::register(&ctx.data, session.clone(), sender).await;
+    let mut game_messages: HashMap<String, MessageId> = HashMap::new();
+    post_ready_games(ctx, msg.channel_id, &session, &bracket, &mut game_messages).await?;
+    //Reminders for scheduled games are posted until the bracket is dropped at the end of the tournament
+    schedule::spawn(ctx.clone(), guild_id, msg.channel_id, Arc::downgrade(&bracket), role);
+    if dm {
+        manager.send_dms(&first_game_dms(ctx, guild_id, msg.channel_id, &bracket).await).await?;
+    }
 
 
     //Asking the user to input names
-    msg.channel_id.say(&ctx.http, "\nUse `!declare [round #]-[game #] [winner's name]` to set the result of a game.\n\
-    If you want to end the tournament use `!stop`.").await?;
-    //Taking input with up to a 100 minute delay
-    let mut answer = msg.author.await_reply(&ctx).timeout(Duration::from_secs(6000)).await;
-    let mut stop = false;
+    msg.channel_id.say(&ctx.http, format!("\nClick the winner of each game, or use `{} [round #]-[game #] [winner's name]` to set the result of a game.\n\
+    Use `{}` to take back the last result or `{} [round #]-[game #] [winner's name]` to change one.\n\
+    Use `{} [round #]-[game #] [date] [time] [time zone]` to schedule a game or `{} rounds [date] [time] [time zone] [time between rounds]` to schedule every round.\n\
+    Use `{} [round #]-[game #] [team]` when a team forfeits a game or `{} [team]` to take a team out of the tournament.\n\
+    Participants can drop out with `{}`, which forfeits their games.\n\
+    Teams can report their own results with `{} [round #]-[game #] [win/loss]`, which the other team confirms with `{}` or disputes with `{}`. \
+    Use `{}` to see the disputed results.\n\
+    If you want to end the tournament use `{}`.", config.command("declare"), config.command("undo"), config.command("correct"),
+    config.command("schedule"), config.command("schedule"), config.command("forfeit"), config.command("withdraw"), config.command("drop"),
+    config.command("report"), config.command("accept"), config.command("dispute"), config.command("disputes"), config.command("stop"))).await?;
+    //Taking input from the organizers with up to the server's tournament timeout (100 minutes by default)
+    let mut answer = next_input(ctx, &config, &organizers, &mut clicks, wait_time(&config, &bracket), None).await;
+    let mut pending_correction: Option<(String, String)> = None;
+    //Results reported by the teams themselves wait here for the other team to confirm them
+    let mut reports = Reports::default();
     // Stops the loop and outputting the teams if the user does `!stop`
     // or keeps updating tournament stats untill there is a winner.
-    while let Some(message) = answer {
-        let text = message.content.as_str();
-        if text == "!stop" {
-            msg.channel_id.say(&ctx.http,"Tournament has been ended.").await?;
-            answer = None;
-        } else if text.starts_with("!declare") {
-            let split: Vec<String> = text.split(" ").map(|s: &str| s.to_string()).collect();
-            if split.len() == 3 {
-                let id = split[1].clone();
-                let already_assigned: bool;
-                let has_team: bool;
-                let next_id: String;
-                {
-                    if all_games.contains_key(&id) {
-                        let mut game = all_games.get(&id).expect("Key not in all_games.").as_ref().lock().expect("There was an unknown error.");
-                        if game.winner == "" {
-                            match &game.next_game {
-                                Some(next_game) => {
-                                    let guard = next_game.as_ref().lock().expect("There was an unknown error.");
-                                    next_id = guard.id.clone();
-                                    if game.top_team == split[2] || game.bottom_team == split[2] {
-                                        has_team = true;
-                                    } else {
-                                        has_team = false;
-                                    }
-                                }
-                                None => {
-                                    stop = true;
-                                    has_team = true;
-                                    next_id = "won the tournament!".to_string();
-                                }
+    while let Some(input) = answer {
+        let mut stop = false;
+        match input {
+            Input::Tick => {
+                //Reports nobody disputed in time count as if they were confirmed
+                for report in reports.expired(storage::now()) {
+                    msg.channel_id.say(&ctx.http, format!("Nobody disputed {}'s report in time, so {} wins {}.", report.reporter, report.winner, game_name(&report.game))).await?;
+                    close_report(ctx, msg.channel_id, &report, "Confirmed automatically.").await?;
+                    stop = declare(ctx, msg.channel_id, &manager, &bracket, &mut board, &game_messages, &report.game, &report.winner).await? || stop;
+                }
+            }
+            Input::Click(click) if click.choice == "accept" || click.choice == "dispute" => {
+                let names = manager.names_of(click.user).await;
+                let request = ReportRequest { names, command: click.choice.clone(), game: click.target.clone(), rest: String::new() };
+                stop = team_report(ctx, &config, &manager, &bracket, &mut board, &game_messages, &mut reports, &teams, &organizers, &session, request).await?;
+            }
+            Input::Click(click) => {
+                if !organizers.accepts_user(click.user, &click.roles) {
+                    msg.channel_id.say(&ctx.http, format!("{}, only the organizers of this tournament can declare winners.", click.user.mention())).await?;
+                } else if let Some((_, top, bottom)) = bracket.ready_games().into_iter().find(|(id, _, _)| *id == click.target) {
+                    let winner = if click.choice == "top" { top } else { bottom };
+                    stop = declare(ctx, msg.channel_id, &manager, &bracket, &mut board, &game_messages, &click.target, &winner).await?;
+                    post_ready_games(ctx, msg.channel_id, &session, &bracket, &mut game_messages).await?;
+                }
+            }
+            Input::Message(message) if is_report_command(&config, &message.content) => {
+                let mut words = message.content.splitn(3, ' ');
+                let request = ReportRequest {
+                    names: manager.names_of(message.author.id).await,
+                    command: words.next().unwrap_or_default().trim_start_matches(config.prefix.as_str()).to_owned(),
+                    game: words.next().unwrap_or_default().to_owned(),
+                    rest: words.next().unwrap_or_default().trim().to_owned(),
+                };
+                stop = team_report(ctx, &config, &manager, &bracket, &mut board, &game_messages, &mut reports, &teams, &organizers, &session, request).await?;
+            }
+            Input::Message(message) if message.content == config.command("drop") => {
+                //A team that drops out forfeits the game they are in and any game they would have played
+                match participants::find_person(&teams, &manager.names_of(message.author.id).await) {
+                    Some(team) if bracket.withdraw(team) => {
+                        msg.channel_id.say(&ctx.http, format!("{} dropped out of the tournament.", team)).await?;
+                        manager.edit_output(&mut board, &bracket.text()).await?;
+                    }
+                    Some(team) => {
+                        msg.channel_id.say(&ctx.http, format!("{} already dropped out.", team)).await?;
+                    }
+                    None => {
+                        msg.channel_id.say(&ctx.http, format!("{}, you are not in this tournament.", message.author.id.mention())).await?;
+                    }
+                }
+            }
+            Input::Message(message) => {
+                let text = message.content.as_str();
+                //A correction that would take back games that were played waits for `!confirm`
+                let confirming = pending_correction.take();
+                if text == config.command("confirm") && confirming.is_some() {
+                    let (game, winner) = confirming.expect("Failed to get the correction.");
+                    correct(ctx, msg.channel_id, &manager, &bracket, &mut board, &mut game_messages, &game, &winner).await?;
+                    post_ready_games(ctx, msg.channel_id, &session, &bracket, &mut game_messages).await?;
+                } else if text == config.command("undo") {
+                    match bracket.undo() {
+                        Some((game, winner)) => {
+                            msg.channel_id.say(&ctx.http, format!("Took back {}'s win in {}.", winner, game_name(&game))).await?;
+                            manager.edit_output(&mut board, &bracket.text()).await?;
+                            remove_game_messages(ctx, msg.channel_id, &bracket, &mut game_messages, &[game]).await?;
+                            post_ready_games(ctx, msg.channel_id, &session, &bracket, &mut game_messages).await?;
+                        }
+                        None => {
+                            msg.channel_id.say(&ctx.http, "There are no results to take back.").await?;
+                        }
+                    }
+                } else if text.starts_with(&config.command("correct")) {
+                    let split: Vec<&str> = text.splitn(3, ' ').collect();
+                    if split.len() == 3 {
+                        //The winner is matched like `!withdraw` does, so the bracket gets the team's own name
+                        let typed = split[2].trim().to_owned();
+                        let winner = participants::find_person(&teams, std::slice::from_ref(&typed)).unwrap_or(&typed).clone();
+                        match bracket.played_after(split[1]) {
+                            Ok(played) if !played.is_empty() => {
+                                let names: Vec<String> = played.iter().map(|id| game_name(id)).collect();
+                                msg.channel_id.say(&ctx.http, format!("Correcting this will also take back the results of {}.\n\
+                                    Use `{}` to do it anyway.", names.join(", "), config.command("confirm"))).await?;
+                                pending_correction = Some((split[1].to_owned(), winner));
                             }
-                            already_assigned = false;
-                            if has_team && !already_assigned {
-                                game.winner(split[2].to_owned());
+                            Ok(_) => {
+                                correct(ctx, msg.channel_id, &manager, &bracket, &mut board, &mut game_messages, split[1], &winner).await?;
+                                post_ready_games(ctx, msg.channel_id, &session, &bracket, &mut game_messages).await?;
+                            }
+                            Err(error) => {
+                                msg.channel_id.say(&ctx.http, error.to_string()).await?;
                             }
-                        } else {
-                            has_team = false;
-                            already_assigned = true;
-                            next_id = "".to_string();
                         }
                     } else {
-                        already_assigned = false;
-                        next_id = "".to_owned();
-                        has_team = false;
+                        msg.channel_id.say(&ctx.http,format!("You should use the format `{} [round #]-[game #] [winner's name]`", config.command("correct"))).await?;
                     }
-                }
-                if already_assigned {
-                    msg.channel_id.say(&ctx.http,"A winner for this game has already been declared.").await?;
-                } else if has_team {
-                    if next_id == "won the tournament!" {
-                        msg.channel_id.say(&ctx.http,format!("{} {}", split[2], next_id)).await?;
+                } else if text.starts_with(&config.command("schedule")) {
+                    let request = text[config.command("schedule").len()..].trim();
+                    match schedule::parse_request(request) {
+                        Some(ScheduleRequest::Game(game, start)) => match bracket.schedule(&game, start) {
+                            Ok(()) => {
+                                let out = match start {
+                                    Some(start) => format!("{} is scheduled for {}.", game_name(&game), schedule::timestamp(start)),
+                                    None => format!("{} is no longer scheduled.", game_name(&game)),
+                                };
+                                msg.channel_id.say(&ctx.http, out).await?;
+                                manager.edit_output(&mut board, &bracket.text()).await?;
+                            }
+                            Err(error) => {
+                                msg.channel_id.say(&ctx.http, error.to_string()).await?;
+                            }
+                        },
+                        Some(ScheduleRequest::Rounds(start, spacing)) => {
+                            bracket.schedule_rounds(start, spacing);
+                            msg.channel_id.say(&ctx.http, format!("The games that have not been played are scheduled by round, starting at {}.", schedule::timestamp(start))).await?;
+                            manager.edit_output(&mut board, &bracket.text()).await?;
+                        }
+                        None => {
+                            msg.channel_id.say(&ctx.http, format!("You should use the format `{} [round #]-[game #] [yyyy-mm-dd] [hh:mm] [time zone]`, \
+                                `{} [round #]-[game #] none` or `{} rounds [yyyy-mm-dd] [hh:mm] [time zone] [time between rounds, like 1h30m]`",
+                                config.command("schedule"), config.command("schedule"), config.command("schedule"))).await?;
+                        }
+                    }
+                } else if text.starts_with(&config.command("forfeit")) {
+                    let split: Vec<&str> = text.splitn(3, ' ').collect();
+                    if split.len() == 3 {
+                        stop = forfeit(ctx, msg.channel_id, &manager, &bracket, &mut board, &game_messages, split[1], split[2]).await?;
+                    } else {
+                        msg.channel_id.say(&ctx.http, format!("You should use the format `{} [round #]-[game #] [forfeiting team's name]`", config.command("forfeit"))).await?;
+                    }
+                } else if text.starts_with(&config.command("withdraw")) {
+                    //The games the team has left are forfeited below, now and as they come up
+                    let name = text[config.command("withdraw").len()..].trim().to_owned();
+                    match participants::find_person(&teams, std::slice::from_ref(&name)) {
+                        Some(team) if bracket.withdraw(team) => {
+                            msg.channel_id.say(&ctx.http, format!("{} has been withdrawn from the tournament.", team)).await?;
+                            manager.edit_output(&mut board, &bracket.text()).await?;
+                        }
+                        Some(team) => {
+                            msg.channel_id.say(&ctx.http, format!("{} has already withdrawn.", team)).await?;
+                        }
+                        None => {
+                            msg.channel_id.say(&ctx.http, format!("{} is not in this tournament.", name)).await?;
+                        }
+                    }
+                } else if text == config.command("disputes") {
+                    if reports.disputes().is_empty() {
+                        msg.channel_id.say(&ctx.http, "There are no disputed results.").await?;
+                    } else {
+                        let mut out = String::from("**Disputed results**\n");
+                        for dispute in reports.disputes().iter() {
+                            out.push_str(&format!("{}: {} says {} did not win. {}\n", game_name(&dispute.game), dispute.team, dispute.winner, dispute.reason));
+                        }
+                        out.push_str(&format!("Use `{} [round #]-[game #] [winner's name]` to settle one.", config.command("declare")));
+                        msg.channel_id.say(&ctx.http, out).await?;
+                    }
+                } else if text == config.command("stop") {
+                    msg.channel_id.say(&ctx.http,"Tournament has been ended.").await?;
+                    stop = true;
+                } else if text.starts_with(&config.command("declare")) {
+                    let split: Vec<&str> = text.splitn(3, ' ').collect();
+                    if split.len() == 3 {
+                        stop = declare(ctx, msg.channel_id, &manager, &bracket, &mut board, &game_messages, split[1], split[2]).await?;
+                        post_ready_games(ctx, msg.channel_id, &session, &bracket, &mut game_messages).await?;
+                    } else {
+                        msg.channel_id.say(&ctx.http,format!("You should use the format `{} [round #]-[game #] [winner's name]`", config.command("declare"))).await?;
+                    }
+                } else if text.starts_with(&config.command("coorganizer")) {
+                    //Only the person who started the tournament and people with the organizer role can add co-organizers
+                    if message.author.id == organizers.author || organizers.has_role(&message) {
+                        if message.mentions.is_empty() {
+                            msg.channel_id.say(&ctx.http, "Please mention the people to make co-organizers.").await?;
+                        } else {
+                            for user in message.mentions.iter() {
+                                organizers.co_organizers.push(user.id);
+                                msg.channel_id.say(&ctx.http, format!("{} can now help run this tournament.", user)).await?;
+                            }
+                        }
                     } else {
-                        msg.channel_id.say(&ctx.http,format!("{}'s next game is {}", split[2], next_id)).await?;
+                        msg.channel_id.say(&ctx.http, "Only the person who started the tournament and organizers can add co-organizers.").await?;
                     }
-                } else {
-                    msg.channel_id.say(&ctx.http,"Please enter a valid game and team for this round.").await?;
                 }
+            }
+        }
 
-            } else {
-                msg.channel_id.say(&ctx.http,"You should use the format `!declare [round #]-[game #] [winner's name]`").await?;
+        //Games against teams that dropped out are won by forfeit
+        if !stop {
+            stop = declare_forfeits(ctx, msg.channel_id, &manager, &bracket, &mut board, &game_messages).await?;
+            post_ready_games(ctx, msg.channel_id, &session, &bracket, &mut game_messages).await?;
+        }
+        //Reports and disputes for games the organizers declared, corrected or took back are not needed anymore
+        let ready: Vec<String> = bracket.ready_games().into_iter().map(|(id, _, _)| id).collect();
+        for report in reports.keep_games(&ready) {
+            close_report(ctx, msg.channel_id, &report, "Settled by the organizers.").await?;
+        }
+        if stop {
+            answer = None;
+        } else {
+            answer = next_input(ctx, &config, &organizers, &mut clicks, wait_time(&config, &bracket), reports.next_deadline()).await;
+        }
+    }
+
+    //Taking the buttons off games that were not finished
+    buttons::unregister(&ctx.data, &session).await;
+    if let Some(tournaments) = ctx.data.write().await.get_mut::<RunningTournaments>() {
+        tournaments.remove(&organizers.session.id);
+    }
+
+    for (id, message_id) in game_messages.iter() {
+        if let Some((_, top, bottom)) = bracket.ready_games().into_iter().find(|(game, _, _)| game == id) {
+            buttons::edit(&ctx.http, msg.channel_id, *message_id, &game_text(id, &top, &bottom), &json!([])).await?;
+        }
+    }
+
+    finish(ctx, guild_id, msg.channel_id, &config, &manager, &organizers, &teams, &bracket, export).await
+}
+
+// Posts where everyone placed once the final has been played, saves the results so they can be exported now or later with `!export`,
+// and updates the server's ratings with every game that was played.
+#[allow(clippy::too_many_arguments)]
+async fn finish(ctx: &Context, guild_id: GuildId, channel_id: ChannelId, config: &GuildConfig, manager: &Manager<'_>, organizers: &Organizers,
+    teams: &[String], bracket: &Bracket, export: Option<ExportFormat>) -> Result<(), CommandError> {
+    let placements = bracket.placements();
+    if !placements.is_empty() {
+        manager.say_output(&format!("Final placements:\n{}", bracket::placements_text(&placements))).await?;
+    }
+
+    let (result, rated) = record_finished(guild_id, organizers.session.id, channel_id, config, teams, bracket)?;
+    if rated > 0 {
+        channel_id.say(&ctx.http, format!("Updated the ratings with {} games. Use `{}` to see them.", rated, config.command("leaderboard"))).await?;
+    }
+    if let Some(format) = export {
+        results::send(ctx, channel_id, &result, format).await?;
+    }
+    Ok(())
+}
+
+// Saves the results of a tournament and updates the server's ratings with its games, for both `!tournament` and `/tournament`.
+// Returns the saved results and how many games were rated.
+pub fn record_finished(guild_id: GuildId, session: u32, channel_id: ChannelId, config: &GuildConfig, teams: &[String], bracket: &Bracket)
+    -> io::Result<(SessionResult, usize)> {
+    let result = SessionResult::from_bracket(session, channel_id, teams, bracket);
+    results::save(guild_id, &result)?;
+    let mut ratings = Ratings::load(guild_id);
+    let rated = ratings.record_result(&result, config);
+    if rated > 0 {
+        ratings.save(guild_id)?;
+    }
+    Ok((result, rated))
+}
+
+// Gets how long to wait for the next input. When games are scheduled, the tournament waits until after the last one starts.
+fn wait_time(config: &GuildConfig, bracket: &Bracket) -> Duration {
+    let last = bracket.scheduled().iter().map(|(_, _, _, start)| *start).max().unwrap_or(0);
+    Duration::from_secs(last.saturating_sub(storage::now())) + config.tournament_timeout()
+}
+
+// Something that happened while a tournament is running.
+enum Input {
+    Message(Arc<Message>),
+    Click(Click),
+    // A reported result is due to count without being confirmed.
+    Tick,
+}
+
+// Waits for the next message from the organizers or click on a winner button, whichever comes first.
+// Participants can also send `!drop` to drop out and report, confirm and dispute results.
+// If deadline is set, stops waiting then so reported results can count.
+async fn next_input(ctx: &Context, config: &GuildConfig, organizers: &Organizers, clicks: &mut UnboundedReceiver<Click>, timeout: Duration, deadline: Option<u64>) -> Option<Input> {
+    let session = organizers.clone();
+    let filter_config = config.clone();
+    let filter = move |message: &Message, content: &str| session.accepts_input(message, content) || content == filter_config.command("drop") || is_report_command(&filter_config, content);
+    let until_deadline = Duration::from_secs(deadline.unwrap_or(0).saturating_sub(storage::now()));
+    tokio::select! {
+        reply = organizers.session.await_reply(ctx, timeout, filter) => reply.map(Input::Message),
+        click = clicks.recv() => click.map(Input::Click),
+        _ = tokio::time::delay_for(until_deadline), if deadline.is_some() => Some(Input::Tick),
+    }
+}
+
+// Checks if a message is a team reporting, confirming or disputing a result.
+fn is_report_command(config: &GuildConfig, content: &str) -> bool {
+    let command = content.split_whitespace().next().unwrap_or_default();
+    ["report", "accept", "dispute"].iter().any(|name| command == config.command(name))
+}
+
+// A team's `!report`, `!accept` or `!dispute`, or a click on a report's buttons.
+struct ReportRequest {
+    // The names the person who sent it could have been entered as.
+    names: Vec<String>,
+    // "report", "accept" or "dispute".
+    command: String,
+    game: String,
+    // The winner of a report or the reason for a dispute.
+    rest: String,
+}
+
+// Reports, confirms or disputes a result for the team of the person who asked. Returns true when the tournament has a champion.
+#[allow(clippy::too_many_arguments)]
+async fn team_report(ctx: &Context, config: &GuildConfig, manager: &Manager<'_>, bracket: &Bracket, board: &mut Message,
+    game_messages: &HashMap<String, MessageId>, reports: &mut Reports, teams: &[String], organizers: &Organizers, session: &str,
+    request: ReportRequest) -> Result<bool, CommandError> {
+    let channel_id = organizers.session.channel_id;
+    let team = match participants::find_person(teams, &request.names) {
+        Some(team) => team.clone(),
+        None => {
+            channel_id.say(&ctx.http, format!("{}, you are not in this tournament.", request.names[0])).await?;
+            return Ok(false);
+        }
+    };
+
+    match request.command.as_str() {
+        "report" => {
+            let ready = bracket.ready_games().into_iter().find(|(id, _, _)| *id == request.game);
+            let opponent = match bracket.opponent(&request.game, &team) {
+                Ok(opponent) if ready.is_some() => opponent,
+                Ok(_) => {
+                    channel_id.say(&ctx.http, "That game is not ready to be played yet.").await?;
+                    return Ok(false);
+                }
+                Err(error) => {
+                    channel_id.say(&ctx.http, error.to_string()).await?;
+                    return Ok(false);
+                }
+            };
+            //The winner can be given as "win", "loss" or a team's name
+            let winner = match request.rest.to_lowercase().as_str() {
+                "win" | "won" => Some(team.clone()),
+                "loss" | "lost" => Some(opponent.clone()),
+                _ => participants::find_person(&[team.clone(), opponent.clone()], std::slice::from_ref(&request.rest)).cloned(),
+            };
+            let winner = match winner {
+                Some(winner) => winner,
+                None => {
+                    channel_id.say(&ctx.http, format!("You should use the format `{} [round #]-[game #] [win/loss/winner's name]`", config.command("report"))).await?;
+                    return Ok(false);
+                }
+            };
+
+            if reports.is_disputed(&request.game) {
+                channel_id.say(&ctx.http, ReportError::Disputed.to_string()).await?;
+                return Ok(false);
             }
-            if stop {
-                answer = None
-            } else {
-                answer = msg.author.await_reply(&ctx).timeout(Duration::from_secs(6000)).await;
+
+            let deadline = storage::now() + config.report_confirm;
+            let text = format!("{} reported that {} won {}.\n{}, confirm or dispute it with the buttons, `{} {}` or `{} {} [reason]`. \
+                It counts {} if nobody disputes it.", team, winner, game_name(&request.game), opponent,
+                config.command("accept"), request.game, config.command("dispute"), request.game, schedule::relative(deadline));
+            let choices = [("Confirm".to_owned(), format!("declare:{}:{}:accept", session, request.game)),
+                ("Dispute".to_owned(), format!("declare:{}:{}:dispute", session, request.game))];
+            let message = buttons::send(&ctx.http, channel_id, &text, &buttons::button_row(&choices)).await?;
+            match reports.report(Report { game: request.game, reporter: team, opponent, winner, deadline, message: Some(message) }) {
+                Ok(Some(replaced)) => close_report(ctx, channel_id, &replaced, "Replaced by a new report.").await?,
+                Ok(None) => {}
+                Err(error) => {
+                    channel_id.say(&ctx.http, error.to_string()).await?;
+                }
             }
-        } else {
-            answer = msg.author.await_reply(&ctx).timeout(Duration::from_secs(6000)).await;
+            Ok(false)
+        }
+        "accept" => match reports.accept(&request.game, &team) {
+            Ok(report) => {
+                close_report(ctx, channel_id, &report, "Confirmed.").await?;
+                declare(ctx, channel_id, manager, bracket, board, game_messages, &report.game, &report.winner).await
+            }
+            Err(error) => {
+                channel_id.say(&ctx.http, error.to_string()).await?;
+                Ok(false)
+            }
+        },
+        _ => match reports.dispute(&request.game, &team, &request.rest) {
+            Ok(report) => {
+                close_report(ctx, channel_id, &report, "Disputed.").await?;
+                let ping = organizers.role.map(|role| format!("{} ", role.mention())).unwrap_or_default();
+                channel_id.say(&ctx.http, format!("{}Organizers, {} disputed {}'s report that {} won {}. {}\n\
+                    Use `{} [round #]-[game #] [winner's name]` to settle it or `{}` to see every dispute.", ping, team, report.reporter,
+                    report.winner, game_name(&report.game), request.rest, config.command("declare"), config.command("disputes"))).await?;
+                Ok(false)
+            }
+            Err(error) => {
+                channel_id.say(&ctx.http, error.to_string()).await?;
+                Ok(false)
+            }
+        },
+    }
+}
+
+// Takes the buttons off a report's message and says what happened to it.
+async fn close_report(ctx: &Context, channel_id: ChannelId, report: &Report, outcome: &str) -> Result<(), CommandError> {
+    if let Some(message_id) = report.message {
+        let text = format!("{} reported that {} won {}.\n{}", report.reporter, report.winner, game_name(&report.game), outcome);
+        buttons::edit(&ctx.http, channel_id, message_id, &text, &json!([])).await?;
+    }
+    Ok(())
+}
+
+// Declares the winner of a game, says what happens next and updates the bracket and the game's message.
+// Returns true when the tournament is over.
+#[allow(clippy::too_many_arguments)]
+async fn declare(ctx: &Context, channel_id: ChannelId, manager: &Manager<'_>, bracket: &Bracket, board: &mut Message,
+    game_messages: &HashMap<String, MessageId>, game: &str, winner: &str) -> Result<bool, CommandError> {
+    let ready = bracket.ready_games().into_iter().find(|(id, _, _)| id == game);
+    let result = bracket.declare(game, winner).map(|declared| (winner.to_owned(), declared));
+    show_result(ctx, channel_id, manager, bracket, board, game_messages, game, ready, result).await
+}
+
+// Declares the opponent of a team that forfeits a game the winner, like declare.
+#[allow(clippy::too_many_arguments)]
+async fn forfeit(ctx: &Context, channel_id: ChannelId, manager: &Manager<'_>, bracket: &Bracket, board: &mut Message,
+    game_messages: &HashMap<String, MessageId>, game: &str, team: &str) -> Result<bool, CommandError> {
+    let ready = bracket.ready_games().into_iter().find(|(id, _, _)| id == game);
+    let result = bracket.forfeit(game, team);
+    show_result(ctx, channel_id, manager, bracket, board, game_messages, game, ready, result).await
+}
+
+// Says what happens after a result is declared and updates the bracket and the game's message.
+// Returns true when the tournament is over, which is once the final and the third place game have been played.
+#[allow(clippy::too_many_arguments)]
+async fn show_result(ctx: &Context, channel_id: ChannelId, manager: &Manager<'_>, bracket: &Bracket, board: &mut Message,
+    game_messages: &HashMap<String, MessageId>, game: &str, ready: Option<(String, String, String)>,
+    result: Result<(String, Declared), DeclareError>) -> Result<bool, CommandError> {
+    let winner = match result {
+        Ok((winner, Declared::NextGame(next_id))) => {
+            channel_id.say(&ctx.http,format!("{}'s next game is {}", winner, next_id)).await?;
+            winner
+        }
+        Ok((winner, Declared::Champion)) => {
+            channel_id.say(&ctx.http,format!("{} won the tournament!", winner)).await?;
+            if !bracket.is_finished() {
+                channel_id.say(&ctx.http, format!("The tournament ends once the {} is played.", game_name(bracket::THIRD_PLACE))).await?;
+            }
+            winner
+        }
+        Ok((winner, Declared::ThirdPlace)) => {
+            channel_id.say(&ctx.http,format!("{} took third place!", winner)).await?;
+            winner
+        }
+        Err(error) => {
+            channel_id.say(&ctx.http,error.to_string()).await?;
+            return Ok(false);
+        }
+    };
+
+    manager.edit_output(board, &bracket.text()).await?;
+    if let (Some(message_id), Some((_, top, bottom))) = (game_messages.get(game), ready) {
+        let how = if bracket.is_forfeit(game) { " by forfeit" } else { "" };
+        let text = format!("{}\n{} won{}.", game_text(game, &top, &bottom), winner, how);
+        buttons::edit(&ctx.http, channel_id, *message_id, &text, &json!([])).await?;
+    }
+    Ok(bracket.is_finished())
+}
+
+// Declares the winner of every ready game where the other team dropped out. Returns true when the tournament is over.
+async fn declare_forfeits(ctx: &Context, channel_id: ChannelId, manager: &Manager<'_>, bracket: &Bracket, board: &mut Message,
+    game_messages: &HashMap<String, MessageId>) -> Result<bool, CommandError> {
+    //Winning by forfeit can make another game ready that is also a forfeit
+    let mut forfeits = bracket.forfeits();
+    while !forfeits.is_empty() {
+        for (id, winner, withdrawn) in forfeits {
+            channel_id.say(&ctx.http, format!("{} wins {} by forfeit because {} withdrew.", winner, game_name(&id), withdrawn)).await?;
+            if forfeit(ctx, channel_id, manager, bracket, board, game_messages, &id, &withdrawn).await? {
+                return Ok(true);
+            }
+        }
+        forfeits = bracket.forfeits();
+    }
+    Ok(false)
+}
+
+// Changes the winner of a declared game, says which results were taken back and updates the bracket and game messages.
+#[allow(clippy::too_many_arguments)]
+async fn correct(ctx: &Context, channel_id: ChannelId, manager: &Manager<'_>, bracket: &Bracket, board: &mut Message,
+    game_messages: &mut HashMap<String, MessageId>, game: &str, winner: &str) -> Result<(), CommandError> {
+    let undone = match bracket.correct(game, winner) {
+        Ok(undone) => undone,
+        Err(error) => {
+            channel_id.say(&ctx.http, error.to_string()).await?;
+            return Ok(());
         }
+    };
+    let mut out = format!("{} is now the winner of {}.", winner, game_name(game));
+    if !undone.is_empty() {
+        let names: Vec<String> = undone.iter().map(|id| game_name(id)).collect();
+        out.push_str(&format!("\nThe results of {} were taken back.", names.join(", ")));
     }
+    channel_id.say(&ctx.http, out).await?;
+    manager.edit_output(board, &bracket.text()).await?;
 
+    let mut changed = undone;
+    changed.push(game.to_owned());
+    remove_game_messages(ctx, channel_id, bracket, game_messages, &changed).await
+}
+
+// Deletes the messages of games whose results were taken back and of the games they led to, so they can be posted again.
+async fn remove_game_messages(ctx: &Context, channel_id: ChannelId, bracket: &Bracket, game_messages: &mut HashMap<String, MessageId>, games: &[String]) -> Result<(), CommandError> {
+    let mut ids: Vec<String> = games.to_vec();
+    ids.extend(games.iter().filter_map(|id| bracket.next_id(id)));
+    ids.extend(games.iter().filter_map(|id| bracket.loser_id(id)));
+    for id in ids.iter() {
+        if let Some(message_id) = game_messages.remove(id) {
+            channel_id.delete_message(&ctx.http, message_id).await?;
+        }
+    }
     Ok(())
 }
 
-//Fills up all the tournament games with their next games.
-fn fill_tournament(current_round: &mut LinkedHashMap<String, Arc<Mutex<Game>>>, round_num: u32, all_games: &mut LinkedHashMap<String, Arc<Mutex<Game>>>) {
-    let round_num = round_num + 1;
-    let mut next_round: LinkedHashMap<String, Arc<Mutex<Game>>> = LinkedHashMap::new();
-    let len = current_round.len();
-    if len > 1 {
-        for (index, (_, game)) in current_round.iter_mut().enumerate() {
-            let next_id = format!("{}-{}", round_num, index / 2 + 1);
-
-            if index % 2 == 0 {
-                let next_game = Arc::new(Mutex::new(Game::new(next_id.clone(), "".to_owned(), "".to_owned(), None)));
-                all_games.insert(next_id.clone(), Arc::clone(&next_game));
-                next_round.insert(next_id.clone(), Arc::clone(&next_game));
-            }
-            game.lock().unwrap().set_next(Some(Arc::clone(&next_round[&format!("{}-{}", round_num, index / 2 + 1)])));
-        }
-        fill_tournament(&mut next_round, round_num, all_games);
-    }
-}
-
-// //Gets rid of games that will only have 1 team in them.
-// fn trim_tournament(round: &mut LinkedHashMap<String, Arc<Mutex<Game>>>, all_games: &mut LinkedHashMap<String, Arc<Mutex<Game>>>) {
-//     let mut next_round: LinkedHashMap<String, Arc<Mutex<Game>>> = LinkedHashMap::new();
-//     if round.len() > 2 && round.len() % 2 == 1 {
-//         let keys: Vec<String> = round.keys().map(|k| k.to_string()).collect();
-//         let size = keys.len() as u32;
-//         let log2size = 32 - (size - 1).leading_zeros() - 1;
-//         let game_arc = Arc::clone(&round.get(&keys[keys.len() - 1]).unwrap());
-//         let game = game_arc.lock().unwrap();
-//         let next = Arc::clone(game.next_game.as_ref().unwrap());
-//         let next_id = g.next_game.as_ref().unwrap().lock().unwrap().id.clone();
-//         next.as_ref().lock().unwrap().id = next_id.clone();
-//         // round.remove(&keys[keys.len() - 1]);
-//         // round.insert(next_id.clone(), next);
-//         // keys.remove(keys.len() - 1);
-//         // keys.push(next_id.clone());
-//         for key in keys.iter() {
-//             next_round.insert(t.next_game.as_ref().unwrap().lock().unwrap().id.clone(), Arc::clone(t.next_game.as_ref().unwrap()));
-//         }
-//         trim_tournament(&mut next_round, all_games);
-//     } else if round.len() == 2 {
-//         let keys: Vec<String> = round.keys().map(|s| s.to_string()).collect();
-//         let id = keys[1].clone();
-//         let game_arc = Arc::clone(round.get(&id).unwrap());
-//         let mut game = game_arc.as_ref().lock().unwrap();
-//         let top = game.top_team.clone();
-//         let bottom = game.bottom_team.clone();
-//         let next_id = game.next_game.as_ref().unwrap().lock().unwrap().id.clone();
-//         if top != "" && bottom == "" {
-//             round.remove(&id);
-//             round.insert(next_id.clone(), Arc::clone(game.next_game.as_mut().unwrap()));
-//         }
-//     }
-// }
+// Posts a message with a button for each team for the games that are ready and have not been posted yet.
+async fn post_ready_games(ctx: &Context, channel_id: ChannelId, session: &str, bracket: &Bracket, game_messages: &mut HashMap<String, MessageId>) -> Result<(), CommandError> {
+    for (id, top, bottom) in bracket.ready_games() {
+        if game_messages.contains_key(&id) {
+            continue;
+        }
+        let mut choices: Vec<(String, String)> = Vec::new();
+        for (team, slot) in [(&top, "top"), (&bottom, "bottom")].iter() {
+            if !team.is_empty() {
+                choices.push((buttons::label(&ctx.cache, team).await, format!("declare:{}:{}:{}", session, id, slot)));
+            }
+        }
+        let message_id = buttons::send(&ctx.http, channel_id, &game_text(&id, &top, &bottom), &buttons::button_row(&choices)).await?;
+        game_messages.insert(id, message_id);
+    }
+    Ok(())
+}
+
+// Makes the text of a game's message.
+fn game_text(id: &str, top: &str, bottom: &str) -> String {
+    match (top.is_empty(), bottom.is_empty()) {
+        (false, false) => format!("**{}**: {} vs {}", game_name(id), top, bottom),
+        _ => format!("**{}**: {}{} (no opponent)", game_name(id), top, bottom),
+    }
+}
+
+// Makes the direct message for each team with the first game they have to play and who they play.
+async fn first_game_dms(ctx: &Context, guild_id: GuildId, channel_id: ChannelId, bracket: &Bracket) -> Vec<(String, String)> {
+    let guild_name = guild_id.name(&ctx.cache).await.unwrap_or_default();
+    let ready = bracket.ready_games();
+    bracket.teams.iter()
+        .map(|team| {
+            let mut text = format!("You are in a tournament in {}, run in {}.\n", guild_name, channel_id.mention());
+            //Imported brackets can already have results, so this is the first game that has not been played
+            match bracket.path(team).into_iter().find(|game| game.winner.is_empty()) {
+                Some(game) if !game.opponents.is_empty() => text.push_str(&format!("Your first game is {} against {}.", game_name(&game.id), game.opponent_text())),
+                Some(game) if ready.iter().any(|(id, _, _)| *id == game.id) => text.push_str(&format!("You have no opponent in {}, so you move on once it is declared.", game_name(&game.id))),
+                Some(game) => text.push_str(&format!("Your first game is {}, and you are waiting to find out who you play.", game_name(&game.id))),
+                None => text.push_str("You have no games left to play."),
+            }
+            (team.clone(), text)
+        })
+        .collect()
+}
@@ -1,11 +1,20 @@
-use std::{collections::HashSet, env};
+use std::{collections::{HashMap, HashSet}, env, sync::Arc};
 
-use serenity::{async_trait, framework::{StandardFramework, standard::{Args, CommandGroup, CommandResult, HelpOptions, help_commands, macros::{group, help}}}, model::{channel::Message, gateway::Ready, id::UserId}, prelude::*};
+use serenity::{async_trait, framework::{StandardFramework, standard::{Args, CommandGroup, CommandResult, HelpOptions, help_commands, macros::{group, help, hook}}}, model::{channel::Message, gateway::Ready, id::UserId}, prelude::*};
 
 mod commands;
 
 use commands::{
+    buttons::{self, ButtonSessions},
+    challenge::*,
+    config::*,
+    draft::*,
+    export::*,
     group::*,
+    interactions::{self, Interactions},
+    leaderboard::*,
+    sessions::*,
+    status::*,
     team::*,
     tournament::*,
 };
@@ -17,6 +26,18 @@ impl EventHandler for Handler {
     async fn ready(&self, _: Context, ready: Ready) {
         println!("{} is connected!", ready.user.name);
     }
+
+    // Sends button clicks that come through the gateway to the session that posted the buttons.
+    async fn unknown(&self, ctx: Context, name: String, raw: serde_json::Value) {
+        if name != "INTERACTION_CREATE" {
+            return;
+        }
+        if let Some(response) = buttons::route(&ctx.data, &raw).await {
+            if let Err(why) = buttons::respond(&raw, &response).await {
+                println!("Error responding to interaction: {:?}", why);
+            }
+        }
+    }
 }
 
 #[help]
@@ -32,15 +53,24 @@ async fn my_help(
     Ok(())
 }
 
+// Gets the command prefix set for the server the message was sent in, or ! in direct messages.
+#[hook]
+async fn guild_prefix(ctx: &Context, msg: &Message) -> Option<String> {
+    match msg.guild_id {
+        Some(guild_id) => Some(commands::config::get(ctx, guild_id).await.prefix),
+        None => Some("!".to_owned()),
+    }
+}
+
 #[group]
-#[commands(group, team, tournament)]
+#[commands(group, team, tournament, draft, config, show_bracket, next, pending, team_history, sessions, export, rating, leaderboard, ladder, challenge)]
 struct General;
 
 #[tokio::main]
 async fn main() {
-    // framework sets ! to be the command prefix and sets General as the command gorup.
+    // framework looks up each server's command prefix (! by default) and sets General as the command gorup.
     let framework = StandardFramework::new()
-        .configure(|c| c.prefix("!"))
+        .configure(|c| c.prefix("").dynamic_prefix(guild_prefix))
         .group(&GENERAL_GROUP)
         .help(&MY_HELP);
 
@@ -52,9 +82,28 @@ async fn main() {
     let mut client = Client::builder(&token)
         .event_handler(Handler)
         .framework(framework)
+        .type_map_insert::<GuildConfigs>(HashMap::new())
+        .type_map_insert::<ButtonSessions>(HashMap::new())
+        .type_map_insert::<RunningTournaments>(HashMap::new())
+        .type_map_insert::<ActiveSessions>(Arc::new(std::sync::Mutex::new(Registry::default())))
         .await
         .expect("Err creating client");
 
+    // Starts the interactions endpoint for slash commands if INTERACTIONS_PORT and DISCORD_PUBLIC_KEY are set.
+    // The commands are registered with Discord when DISCORD_APPLICATION_ID is also set.
+    if let (Ok(port), Ok(public_key)) = (env::var("INTERACTIONS_PORT"), env::var("DISCORD_PUBLIC_KEY")) {
+        let port: u16 = port.parse().expect("INTERACTIONS_PORT should be a port number");
+        let endpoint = Interactions::new(&public_key, Arc::clone(&client.data), Arc::clone(&client.cache_and_http.cache))
+            .expect("DISCORD_PUBLIC_KEY should be a hex encoded key");
+        tokio::spawn(interactions::serve(port, Arc::new(endpoint)));
+
+        if let Ok(application_id) = env::var("DISCORD_APPLICATION_ID") {
+            if let Err(why) = interactions::register_commands(&application_id, &token).await {
+                println!("Error registering slash commands: {:?}", why);
+            }
+        }
+    }
+
     // Starts the client with a single shard
     if let Err(why) = client.start_autosharded().await {
         println!("Client error: {:?}", why);